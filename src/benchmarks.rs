@@ -0,0 +1,122 @@
+//! Timing benchmarks for indexing and code completion over synthetic workspaces. These are
+//! ignored tests, since they take a while and only make sense in release builds:
+//!
+//! `cargo test --release benchmarks -- --ignored --nocapture --test-threads=1`
+
+use std::time::{Duration, Instant};
+
+use tower_lsp::lsp_types;
+
+use crate::dataflex_document::DataFlexDocument;
+use crate::index;
+
+/// Generates a package with `class_count` classes, each with `method_count` procedures and a
+/// property, where every class inherits from the first class of the previous file.
+pub fn synthetic_package(file_number: usize, class_count: usize, method_count: usize) -> String {
+    let mut source = String::new();
+    if file_number > 0 {
+        source.push_str(&format!("Use File{}.pkg\n\n", file_number - 1));
+    }
+    for class_number in 0..class_count {
+        let superclass = if file_number > 0 {
+            format!("cClass{}_0", file_number - 1)
+        } else {
+            String::from("cObject")
+        };
+        source.push_str(&format!(
+            "Class cClass{file_number}_{class_number} is a {superclass}\n"
+        ));
+        source.push_str("    Procedure Construct_Object\n");
+        source.push_str("        Forward Send Construct_Object\n");
+        source.push_str(&format!(
+            "        Property Integer piValue{file_number}_{class_number} 0\n"
+        ));
+        source.push_str("    End_Procedure\n\n");
+        for method_number in 0..method_count {
+            source.push_str(&format!(
+                "    Procedure DoSomething{file_number}_{class_number}_{method_number} Integer iValue\n"
+            ));
+            source.push_str("        String sName\n");
+            source.push_str("        Move iValue to sName\n");
+            source.push_str("    End_Procedure\n\n");
+        }
+        source.push_str("End_Class\n\n");
+    }
+    source
+}
+
+/// Indexes `file_count` synthetic packages into a new index.
+pub fn synthetic_index(
+    file_count: usize,
+    class_count: usize,
+    method_count: usize,
+) -> index::IndexRef {
+    let index = index::IndexRef::make_test_index_ref();
+    for file_number in 0..file_count {
+        index::Indexer::index_test_content(
+            &synthetic_package(file_number, class_count, method_count),
+            format!("File{file_number}.pkg").into(),
+            &index,
+        );
+    }
+    index
+}
+
+/// Runs the closure `iterations` times and prints the mean and fastest run.
+pub fn measure(name: &str, iterations: usize, mut f: impl FnMut()) -> Duration {
+    let timings: Vec<Duration> = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    let mean = timings.iter().sum::<Duration>() / iterations as u32;
+    let fastest = timings.iter().min().copied().unwrap_or_default();
+    println!("{name}: mean {mean:?}, fastest {fastest:?} ({iterations} iterations)");
+    mean
+}
+
+#[test]
+#[ignore]
+fn bench_index_files() {
+    let sources: Vec<String> = (0..200).map(|i| synthetic_package(i, 5, 10)).collect();
+    measure("index 200 files", 5, || {
+        let index = index::IndexRef::make_test_index_ref();
+        for (file_number, source) in sources.iter().enumerate() {
+            index::Indexer::index_test_content(
+                source,
+                format!("File{file_number}.pkg").into(),
+                &index,
+            );
+        }
+    });
+}
+
+#[test]
+#[ignore]
+fn bench_method_completion() {
+    let index = synthetic_index(200, 5, 10);
+    let content = "Use File199.pkg\n\nObject oTest is a cClass199_0\n    Procedure Test\n        Send Do\n    End_Procedure\nEnd_Object\n";
+    let doc = DataFlexDocument::new("Test.vw".into(), content, index);
+    let position = lsp_types::Position::new(4, 15);
+    assert!(doc.code_completion(position, false).is_some());
+
+    measure("method completion over 10000 methods", 20, || {
+        doc.code_completion(position, false);
+    });
+}
+
+#[test]
+#[ignore]
+fn bench_class_completion() {
+    let index = synthetic_index(200, 5, 10);
+    let content = "Object oTest is a cCl\nEnd_Object\n";
+    let doc = DataFlexDocument::new("Test.vw".into(), content, index);
+    let position = lsp_types::Position::new(0, 21);
+    assert!(doc.code_completion(position, false).is_some());
+
+    measure("class completion over 1000 classes", 20, || {
+        doc.completion_list(position, false, 1_000, false);
+    });
+}
@@ -3,23 +3,32 @@ use std::path::PathBuf;
 use tower_lsp::lsp_types;
 use tree_sitter::{InputEdit, Point, Tree};
 
-use crate::{dataflex_parser::DataFlexTreeParser, index};
+use crate::{dataflex_parser::DataFlexTreeParser, index, settings::Settings};
 use document_context::DocumentContext;
 use reference_resolver::ReferenceResolver;
+use references::ReferenceKind;
+pub use references::ReferenceTarget;
 use streaming_iterator::StreamingIterator;
 use tree_cursor::DataFlexTreeCursor;
 
 mod code_completion;
 mod code_lens;
+mod diagnostics;
 mod document_context;
+mod folding_ranges;
+mod formatting;
 mod line_map;
 mod parameter_info;
 mod reference_resolver;
+mod references;
 mod scope_balancer;
+mod selection_ranges;
 mod symbol_declaration;
 mod syntax_map;
 mod tree_cursor;
 
+pub type CompletionContextKind = DocumentContext;
+
 pub struct DataFlexDocument {
     file_path: PathBuf,
     line_map: line_map::LineMap,
@@ -27,6 +36,8 @@ pub struct DataFlexDocument {
     index: index::IndexRef,
     tree: Option<Tree>,
     syntax_map: Option<syntax_map::SyntaxMap>,
+    semantic_tokens_result_id: u64,
+    semantic_tokens_result: Option<(u64, Vec<lsp_types::SemanticToken>)>,
 }
 
 impl DataFlexDocument {
@@ -38,11 +49,17 @@ impl DataFlexDocument {
             index: index_ref,
             tree: None,
             syntax_map: None,
+            semantic_tokens_result_id: 0,
+            semantic_tokens_result: None,
         };
         doc.update();
         doc
     }
 
+    pub fn set_index(&mut self, index_ref: index::IndexRef) {
+        self.index = index_ref;
+    }
+
     pub fn tree(&self) -> Option<&Tree> {
         self.tree.as_ref()
     }
@@ -58,7 +75,59 @@ impl DataFlexDocument {
 
     pub fn symbol_at_position(&self, position: Point) -> Option<index::SymbolName> {
         self.node_at_position(position)
-            .map(|node| self.line_map.text_for_node(&node).into())
+            .map(|node| self.identifier_text_for_node(&node).into())
+    }
+
+    fn identifier_text_for_node(&self, node: &tree_sitter::Node) -> String {
+        let (start, end) = self
+            .line_map
+            .identifier_range(node.start_position(), node.end_position());
+        self.line_map.text_in_range(start, end)
+    }
+
+    /// The range of the identifier containing or ending at the position, or of the file path in a
+    /// `Use` statement, since file names contain dots.
+    fn identifier_at(&self, position: Point) -> Option<std::ops::Range<Point>> {
+        if let Some(mut cursor) = self.cursor()
+            && cursor.goto_leaf_node_at_or_before_point(position)
+            && cursor.is_file_path()
+            && cursor.node().end_position() >= position
+        {
+            return Some(cursor.node().start_position()..cursor.node().end_position());
+        }
+        let (start, end) = self.line_map.identifier_range(position, position);
+        (start != end).then_some(start..end)
+    }
+
+    fn point_for_position(&self, position: lsp_types::Position) -> Point {
+        // Clients may send positions past the end of a line or the document when racing with
+        // edits, so clamp them to the current content.
+        let row = (position.line as usize).min(self.line_map.line_count().saturating_sub(1));
+        Point {
+            row,
+            column: self
+                .line_map
+                .utf16_column_to_byte(row, position.character as usize),
+        }
+    }
+
+    pub fn node_kind_at(&self, position: lsp_types::Position) -> Option<&str> {
+        self.node_at_position(self.point_for_position(position))
+            .map(|node| node.kind())
+    }
+
+    /// The names and types of the parameters and local variables of the method enclosing the
+    /// point, or nothing outside a method.
+    pub fn locals_in_scope(&self, point: Point) -> Vec<(index::SymbolName, String)> {
+        ReferenceResolver::new(self)
+            .local_variables(point)
+            .map(|variable| {
+                (
+                    variable.symbol_path.name().clone(),
+                    variable.data_type.to_string(),
+                )
+            })
+            .collect()
     }
 
     pub fn text_content(&self) -> String {
@@ -66,6 +135,7 @@ impl DataFlexDocument {
     }
 
     fn update(&mut self) {
+        let old_tree = self.tree.clone();
         self.tree = self.parser.parse_with_options(
             &mut |_, point| {
                 self.line_map
@@ -76,10 +146,38 @@ impl DataFlexDocument {
             self.tree.as_ref(),
             None,
         );
-        self.update_syntax_map();
+
+        // After an incremental parse, only the edited lines and the rows whose syntax changed
+        // need new tokens.
+        if let Some(old_tree) = old_tree
+            && let Some(tree) = self.tree.as_ref()
+            && self.line_map.line_count() <= Settings::get().max_semantic_token_lines
+            && let Some(mut syntax_map) = self.syntax_map.take()
+            && syntax_map.line_count() == self.line_map.line_count()
+        {
+            let changed_rows: Vec<std::ops::Range<usize>> = old_tree
+                .changed_ranges(tree)
+                .map(|range| range.start_point.row..range.end_point.row + 1)
+                .collect();
+            syntax_map.update_lines(self, changed_rows);
+            self.syntax_map = Some(syntax_map);
+        } else {
+            self.update_syntax_map();
+        }
     }
 
     pub fn update_syntax_map(&mut self) {
+        let max_lines = Settings::get().max_semantic_token_lines;
+        if self.line_map.line_count() > max_lines {
+            log::debug!(
+                "Suppressing semantic tokens for {:?}: {} lines exceeds limit of {}",
+                self.file_path,
+                self.line_map.line_count(),
+                max_lines
+            );
+            self.syntax_map = None;
+            return;
+        }
         self.syntax_map = Some(syntax_map::SyntaxMap::new(self));
     }
 
@@ -100,21 +198,17 @@ impl DataFlexDocument {
                 self.tree = None;
                 continue;
             };
-            // TODO: Convert UTF-16 to UTF-8 range.
-            let start = Point {
-                row: range.start.line as usize,
-                column: range.start.character as usize,
-            };
-            let end = Point {
-                row: range.end.line as usize,
-                column: range.end.character as usize,
-            };
+            let start = self.point_for_position(range.start);
+            let end = self.point_for_position(range.end);
             let start_byte = self.line_map.offset_at_point(start);
             let old_end_byte = self.line_map.offset_at_point(end);
             let new_end_byte = start_byte + change.text.len();
 
             self.line_map.replace_range(start, end, &change.text);
             let new_end_position = self.line_map.point_at_offset(new_end_byte);
+            if let Some(syntax_map) = self.syntax_map.as_mut() {
+                syntax_map.edit_lines(start.row..end.row + 1, new_end_position.row + 1 - start.row);
+            }
 
             if let Some(tree) = self.tree.as_mut() {
                 tree.edit(&InputEdit {
@@ -132,9 +226,7 @@ impl DataFlexDocument {
         if changes.len() == 1
             && let Some(change) = changes.first()
             && scope_balancer::ScopeBalancer::is_auto_close_scope_trigger(&change.text)
-            && let Some(position) = change
-                .range
-                .map(|range| Point::new(range.end.line as usize, range.end.character as usize))
+            && let Some(position) = change.range.map(|range| self.point_for_position(range.end))
         {
             scope_balancer::ScopeBalancer::auto_close_scope(self, position, &change.text).map(
                 |edit| {
@@ -158,22 +250,80 @@ impl DataFlexDocument {
     }
 
     pub fn semantic_tokens_full(&self) -> Option<Vec<lsp_types::SemanticToken>> {
+        if self.line_map.line_count() > Settings::get().max_semantic_token_lines {
+            return Some(Vec::new());
+        }
         let syntax_map = self.syntax_map.as_ref()?;
         Some(syntax_map.get_all_tokens())
     }
 
+    /// The semantic tokens of the lines in the range.
+    pub fn semantic_tokens_range(
+        &self,
+        range: lsp_types::Range,
+    ) -> Option<Vec<lsp_types::SemanticToken>> {
+        if self.line_map.line_count() > Settings::get().max_semantic_token_lines {
+            return Some(Vec::new());
+        }
+        let syntax_map = self.syntax_map.as_ref()?;
+        Some(syntax_map.get_tokens_for_lines(range.start.line as usize..=range.end.line as usize))
+    }
+
+    /// All semantic tokens, with a result id that a later delta request can refer to.
+    pub fn semantic_tokens(&mut self) -> Option<lsp_types::SemanticTokens> {
+        let tokens = self.semantic_tokens_full()?;
+        Some(lsp_types::SemanticTokens {
+            result_id: Some(self.store_semantic_tokens_result(tokens.clone())),
+            data: tokens,
+        })
+    }
+
+    /// The edits from the tokens returned with `previous_result_id` to the current tokens, or all
+    /// tokens when that result is no longer known.
+    pub fn semantic_tokens_delta(
+        &mut self,
+        previous_result_id: &str,
+    ) -> Option<lsp_types::SemanticTokensFullDeltaResult> {
+        let previous_tokens = self
+            .semantic_tokens_result
+            .take()
+            .filter(|(result_id, _)| result_id.to_string() == previous_result_id)
+            .map(|(_, tokens)| tokens);
+        let Some(previous_tokens) = previous_tokens else {
+            return self
+                .semantic_tokens()
+                .map(lsp_types::SemanticTokensFullDeltaResult::Tokens);
+        };
+        let tokens = self.semantic_tokens_full()?;
+        let edits = syntax_map::SyntaxMap::token_edits(&previous_tokens, &tokens);
+        Some(lsp_types::SemanticTokensFullDeltaResult::TokensDelta(
+            lsp_types::SemanticTokensDelta {
+                result_id: Some(self.store_semantic_tokens_result(tokens)),
+                edits,
+            },
+        ))
+    }
+
+    fn store_semantic_tokens_result(&mut self, tokens: Vec<lsp_types::SemanticToken>) -> String {
+        self.semantic_tokens_result_id += 1;
+        self.semantic_tokens_result = Some((self.semantic_tokens_result_id, tokens));
+        self.semantic_tokens_result_id.to_string()
+    }
+
     pub fn find_definition(
         &self,
         position: lsp_types::Position,
     ) -> Option<Vec<lsp_types::Location>> {
         log::trace!("find_definition {:?}", position);
-        let position = Point {
-            row: position.line as usize,
-            column: position.character as usize,
-        };
+        let position = self.point_for_position(position);
         let Some(context) = DocumentContext::context(self, position) else {
-            log::trace!("no context");
-            return None;
+            // The name in a class header has no context, but resolves to the class itself.
+            let symbols = ReferenceResolver::new(self).resolve_class_declaration(position);
+            if symbols.is_empty() {
+                log::trace!("no context");
+                return None;
+            }
+            return Some(symbols.iter().map(lsp_types::Location::from).collect());
         };
         log::trace!("context {:?}", context);
 
@@ -196,26 +346,17 @@ impl DataFlexDocument {
                 })),
             )]
         } else if context.is_file_reference()
-            && let Some(file_ref) = self.node_at_position(position).map(|node| {
-                index::IndexFileRef::from(&PathBuf::from(self.line_map.text_for_node(&node)))
-            })
+            && let Some(path) = reference_resolver.resolve_file_dependency(position)
         {
-            self.index
-                .get()
-                .find_file_path(&file_ref)
-                .map(|path| {
-                    lsp_types::Location::new(
-                        lsp_types::Url::from_file_path(path).unwrap(),
-                        lsp_types::Range::default(),
-                    )
-                })
-                .into_iter()
-                .collect()
+            vec![lsp_types::Location::new(
+                lsp_types::Url::from_file_path(path).unwrap(),
+                lsp_types::Range::default(),
+            )]
         } else {
-            let symbols = reference_resolver.resolve_reference(context, position);
-            symbols
-                .map(|qualified_symbol| lsp_types::Location::from(&qualified_symbol))
-                .collect()
+            // Release the index read lock before building the locations.
+            let symbols = reference_resolver.resolve_symbols(context, position);
+            drop(reference_resolver);
+            symbols.iter().map(lsp_types::Location::from).collect()
         };
 
         if !locations.is_empty() {
@@ -230,40 +371,119 @@ impl DataFlexDocument {
         position: lsp_types::Position,
         auto_complete: bool,
     ) -> Option<Vec<lsp_types::CompletionItem>> {
-        let position = Point {
-            row: position.line as usize,
-            column: position.character as usize,
-        };
+        let position = self.point_for_position(position);
+
+        code_completion::CodeCompletion::code_completion(self, position, auto_complete)
+            .map(|completions| self.lsp_completion_items(position, completions))
+    }
 
-        let completions =
-            code_completion::CodeCompletion::code_completion(self, position, auto_complete);
-        completions.map(|mut completions| {
-            completions
-                .drain(..)
-                .map(|item| lsp_types::CompletionItem {
-                    label: item.label,
-                    kind: Some(lsp_types::CompletionItemKind::from(item.kind)),
-                    label_details: item.details.map(|details| {
-                        lsp_types::CompletionItemLabelDetails {
-                            detail: Some(details),
-                            description: None,
-                        }
+    fn lsp_completion_items(
+        &self,
+        position: Point,
+        completions: Vec<code_completion::CompletionItem>,
+    ) -> Vec<lsp_types::CompletionItem> {
+        let replace_range = self.identifier_at(position).map(|r| self.lsp_range(&r));
+        completions
+            .into_iter()
+            .map(|item| lsp_types::CompletionItem {
+                // Clients sort by label unless a sort text is given, so prefix local items to list
+                // them first.
+                sort_text: item.local.then(|| format!("0{}", item.label)),
+                text_edit: replace_range.map(|range| {
+                    lsp_types::CompletionTextEdit::Edit(lsp_types::TextEdit {
+                        range,
+                        new_text: item.insert_text.clone().unwrap_or(item.label.clone()),
+                    })
+                }),
+                label: item.label,
+                kind: Some(lsp_types::CompletionItemKind::from(item.kind)),
+                label_details: item
+                    .details
+                    .map(|details| lsp_types::CompletionItemLabelDetails {
+                        detail: Some(details),
+                        description: None,
                     }),
-                    insert_text: item.insert_text,
-                    ..Default::default()
-                })
-                .collect()
+                insert_text: item.insert_text,
+                data: item
+                    .symbol
+                    .and_then(|symbol| serde_json::to_value(symbol).ok()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// The documentation of a completion item, looked up from the symbol in the item data when the
+    /// client resolves the item rather than for every item in the list.
+    pub fn completion_item_documentation(
+        index: &index::Index,
+        data: serde_json::Value,
+    ) -> Option<lsp_types::Documentation> {
+        let symbol_ref = serde_json::from_value::<index::IndexSymbolRef>(data).ok()?;
+        code_completion::CodeCompletion::documentation(index, &symbol_ref).map(|documentation| {
+            lsp_types::Documentation::MarkupContent(lsp_types::MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value: documentation,
+            })
+        })
+    }
+
+    /// Code completion limited to `max_items`. A truncated list is marked incomplete, so the client
+    /// asks again as the typed prefix narrows down the results. Before truncating, items that don't
+    /// match the prefix are dropped to keep the relevant ones.
+    ///
+    /// With `fallback` set, explicitly requested completion in a position without a known context
+    /// offers commands and local variables instead of nothing.
+    pub fn completion_list(
+        &self,
+        position: lsp_types::Position,
+        auto_complete: bool,
+        max_items: usize,
+        fallback: bool,
+    ) -> Option<lsp_types::CompletionList> {
+        let point = self.point_for_position(position);
+        let mut items = self.code_completion(position, auto_complete).or_else(|| {
+            (fallback && !auto_complete).then(|| {
+                let completions =
+                    code_completion::CodeCompletion::fallback_completions(self, point);
+                self.lsp_completion_items(point, completions)
+            })
+        })?;
+        if items.len() > max_items
+            && let Some(range) = self.identifier_at(point)
+        {
+            let prefix = self.line_map.text_in_range(range.start, point);
+            items.retain(|item| {
+                index::SymbolName::from(item.label.as_str())
+                    .match_score(&prefix, index::SymbolMatchMode::Fuzzy)
+                    .is_some()
+            });
+        }
+        let is_incomplete = items.len() > max_items;
+        items.truncate(max_items);
+        Some(lsp_types::CompletionList {
+            is_incomplete,
+            items,
         })
     }
 
+    pub fn completion_context(
+        &self,
+        position: lsp_types::Position,
+    ) -> Option<CompletionContextKind> {
+        let position = self.point_for_position(position);
+        DocumentContext::context(self, position)
+    }
+
     pub fn symbol_declaration(
         &self,
         position: lsp_types::Position,
     ) -> Option<lsp_types::MarkedString> {
-        let position = Point {
-            row: position.line as usize,
-            column: position.character as usize,
-        };
+        let position = self.point_for_position(position);
+        self.keyword_declaration(position)
+            .or_else(|| self.resolved_symbol_declaration(position))
+    }
+
+    fn resolved_symbol_declaration(&self, position: Point) -> Option<lsp_types::MarkedString> {
         let context = DocumentContext::context(self, position)?;
 
         let reference_resolver = ReferenceResolver::new(self);
@@ -292,14 +512,31 @@ impl DataFlexDocument {
         }
     }
 
+    /// Brief documentation for a keyword like `Send` or `Procedure`, when hovering a keyword that
+    /// doesn't resolve to a symbol.
+    fn keyword_declaration(&self, position: Point) -> Option<lsp_types::MarkedString> {
+        let mut cursor = self.cursor()?;
+        if !cursor.goto_leaf_node_at_or_before_point(position)
+            || cursor.node().kind() != "keyword"
+            || cursor.node().end_position() < position
+        {
+            return None;
+        }
+        let keyword = self.line_map.text_for_node(&cursor.node());
+        symbol_declaration::SymbolDeclaration::keyword_documentation(&keyword).map(
+            |documentation| {
+                lsp_types::MarkedString::from_markdown(format!(
+                    "```dataflex\n{keyword}\n```\n\n{documentation}\n"
+                ))
+            },
+        )
+    }
+
     pub fn signature_help(
         &self,
         position: lsp_types::Position,
     ) -> Option<Vec<lsp_types::SignatureInformation>> {
-        let position = Point {
-            row: position.line as usize,
-            column: position.character as usize,
-        };
+        let position = self.point_for_position(position);
         let parameter_info = parameter_info::ParameterInfo::parameter_info(self, position)?;
         Some(
             parameter_info
@@ -313,10 +550,7 @@ impl DataFlexDocument {
         &self,
         position: lsp_types::Position,
     ) -> Option<Vec<lsp_types::DocumentHighlight>> {
-        let position = Point {
-            row: position.line as usize,
-            column: position.character as usize,
-        };
+        let position = self.point_for_position(position);
 
         if let Some(range_pair) =
             scope_balancer::ScopeBalancer::open_and_close_scope_range_pair(self, position)
@@ -328,17 +562,38 @@ impl DataFlexDocument {
             Some(
                 [range_pair.0, range_pair.1]
                     .map(|r| lsp_types::DocumentHighlight {
-                        range: lsp_types::Range::new(
-                            lsp_types::Position::new(r.start.row as u32, r.start.column as u32),
-                            lsp_types::Position::new(r.end.row as u32, r.end.column as u32),
-                        ),
+                        range: self.lsp_range(&r),
                         kind: Some(lsp_types::DocumentHighlightKind::TEXT),
                     })
                     .to_vec(),
             )
         } else {
-            let allow_default_highlights = DocumentContext::context(self, position).is_some();
-            if allow_default_highlights {
+            let context = DocumentContext::context(self, position);
+            let reference_kind = match context {
+                Some(DocumentContext::ClassReference) => Some(ReferenceKind::Class),
+                Some(
+                    DocumentContext::MethodReference(kind)
+                    | DocumentContext::MethodDeclaration(kind),
+                ) => Some(ReferenceKind::Method(kind)),
+                _ => None,
+            };
+            let references = reference_kind
+                .zip(self.symbol_at_position(position))
+                .map(|(kind, name)| self.references_in_file(&name, kind))
+                .filter(|references| !references.is_empty());
+
+            if let Some(references) = references {
+                Some(
+                    references
+                        .into_iter()
+                        .map(|range| lsp_types::DocumentHighlight {
+                            range,
+                            kind: Some(lsp_types::DocumentHighlightKind::TEXT),
+                        })
+                        .collect(),
+                )
+            } else if context.is_some() {
+                // Allow the client's default highlights.
                 None
             } else {
                 Some(vec![])
@@ -346,17 +601,193 @@ impl DataFlexDocument {
         }
     }
 
+    /// The range of the footer keyword matching the header keyword at the position, or with
+    /// `to_end` unset, of the header keyword matching the footer keyword at the position.
+    pub fn matching_scope_range(
+        &self,
+        position: lsp_types::Position,
+        to_end: bool,
+    ) -> Option<lsp_types::Range> {
+        let position = self.point_for_position(position);
+        let (open_range, close_range) =
+            scope_balancer::ScopeBalancer::open_and_close_scope_range_pair(self, position)?;
+        let (from, to) = if to_end {
+            (open_range, close_range)
+        } else {
+            (close_range, open_range)
+        };
+        std::ops::RangeInclusive::new(from.start, from.end)
+            .contains(&position)
+            .then(|| self.lsp_range(&to))
+    }
+
+    pub fn references_in_file(
+        &self,
+        name: &index::SymbolName,
+        kind: ReferenceKind,
+    ) -> Vec<lsp_types::Range> {
+        references::References::references_in_file(self, name, kind)
+            .iter()
+            .map(|range| self.lsp_range(range))
+            .collect()
+    }
+
+    /// The symbols of the open document, indexed from its current content, including any unsaved
+    /// edits.
+    pub fn local_index_file(&self) -> Option<index::IndexFile> {
+        let tree = self.tree()?;
+        Some(index::IndexFile::with_parse_tree(
+            tree,
+            self.text_content().as_bytes(),
+        ))
+    }
+
+    pub fn reference_target(&self, position: lsp_types::Position) -> Option<ReferenceTarget> {
+        let position = self.point_for_position(position);
+        references::References::reference_target(self, position)
+    }
+
+    /// The references in this document to a class or method found with `reference_target()`,
+    /// possibly in another document.
+    pub fn find_references(
+        &self,
+        target: &ReferenceTarget,
+        include_declaration: bool,
+    ) -> Vec<lsp_types::Location> {
+        let Ok(uri) = lsp_types::Url::from_file_path(&self.file_path) else {
+            return Vec::new();
+        };
+        references::References::find_references(self, target, include_declaration)
+            .iter()
+            .map(|range| lsp_types::Location::new(uri.clone(), self.lsp_range(range)))
+            .collect()
+    }
+
+    /// The class named at the position, either by a reference or its declaration, as the starting
+    /// point for exploring its superclasses and subclasses.
+    pub fn type_hierarchy_item(
+        &self,
+        position: lsp_types::Position,
+    ) -> Option<lsp_types::TypeHierarchyItem> {
+        let target = self.reference_target(position)?;
+        if target.kind != ReferenceKind::Class {
+            return None;
+        }
+        let index = self.index.get();
+        index
+            .find_class_from_file(&target.name, &index::IndexFileRef::from(&self.file_path))
+            .and_then(|symbol_ref| index.resolve_symbol(symbol_ref))
+            .map(|symbol| lsp_types::TypeHierarchyItem::from(&symbol))
+    }
+
+    /// The range of the name at the position that a rename of the target replaces.
+    pub fn rename_range(
+        &self,
+        target: &ReferenceTarget,
+        position: lsp_types::Position,
+    ) -> Option<lsp_types::Range> {
+        let position = self.point_for_position(position);
+        references::References::rename_range(self, target, position)
+            .map(|range| self.lsp_range(&range))
+    }
+
+    /// Why the class or method found with `reference_target()` can't be renamed to the name.
+    pub fn rename_error(&self, target: &ReferenceTarget, new_name: &str) -> Option<String> {
+        references::References::rename_error(self, target, new_name)
+    }
+
     pub fn document_symbols(&self) -> Vec<lsp_types::DocumentSymbol> {
-        let Some(tree) = self.tree() else {
+        let Some(index_file) = self.local_index_file() else {
             return Vec::new();
         };
-        index::IndexFile::with_parse_tree(tree, self.text_content().as_bytes())
-            .symbols
+        index_file.symbols.iter().map(|s| s.into()).collect()
+    }
+
+    pub fn folding_ranges(&self) -> Vec<lsp_types::FoldingRange> {
+        folding_ranges::FoldingRanges::folding_ranges(self)
+    }
+
+    pub fn selection_ranges(
+        &self,
+        positions: &[lsp_types::Position],
+    ) -> Vec<lsp_types::SelectionRange> {
+        let points: Vec<Point> = positions
             .iter()
-            .map(|s| s.into())
+            .map(|position| self.point_for_position(*position))
+            .collect();
+        selection_ranges::SelectionRanges::selection_ranges(self, &points)
+    }
+
+    pub fn formatting(&self, options: &lsp_types::FormattingOptions) -> Vec<lsp_types::TextEdit> {
+        let settings = Settings::get();
+        formatting::WhitespaceFormatter::edits(
+            self,
+            formatting::WhitespaceOptions {
+                trim_trailing_whitespace: options
+                    .trim_trailing_whitespace
+                    .unwrap_or(settings.trim_trailing_whitespace),
+                insert_final_newline: options
+                    .insert_final_newline
+                    .unwrap_or(settings.insert_final_newline),
+            },
+        )
+    }
+
+    pub fn all_diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        let settings = Settings::get();
+        diagnostics::Diagnostics::diagnostics(
+            self,
+            diagnostics::DiagnosticsOptions {
+                mismatched_footers: settings.mismatched_footer_diagnostics,
+                unknown_classes: settings.unknown_class_diagnostics,
+                syntax_errors: settings.syntax_error_diagnostics,
+                inheritance_cycles: settings.inheritance_cycle_diagnostics,
+                use_cycles: settings.use_cycle_diagnostics,
+            },
+        )
+    }
+
+    pub fn code_actions(
+        &self,
+        uri: &lsp_types::Url,
+        range: lsp_types::Range,
+    ) -> Vec<lsp_types::CodeAction> {
+        scope_balancer::ScopeBalancer::mismatched_close_scopes(self)
+            .into_iter()
+            .filter(|mismatched| {
+                let mismatched_range = self.lsp_range(&mismatched.range);
+                mismatched_range.start <= range.end && range.start <= mismatched_range.end
+            })
+            .map(|mismatched| {
+                let edit = lsp_types::TextEdit {
+                    range: self.lsp_range(&mismatched.range),
+                    new_text: mismatched.expected.into(),
+                };
+                lsp_types::CodeAction {
+                    title: format!("Replace {} with {}", mismatched.found, mismatched.expected),
+                    kind: Some(lsp_types::CodeActionKind::QUICKFIX),
+                    edit: Some(lsp_types::WorkspaceEdit {
+                        changes: Some([(uri.clone(), vec![edit])].into()),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                }
+            })
             .collect()
     }
 
+    /// Converts a range with the byte columns of the parse tree to the UTF-16 columns of LSP.
+    fn lsp_range(&self, range: &std::ops::Range<Point>) -> lsp_types::Range {
+        let position = |point: Point| {
+            lsp_types::Position::new(
+                point.row as u32,
+                self.line_map.byte_column_to_utf16(point.row, point.column) as u32,
+            )
+        };
+        lsp_types::Range::new(position(range.start), position(range.end))
+    }
+
     pub fn code_lens_items(&self) -> Vec<lsp_types::CodeLens> {
         code_lens::CodeLens::code_lens(self)
             .into_iter()
@@ -410,6 +841,43 @@ impl From<&index::QualifiedIndexSymbol<'_>> for lsp_types::Location {
     }
 }
 
+impl From<&index::QualifiedIndexSymbol<'_>> for lsp_types::TypeHierarchyItem {
+    fn from(qualified_symbol: &index::QualifiedIndexSymbol) -> Self {
+        let location = lsp_types::Location::from(qualified_symbol);
+        lsp_types::TypeHierarchyItem {
+            name: qualified_symbol.symbol.name().to_string(),
+            kind: lsp_types::SymbolKind::from(qualified_symbol.symbol),
+            tags: None,
+            detail: qualified_symbol
+                .file
+                .path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned()),
+            uri: location.uri,
+            range: lsp_types::Range::from(qualified_symbol.symbol.range()),
+            selection_range: location.range,
+            // Identifies the exact class for the supertypes and subtypes requests, as several
+            // classes may have the same name.
+            data: serde_json::to_value(index::IndexSymbolRef::new(
+                index::IndexFileRef::from(&qualified_symbol.file.path),
+                qualified_symbol.symbol.symbol_path().clone(),
+            ))
+            .ok(),
+        }
+    }
+}
+
+impl From<&reference_resolver::ResolvedSymbol> for lsp_types::Location {
+    fn from(symbol: &reference_resolver::ResolvedSymbol) -> Self {
+        let position =
+            lsp_types::Position::new(symbol.location.row as u32, symbol.location.column as u32);
+        lsp_types::Location::new(
+            lsp_types::Url::from_file_path(&symbol.path).unwrap(),
+            lsp_types::Range::new(position, position),
+        )
+    }
+}
+
 impl From<index::SourceRange> for lsp_types::Range {
     fn from(range: index::SourceRange) -> Self {
         lsp_types::Range::new(
@@ -451,6 +919,12 @@ impl From<&index::IndexSymbol> for lsp_types::DocumentSymbol {
             line: symbol.location().line as u32,
             character: symbol.location().column as u32,
         };
+        // The range spans the whole definition, including any trailing clauses in the header,
+        // while the selection range only covers the name.
+        let name_end = lsp_types::Position {
+            character: position.character + symbol.name().to_string().len() as u32,
+            ..position
+        };
 
         let range = lsp_types::Range {
             start: lsp_types::Position {
@@ -478,7 +952,7 @@ impl From<&index::IndexSymbol> for lsp_types::DocumentSymbol {
             range: range,
             selection_range: lsp_types::Range {
                 start: position,
-                end: position,
+                end: name_end,
             },
             children: if children.is_empty() {
                 None
@@ -495,6 +969,7 @@ impl From<&index::IndexSymbol> for lsp_types::SymbolKind {
             index::IndexSymbol::Class(_) => Self::CLASS,
             index::IndexSymbol::Object(_) => Self::OBJECT,
             index::IndexSymbol::Struct(_) => Self::STRUCT,
+            index::IndexSymbol::Method(method) if method.external.is_some() => Self::FUNCTION,
             index::IndexSymbol::Method(_) => Self::METHOD,
             index::IndexSymbol::Property(_) => Self::PROPERTY,
             index::IndexSymbol::Variable(_) => Self::VARIABLE,
@@ -558,4 +1033,925 @@ mod tests {
             "(source_file (object_definition (object_header (keyword) name: (identifier) (keyword) (keyword) superclass: (identifier)) (procedure_definition (procedure_header (keyword) name: (identifier)) (procedure_footer (keyword))) (object_footer (keyword))))"
         );
     }
+
+    #[test]
+    fn test_edit_content_with_multibyte_characters() {
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oCafé is a cObject // 😀\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+
+        // UTF-16 columns after the accented character, and after the surrogate pair.
+        doc.edit_content(&vec![
+            lsp_types::TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    lsp_types::Position::new(0, 18),
+                    lsp_types::Position::new(0, 25),
+                )),
+                text: "cBase".to_string(),
+                range_length: None,
+            },
+            lsp_types::TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(
+                    lsp_types::Position::new(0, 29),
+                    lsp_types::Position::new(0, 29),
+                )),
+                text: "!".to_string(),
+                range_length: None,
+            },
+        ]);
+
+        let expected = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oCafé is a cBase // 😀!\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(doc.text_content(), expected.text_content());
+        assert_eq!(
+            doc.root_node().unwrap().to_sexp(),
+            expected.root_node().unwrap().to_sexp()
+        );
+        for point in [Point::new(0, 20), Point::new(0, 33), Point::new(1, 0)] {
+            assert_eq!(
+                doc.node_at_position(point).map(|node| node.byte_range()),
+                expected
+                    .node_at_position(point)
+                    .map(|node| node.byte_range())
+            );
+        }
+        assert_eq!(
+            doc.node_at_position(Point::new(0, 20))
+                .map(|node| node.byte_range()),
+            Some(19..24)
+        );
+    }
+
+    #[test]
+    fn test_edit_content_with_error_nodes() {
+        fn insert(doc: &mut DataFlexDocument, line: u32, character: u32, text: &str) {
+            let position = lsp_types::Position::new(line, character);
+            doc.edit_content(&vec![lsp_types::TextDocumentContentChangeEvent {
+                range: Some(lsp_types::Range::new(position, position)),
+                text: text.to_string(),
+                range_length: None,
+            }]);
+        }
+
+        // Type the class one character at a time, leaving the tree with ERROR nodes in between.
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let (mut line, mut character) = (0, 0);
+        for c in
+            "Class cMyClass cObject\n    Procedure Test\n    End_Procedure\nEnd_Class\n".chars()
+        {
+            insert(&mut doc, line, character, &c.to_string());
+            if c == '\n' {
+                (line, character) = (line + 1, 0);
+            } else {
+                character += 1;
+            }
+        }
+        assert!(doc.root_node().unwrap().has_error());
+
+        // Complete the header in the middle of the error region.
+        for (i, c) in "is a ".chars().enumerate() {
+            insert(&mut doc, 0, 15 + i as u32, &c.to_string());
+        }
+
+        let expected = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Class cMyClass is a cObject\n    Procedure Test\n    End_Procedure\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(doc.text_content(), expected.text_content());
+        assert!(!doc.root_node().unwrap().has_error());
+        assert_eq!(
+            doc.root_node().unwrap().to_sexp(),
+            expected.root_node().unwrap().to_sexp()
+        );
+        assert_eq!(
+            doc.root_node().unwrap().to_sexp(),
+            "(source_file (class_definition (class_header (keyword) name: (identifier) (keyword) (keyword) superclass: (identifier)) (procedure_definition (procedure_header (keyword) name: (identifier)) (procedure_footer (keyword))) (class_footer (keyword))))"
+        );
+    }
+
+    #[test]
+    fn test_document_symbols_with_header_clause() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oButton is a Button Label \"OK\"\n    Procedure OnClick\n    End_Procedure\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let header = doc
+            .root_node()
+            .and_then(|root| root.child(0))
+            .and_then(|object| object.child(0))
+            .unwrap();
+        assert_eq!(header.kind(), "object_header");
+        assert_eq!(header.end_position(), Point::new(0, 37));
+
+        let symbols = doc.document_symbols();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(
+            symbols[0].range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, 0),
+                lsp_types::Position::new(3, 10)
+            )
+        );
+        assert_eq!(
+            symbols[0].selection_range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, 7),
+                lsp_types::Position::new(0, 14)
+            )
+        );
+        assert_eq!(
+            symbols[0].children.as_ref().unwrap()[0].selection_range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(1, 14),
+                lsp_types::Position::new(1, 21)
+            )
+        );
+    }
+
+    #[test]
+    fn test_document_symbols_class_outline() {
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Class cMyClass is a cObject\n    Procedure DoIt\n    End_Procedure\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let outline = |doc: &DataFlexDocument| {
+            fn describe(symbol: &lsp_types::DocumentSymbol) -> String {
+                let children = symbol.children.iter().flatten().map(describe);
+                format!("{} {:?}", symbol.name, symbol.kind)
+                    + &children.map(|c| format!(" [{c}]")).collect::<String>()
+            }
+            doc.document_symbols()
+                .iter()
+                .map(describe)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(outline(&doc), ["cMyClass CLASS [DoIt METHOD]"]);
+
+        // The outline reflects the open document, not the (empty) workspace index.
+        doc.replace_content(
+            "Class cMyClass is a cObject\n    Procedure DoIt\n    End_Procedure\n\n    Function Value Returns Integer\n    End_Function\nEnd_Class\n",
+        );
+        assert_eq!(
+            outline(&doc),
+            ["cMyClass CLASS [DoIt METHOD] [Value METHOD]"]
+        );
+        let class = &doc.document_symbols()[0];
+        assert_eq!(
+            class.children.as_ref().unwrap()[1].range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(4, 4),
+                lsp_types::Position::new(5, 16)
+            )
+        );
+    }
+
+    #[test]
+    fn test_node_kind_at() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Class cCafé is a cBase\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            doc.node_kind_at(lsp_types::Position::new(0, 0)),
+            Some("keyword")
+        );
+        assert_eq!(
+            doc.node_kind_at(lsp_types::Position::new(0, 7)),
+            Some("identifier")
+        );
+
+        // The position is in UTF-16 code units, so this is the start of `cBase`.
+        assert_eq!(
+            doc.node_kind_at(lsp_types::Position::new(0, 17)),
+            Some("identifier")
+        );
+    }
+
+    #[test]
+    fn test_completion_context() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Use cWebView.pkg\nObject oTest is a cWebView\n    Procedure Test\n        Send Foo\n        Get pValue to i\n        Set pValue to 1\n        Move 1 to i\n    End_Procedure\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let context_at =
+            |line, character| doc.completion_context(lsp_types::Position::new(line, character));
+
+        assert_eq!(
+            context_at(0, 6),
+            Some(CompletionContextKind::FileDependency)
+        );
+        assert_eq!(
+            context_at(1, 20),
+            Some(CompletionContextKind::ClassReference)
+        );
+        assert_eq!(
+            context_at(2, 16),
+            Some(CompletionContextKind::MethodDeclaration(
+                index::MethodKind::Msg
+            ))
+        );
+        assert_eq!(
+            context_at(3, 9),
+            Some(CompletionContextKind::CommandReference)
+        );
+        assert_eq!(
+            context_at(3, 14),
+            Some(CompletionContextKind::MethodReference(
+                index::MethodKind::Msg
+            ))
+        );
+        assert_eq!(
+            context_at(4, 14),
+            Some(CompletionContextKind::MethodReference(
+                index::MethodKind::Get
+            ))
+        );
+        assert_eq!(
+            context_at(5, 14),
+            Some(CompletionContextKind::MethodReference(
+                index::MethodKind::Set
+            ))
+        );
+        assert_eq!(context_at(6, 19), Some(CompletionContextKind::Expression));
+    }
+
+    #[test]
+    fn test_matching_scope_range() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cObject\n    Procedure Test\n        Send Foo\n    End_Procedure\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let procedure_range = lsp_types::Range::new(
+            lsp_types::Position::new(1, 4),
+            lsp_types::Position::new(1, 13),
+        );
+        let end_procedure_range = lsp_types::Range::new(
+            lsp_types::Position::new(3, 4),
+            lsp_types::Position::new(3, 17),
+        );
+
+        assert_eq!(
+            doc.matching_scope_range(lsp_types::Position::new(1, 6), true),
+            Some(end_procedure_range)
+        );
+        assert_eq!(
+            doc.matching_scope_range(lsp_types::Position::new(3, 8), false),
+            Some(procedure_range)
+        );
+        assert_eq!(
+            doc.matching_scope_range(lsp_types::Position::new(1, 6), false),
+            None
+        );
+        assert_eq!(
+            doc.matching_scope_range(lsp_types::Position::new(2, 10), true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mismatched_footer_diagnostic_and_fix() {
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let diagnostics = diagnostics::Diagnostics::mismatched_footers(&doc);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(1, 0),
+                lsp_types::Position::new(1, 9),
+            )
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Mismatched End_Class, expected End_Object"
+        );
+
+        let uri = lsp_types::Url::parse("file:///test.pkg").unwrap();
+        let code_actions = doc.code_actions(
+            &uri,
+            lsp_types::Range::new(
+                lsp_types::Position::new(1, 2),
+                lsp_types::Position::new(1, 2),
+            ),
+        );
+        assert_eq!(code_actions.len(), 1);
+        assert_eq!(code_actions[0].title, "Replace End_Class with End_Object");
+
+        let edit = &code_actions[0]
+            .edit
+            .as_ref()
+            .unwrap()
+            .changes
+            .as_ref()
+            .unwrap()[&uri][0];
+        doc.edit_content(&vec![lsp_types::TextDocumentContentChangeEvent {
+            range: Some(edit.range),
+            text: edit.new_text.clone(),
+            range_length: None,
+        }]);
+        assert_eq!(doc.text_content(), "Object oTest is a cTest\nEnd_Object\n");
+        assert!(diagnostics::Diagnostics::mismatched_footers(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_semantic_tokens_delta() {
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cObject\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let tokens = doc.semantic_tokens().unwrap();
+        assert_eq!(tokens.result_id.as_deref(), Some("1"));
+
+        doc.replace_content(
+            "Object oTest is a cObject\n    Procedure Test\n    End_Procedure\nEnd_Object\n",
+        );
+        let Some(lsp_types::SemanticTokensFullDeltaResult::TokensDelta(delta)) =
+            doc.semantic_tokens_delta("1")
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(delta.result_id.as_deref(), Some("2"));
+        assert!(!delta.edits.is_empty());
+
+        // Applying the edits to the previous tokens gives the current tokens.
+        let mut data = tokens.data;
+        for edit in delta.edits.iter().rev() {
+            let start = edit.start as usize / 5;
+            let end = start + edit.delete_count as usize / 5;
+            data.splice(start..end, edit.data.clone().unwrap_or_default());
+        }
+        assert_eq!(Some(data), doc.semantic_tokens_full());
+
+        // Nothing changed since the last result.
+        let Some(lsp_types::SemanticTokensFullDeltaResult::TokensDelta(delta)) =
+            doc.semantic_tokens_delta("2")
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(delta.result_id.as_deref(), Some("3"));
+        assert!(delta.edits.is_empty());
+
+        // An unknown previous result falls back to all tokens.
+        let Some(lsp_types::SemanticTokensFullDeltaResult::Tokens(tokens)) =
+            doc.semantic_tokens_delta("1")
+        else {
+            panic!("expected all tokens");
+        };
+        assert_eq!(tokens.result_id.as_deref(), Some("4"));
+        assert_eq!(Some(tokens.data), doc.semantic_tokens_full());
+    }
+
+    #[test]
+    fn test_semantic_tokens_suppressed_for_large_file() {
+        let max_lines = Settings::get().max_semantic_token_lines;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            &"Move 1 to iTest\n".repeat(max_lines + 1),
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert!(doc.syntax_map.is_none());
+        assert_eq!(doc.semantic_tokens_full(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_multi_file_project_with_case_variant_dependencies() {
+        let index = index::IndexRef::make_test_index_ref();
+        let project_dir = std::env::temp_dir();
+
+        let base_path = project_dir.join("base.pkg");
+        let base_content = r#"
+Class cBase is a cObject
+    Procedure SayHello
+    End_Procedure
+End_Class
+"#;
+        index::Indexer::index_test_content(base_content, base_path.clone(), &index);
+
+        let derived_path = project_dir.join("derived.pkg");
+        let derived_content = r#"
+Use BASE.PKG
+Class cDerived is a cBase
+    Procedure SayGoodbye
+    End_Procedure
+End_Class
+"#;
+        index::Indexer::index_test_content(derived_content, derived_path.clone(), &index);
+
+        let view_path = project_dir.join("view.vw");
+        let view_content = r#"
+Use Derived.pkg
+Object oView is a cDerived
+    Procedure Test
+        Send SayHello
+    End_Procedure
+End_Object
+"#;
+        index::Indexer::index_test_content(view_content, view_path.clone(), &index);
+
+        let base_url = lsp_types::Url::from_file_path(&base_path).unwrap();
+        let derived_url = lsp_types::Url::from_file_path(&derived_path).unwrap();
+
+        // Goto definition of file dependencies with different casing than the file names.
+        let doc = DataFlexDocument::new(derived_path.clone(), derived_content, index.clone());
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(1, 6)),
+            Some(vec![lsp_types::Location::new(
+                base_url.clone(),
+                lsp_types::Range::default(),
+            )])
+        );
+
+        let doc = DataFlexDocument::new(view_path.clone(), view_content, index.clone());
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(1, 6)),
+            Some(vec![lsp_types::Location::new(
+                derived_url.clone(),
+                lsp_types::Range::default(),
+            )])
+        );
+
+        // Goto definition of the derived class, and of a method inherited from the base class.
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(2, 20)),
+            Some(vec![lsp_types::Location::new(
+                derived_url.clone(),
+                lsp_types::Range::new(
+                    lsp_types::Position::new(2, 6),
+                    lsp_types::Position::new(2, 6),
+                )
+            )])
+        );
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(4, 15)),
+            Some(vec![lsp_types::Location::new(
+                base_url.clone(),
+                lsp_types::Range::new(
+                    lsp_types::Position::new(2, 14),
+                    lsp_types::Position::new(2, 14),
+                )
+            )])
+        );
+
+        // Hover.
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(2, 20)),
+            Some(lsp_types::MarkedString::from_markdown(
+                "```dataflex\nClass cDerived is a cBase\n```\n\nDefined in `derived.pkg`\n".into(),
+            ))
+        );
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(4, 15)),
+            Some(lsp_types::MarkedString::from_markdown(
+                "```dataflex\nProcedure SayHello\n```\n\nDefined in `base.pkg`\n".into(),
+            ))
+        );
+
+        // Code completion.
+        let completions = doc
+            .code_completion(lsp_types::Position::new(2, 20), false)
+            .unwrap();
+        assert!(completions.iter().any(|item| item.label == "cBase"));
+        assert!(completions.iter().any(|item| item.label == "cDerived"));
+
+        let completions = doc
+            .code_completion(lsp_types::Position::new(4, 15), false)
+            .unwrap();
+        assert!(completions.iter().any(|item| item.label == "SayHello"));
+        assert!(completions.iter().any(|item| item.label == "SayGoodbye"));
+    }
+
+    #[test]
+    fn test_find_definition_in_class_header() {
+        let path = std::env::temp_dir().join("classes.pkg");
+        let test_content =
+            "Class cBase is a cObject\nEnd_Class\n\nClass cDerived is a cBase\nEnd_Class\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, path.clone(), &index);
+        let doc = DataFlexDocument::new(path.clone(), test_content, index.clone());
+        let location = |line, character| {
+            lsp_types::Location::new(
+                lsp_types::Url::from_file_path(&path).unwrap(),
+                lsp_types::Range::new(
+                    lsp_types::Position::new(line, character),
+                    lsp_types::Position::new(line, character),
+                ),
+            )
+        };
+
+        // The class name resolves to the class itself.
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(3, 10)),
+            Some(vec![location(3, 6)])
+        );
+        // The superclass resolves to the base class.
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(3, 22)),
+            Some(vec![location(0, 6)])
+        );
+    }
+
+    #[test]
+    fn test_find_definition_of_file_dependency() {
+        let path = std::env::temp_dir().join("cIndexed.pkg");
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            "Class cIndexed is a cObject\nEnd_Class\n",
+            path.clone(),
+            &index,
+        );
+        let doc = DataFlexDocument::new("test.vw".into(), "Use cIndexed\nUse cUnindexed\n", index);
+
+        assert_eq!(
+            doc.find_definition(lsp_types::Position::new(0, 6)),
+            Some(vec![lsp_types::Location::new(
+                lsp_types::Url::from_file_path(&path).unwrap(),
+                lsp_types::Range::default(),
+            )])
+        );
+        assert_eq!(doc.find_definition(lsp_types::Position::new(1, 6)), None);
+    }
+
+    #[test]
+    fn test_type_hierarchy_item() {
+        let test_content =
+            "Class cBase is a cObject\nEnd_Class\n\nClass cDerived is a cBase\nEnd_Class\n";
+        let path = std::env::temp_dir().join("TypeHierarchy.pkg");
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, path.clone(), &index);
+        let doc = DataFlexDocument::new(path.clone(), test_content, index.clone());
+
+        // The superclass reference.
+        let item = doc
+            .type_hierarchy_item(lsp_types::Position::new(3, 22))
+            .unwrap();
+        assert_eq!(item.name, "cBase");
+        assert_eq!(item.kind, lsp_types::SymbolKind::CLASS);
+        assert_eq!(item.detail.as_deref(), Some("TypeHierarchy.pkg"));
+        assert_eq!(item.selection_range.start, lsp_types::Position::new(0, 6));
+        let class = serde_json::from_value::<index::IndexSymbolRef>(item.data.unwrap()).unwrap();
+        assert_eq!(class.file_ref, index::IndexFileRef::from(&path));
+        assert_eq!(class.symbol_path.name(), &"cBase".into());
+
+        // The class declaration.
+        let item = doc
+            .type_hierarchy_item(lsp_types::Position::new(3, 8))
+            .unwrap();
+        assert_eq!(item.name, "cDerived");
+        assert_eq!(item.range.start, lsp_types::Position::new(3, 0));
+
+        assert!(
+            doc.type_hierarchy_item(lsp_types::Position::new(1, 2))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_identifiers() {
+        let test_content = r#"Class cCafé is a cObject
+    Procedure Privé.Déjà
+    End_Procedure
+End_Class
+
+Object oTest is a cCafé
+    Procedure Test
+        Send Privé.Dé
+    End_Procedure
+End_Object
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        // Byte column in the middle of the identifier.
+        assert_eq!(
+            doc.symbol_at_position(Point::new(5, 20)),
+            Some(index::SymbolName::from("cCafé"))
+        );
+
+        // UTF-16 column on the accented character.
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(5, 22)),
+            Some(lsp_types::MarkedString::from_markdown(
+                "```dataflex\nClass cCafé is a cObject\n```\n\nDefined in `test.pkg`\n".into(),
+            ))
+        );
+
+        let completions = doc
+            .code_completion(lsp_types::Position::new(7, 21), false)
+            .unwrap();
+        assert!(completions.iter().any(|item| item.label == "Déjà"));
+        assert!(!completions.iter().any(|item| item.label.contains('.')));
+    }
+
+    #[test]
+    fn test_rename_after_non_ascii_characters() {
+        let test_content =
+            "Class cBase is a cObject\nEnd_Class\n\nClass cCafé is a cBase\nEnd_Class\n";
+        let path = std::env::temp_dir().join("RenameNonAscii.pkg");
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, path.clone(), &index);
+        let doc = DataFlexDocument::new(path, test_content, index.clone());
+
+        // The superclass name starts at byte column 18, but UTF-16 column 17.
+        let range = |line, start, end| {
+            lsp_types::Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, end),
+            )
+        };
+        let target = doc
+            .reference_target(lsp_types::Position::new(3, 18))
+            .unwrap();
+        assert_eq!(
+            doc.rename_range(&target, lsp_types::Position::new(3, 18)),
+            Some(range(3, 17, 22))
+        );
+        let mut ranges: Vec<lsp_types::Range> = doc
+            .find_references(&target, true)
+            .into_iter()
+            .map(|location| location.range)
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        assert_eq!(ranges, [range(0, 6, 11), range(3, 17, 22)]);
+    }
+
+    #[test]
+    fn test_document_highlight_after_non_ascii_characters() {
+        let test_content =
+            "Object oCafé is a cObject\nEnd_Object\n\nObject oDéjà is a cObject\nEnd_Object\n";
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+
+        let highlight = |line, start, end| lsp_types::DocumentHighlight {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, end),
+            ),
+            kind: Some(lsp_types::DocumentHighlightKind::TEXT),
+        };
+        assert_eq!(
+            doc.document_highlight(lsp_types::Position::new(0, 19)),
+            Some(vec![highlight(0, 17, 24), highlight(3, 17, 24)])
+        );
+    }
+
+    #[test]
+    fn test_hover_keyword() {
+        let test_content = "Object oTest is a cObject\n    Procedure Test\n        Send Test\n    End_Procedure\nEnd_Object\n";
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+
+        let hover = |line, character| match doc
+            .symbol_declaration(lsp_types::Position::new(line, character))
+        {
+            Some(lsp_types::MarkedString::String(text)) => text,
+            hover => panic!("expected markdown hover, got {hover:?}"),
+        };
+        assert!(hover(2, 10).starts_with("```dataflex\nSend\n```\n\nCalls a procedure"));
+        // Symbols still show their declaration.
+        assert!(hover(2, 14).starts_with("```dataflex\nProcedure Test\n```"));
+    }
+
+    #[test]
+    fn test_hover_get_returning_class() {
+        let test_content = r#"Class cConnection is a cObject
+End_Class
+
+Object oTest is a cObject
+    Function Server Returns cConnection
+    End_Function
+
+    Function Port Returns Integer
+    End_Function
+
+    Procedure Test
+        Handle hServer
+        Integer iPort
+        Get Server to hServer
+        Get Port to iPort
+    End_Procedure
+End_Object
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(13, 13)),
+            Some(lsp_types::MarkedString::from_markdown(
+                "```dataflex\nFunction Server Returns cConnection\n```\n\n`Get Server : cConnection`\n\nDefined in `test.pkg`\n".into(),
+            ))
+        );
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(14, 13)),
+            Some(lsp_types::MarkedString::from_markdown(
+                "```dataflex\nFunction Port Returns Integer\n```\n\nDefined in `test.pkg`\n".into(),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_completion_text_edit() {
+        let test_content =
+            "Class cWebButton is a cObject\nEnd_Class\n\nObject oTest is a cWebBu\nEnd_Object\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let completions = doc
+            .code_completion(lsp_types::Position::new(3, 21), false)
+            .unwrap();
+        let item = completions
+            .iter()
+            .find(|item| item.label == "cWebButton")
+            .unwrap();
+        assert_eq!(
+            item.text_edit,
+            Some(lsp_types::CompletionTextEdit::Edit(lsp_types::TextEdit {
+                range: lsp_types::Range::new(
+                    lsp_types::Position::new(3, 18),
+                    lsp_types::Position::new(3, 24),
+                ),
+                new_text: "cWebButton".into(),
+            }))
+        );
+
+        // The replaced range is in UTF-16 columns on lines with non-ASCII characters.
+        let test_content = "Class cCafé is a cObject\n    Procedure Privé.Déjà\n    End_Procedure\nEnd_Class\n\nObject oTest is a cCafé\n    Procedure Test\n        Send Privé.Dé\n    End_Procedure\nEnd_Object\n";
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        let completions = doc
+            .code_completion(lsp_types::Position::new(7, 20), false)
+            .unwrap();
+        let item = completions
+            .iter()
+            .find(|item| item.label == "Déjà")
+            .unwrap();
+        assert_eq!(
+            item.text_edit,
+            Some(lsp_types::CompletionTextEdit::Edit(lsp_types::TextEdit {
+                range: lsp_types::Range::new(
+                    lsp_types::Position::new(7, 19),
+                    lsp_types::Position::new(7, 21),
+                ),
+                new_text: "Déjà".into(),
+            }))
+        );
+
+        let doc = DataFlexDocument::new("test.pkg".into(), "Object oTest is a \n", index.clone());
+        let completions = doc
+            .code_completion(lsp_types::Position::new(0, 18), false)
+            .unwrap();
+        assert!(completions.iter().all(|item| item.text_edit.is_none()));
+    }
+
+    #[test]
+    fn test_completion_item_documentation() {
+        let test_content = "{ Description = \"A button\" }\nClass cButton is a cObject\nEnd_Class\n\nObject oTest is a cBu\nEnd_Object\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let list = doc
+            .completion_list(lsp_types::Position::new(4, 21), false, 100, false)
+            .unwrap();
+        let item = list
+            .items
+            .into_iter()
+            .find(|item| item.label == "cButton")
+            .unwrap();
+        assert_eq!(item.documentation, None);
+        assert_eq!(
+            DataFlexDocument::completion_item_documentation(&index.get(), item.data.unwrap()),
+            Some(lsp_types::Documentation::MarkupContent(
+                lsp_types::MarkupContent {
+                    kind: lsp_types::MarkupKind::Markdown,
+                    value: "A button".into(),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_completion_list_is_incomplete() {
+        let test_content = "Class cButton is a cObject\nEnd_Class\nClass cCheckBox is a cObject\nEnd_Class\nClass cWebView is a cObject\nEnd_Class\n\nObject oTest is a \nEnd_Object\n\nObject oOther is a cWe\nEnd_Object\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        // Broad context without a prefix, truncated to the limit.
+        let list = doc
+            .completion_list(lsp_types::Position::new(7, 18), false, 2, false)
+            .unwrap();
+        assert!(list.is_incomplete);
+        assert_eq!(list.items.len(), 2);
+
+        // Narrowed down by the prefix, so all matching items fit within the limit.
+        let list = doc
+            .completion_list(lsp_types::Position::new(10, 22), false, 2, false)
+            .unwrap();
+        assert!(!list.is_incomplete);
+        assert_eq!(
+            list.items
+                .iter()
+                .map(|item| item.label.as_str())
+                .collect::<Vec<_>>(),
+            ["cWebView"]
+        );
+
+        let list = doc
+            .completion_list(lsp_types::Position::new(7, 18), false, 100, false)
+            .unwrap();
+        assert!(!list.is_incomplete);
+        assert_eq!(list.items.len(), 3);
+    }
+
+    #[test]
+    fn test_fallback_completion() {
+        let test_content = "Procedure Test\n    Integer iCount\n    Send Foo iC\nEnd_Procedure\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        let position = lsp_types::Position::new(2, 15);
+
+        assert!(doc.code_completion(position, false).is_none());
+        assert!(doc.completion_list(position, false, 100, false).is_none());
+        assert!(doc.completion_list(position, true, 100, true).is_none());
+
+        let list = doc.completion_list(position, false, 100, true).unwrap();
+        assert!(list.items.iter().any(|item| item.label == "iCount"
+            && item.kind == Some(lsp_types::CompletionItemKind::VARIABLE)));
+        assert!(list.items.iter().any(|item| item.label == "Move"));
+    }
+
+    #[test]
+    fn test_out_of_range_positions() {
+        let test_content =
+            "Class cBase is a cObject\nEnd_Class\n\nObject oTest is a cBase\nEnd_Object\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        // Row beyond the end of the document is clamped to the last line.
+        assert_eq!(
+            doc.point_for_position(lsp_types::Position::new(100, 4)),
+            Point::new(5, 0)
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                doc.code_completion(lsp_types::Position::new(100, 4), false)
+            ),
+            format!(
+                "{:?}",
+                doc.code_completion(lsp_types::Position::new(5, 0), false)
+            )
+        );
+        assert_eq!(doc.find_definition(lsp_types::Position::new(100, 4)), None);
+
+        // Column beyond the end of a line is clamped to the end of the line.
+        assert_eq!(
+            doc.point_for_position(lsp_types::Position::new(3, 100)),
+            Point::new(3, 23)
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                doc.code_completion(lsp_types::Position::new(3, 100), false)
+            ),
+            format!(
+                "{:?}",
+                doc.code_completion(lsp_types::Position::new(3, 23), false)
+            )
+        );
+        assert_eq!(
+            doc.symbol_declaration(lsp_types::Position::new(3, 100)),
+            doc.symbol_declaration(lsp_types::Position::new(3, 23))
+        );
+    }
 }
@@ -1,7 +1,8 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 
 use super::*;
-use index::{IndexSymbolType, MethodKind, MethodSymbol, StructSymbol};
+use index::{ClassSymbol, IndexSymbolType, MethodKind, MethodSymbol, StructSymbol, VariableSymbol};
 
 pub struct CodeCompletion {}
 
@@ -11,9 +12,13 @@ pub struct CompletionItem {
     pub kind: CompletionItemKind,
     pub details: Option<String>,
     pub insert_text: Option<String>,
+    /// The index symbol documenting the item, looked up when the client resolves the item.
+    pub symbol: Option<index::IndexSymbolRef>,
+    /// Sourced from the open document rather than the workspace index.
+    pub local: bool,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CompletionItemKind {
     #[default]
     Text,
@@ -50,9 +55,10 @@ impl CodeCompletion {
                 Some(Self::method_completions(doc, position, kind))
             }
             DocumentContext::Expression => Some(Self::expr_completions(doc, position)),
+            DocumentContext::PropertyReference => Some(Self::expr_completions(doc, position)),
             DocumentContext::ParenExpression => Some(Self::paren_expr_completions(doc, position)),
             DocumentContext::DotMemberExpression => Some(Self::dot_completions(doc, position)),
-            DocumentContext::CommandReference => Some(Self::command_completions(doc)),
+            DocumentContext::CommandReference => Some(Self::command_completions(doc, position)),
             DocumentContext::FileDependency => Some(Self::file_completions(doc)),
             DocumentContext::MethodDeclaration(kind) => {
                 Some(Self::override_completions(doc, position, kind))
@@ -63,6 +69,14 @@ impl CodeCompletion {
         completions
     }
 
+    /// Completions for positions without a known context: commands and the local variables in
+    /// scope, which covers most of what can be typed at the start of a statement.
+    pub fn fallback_completions(doc: &DataFlexDocument, position: Point) -> Vec<CompletionItem> {
+        Self::system_commands(doc)
+            .chain(Self::local_variable_completions(doc, position))
+            .collect()
+    }
+
     fn should_auto_complete_with_context(context: &DocumentContext) -> bool {
         match context {
             DocumentContext::ClassReference => true,
@@ -70,6 +84,7 @@ impl CodeCompletion {
             DocumentContext::DotMemberExpression => true,
             DocumentContext::FileDependency => true,
             DocumentContext::Expression => false,
+            DocumentContext::PropertyReference => false,
             DocumentContext::ParenExpression => false,
             DocumentContext::CommandReference => false,
             DocumentContext::MethodDeclaration(_) => false,
@@ -77,39 +92,118 @@ impl CodeCompletion {
         }
     }
 
-    fn class_completions(doc: &DataFlexDocument) -> Vec<CompletionItem> {
-        doc.index
-            .get()
-            .all_known_classes()
-            .drain(..)
-            .map(|class_name| CompletionItem {
-                label: class_name.to_string(),
-                kind: CompletionItemKind::Class,
-                ..Default::default()
+    /// Merges completions from the open document with those from the index. The open document may
+    /// have unsaved edits, so its items win over index items with the same name and kind, and are
+    /// listed first.
+    fn merge_completions(
+        local: impl IntoIterator<Item = CompletionItem>,
+        global: impl IntoIterator<Item = CompletionItem>,
+    ) -> Vec<CompletionItem> {
+        let mut seen = HashSet::new();
+        local
+            .into_iter()
+            .map(|item| CompletionItem {
+                local: true,
+                ..item
             })
+            .chain(global)
+            .filter(|item| seen.insert((item.label.to_lowercase(), item.kind)))
             .collect()
     }
 
+    fn class_completions(doc: &DataFlexDocument) -> Vec<CompletionItem> {
+        let local_classes = doc
+            .local_index_file()
+            .map(|index_file| {
+                index_file
+                    .symbols
+                    .iter()
+                    .filter_map(ClassSymbol::from_index_symbol)
+                    .map(|class| CompletionItem {
+                        label: class.symbol_path.name().to_string(),
+                        kind: CompletionItemKind::Class,
+                        details: Some(format!(" is a {}", class.superclass)),
+                        ..Default::default()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let index = doc.index.get();
+        Self::merge_completions(
+            local_classes,
+            index.all_known_classes().drain(..).map(|class_name| {
+                let class_ref = index.find_class(&class_name);
+                CompletionItem {
+                    details: class_ref
+                        .and_then(|class_ref| index.resolve_symbol(class_ref))
+                        .and_then(|class| ClassSymbol::from_index_symbol(class.symbol))
+                        .map(|class| format!(" is a {}", class.superclass)),
+                    symbol: class_ref.cloned(),
+                    label: class_name.to_string(),
+                    kind: CompletionItemKind::Class,
+                    ..Default::default()
+                }
+            }),
+        )
+    }
+
+    /// The `Description` meta tags of the symbol, as shown on hover.
+    pub fn documentation(
+        index: &index::Index,
+        symbol_ref: &index::IndexSymbolRef,
+    ) -> Option<String> {
+        index.resolve_symbol(symbol_ref).and_then(|symbol| {
+            symbol_declaration::SymbolDeclaration::new(&symbol, index).description
+        })
+    }
+
     fn method_completions(
         doc: &DataFlexDocument,
         position: Point,
         kind: index::MethodKind,
     ) -> Vec<CompletionItem> {
+        // Scope to the members of the receiver's class when an explicit receiver is given, e.g.
+        // `Send DoSomething of oObject`, and fall back to all known methods otherwise.
         let completions: Vec<CompletionItem> =
-            match kind {
-                MethodKind::Msg => doc
-                    .index
-                    .get()
-                    .all_known_methods(kind)
-                    .drain(..)
-                    .map(|method_name| CompletionItem {
-                        label: method_name.to_string(),
-                        kind: CompletionItemKind::Method,
-                        ..Default::default()
-                    })
-                    .collect(),
-                MethodKind::Get | MethodKind::Set => {
-                    doc.index
+            if let Some(completions) = Self::receiver_member_completions(doc, position, kind) {
+                completions
+            } else {
+                match kind {
+                    MethodKind::Msg => {
+                        // Show the owner as detail for methods declared by a single class, and the
+                        // number of classes otherwise.
+                        let index = doc.index.get();
+                        let mut owners: HashMap<&index::SymbolName, Vec<&index::SymbolName>> =
+                            HashMap::new();
+                        for (class_name, method_name) in index.methods_of_kind(kind) {
+                            let classes = owners.entry(method_name).or_default();
+                            if !classes.contains(&class_name) {
+                                classes.push(class_name);
+                            }
+                        }
+                        index
+                            .all_known_methods(kind)
+                            .drain(..)
+                            .map(|method_name| {
+                                let classes = owners.get(&method_name);
+                                CompletionItem {
+                                    details: classes.map(|classes| match classes.as_slice() {
+                                        [class_name] => format!(" ({class_name})"),
+                                        classes => format!(" ({} classes)", classes.len()),
+                                    }),
+                                    symbol: classes
+                                        .filter(|classes| classes.len() == 1)
+                                        .and_then(|_| index.find_methods(&method_name, kind).next())
+                                        .cloned(),
+                                    label: method_name.to_string(),
+                                    kind: CompletionItemKind::Method,
+                                    ..Default::default()
+                                }
+                            })
+                            .collect()
+                    }
+                    MethodKind::Get | MethodKind::Set => doc
+                        .index
                         .get()
                         .all_known_methods(kind)
                         .drain(..)
@@ -125,7 +219,7 @@ impl CodeCompletion {
                                 ..Default::default()
                             },
                         ))
-                        .collect()
+                        .collect(),
                 }
             };
 
@@ -133,7 +227,7 @@ impl CodeCompletion {
             && cursor.goto_leaf_node_at_or_before_point(position)
             && let Some(filter_text) = cursor
                 .is_identifier()
-                .then(|| doc.line_map.text_for_node(&cursor.node()))
+                .then(|| doc.identifier_text_for_node(&cursor.node()))
                 .filter(|text| text.contains('.'))
             && let Some(filter_text) = filter_text.rfind('.').map(|indx| &filter_text[..=indx])
         {
@@ -142,8 +236,10 @@ impl CodeCompletion {
             completions
                 .into_iter()
                 .filter_map(|mut cc| {
-                    if cc.label.len() >= filter_text.len()
-                        && cc.label[..filter_text.len()].eq_ignore_ascii_case(filter_text)
+                    if cc
+                        .label
+                        .get(..filter_text.len())
+                        .is_some_and(|prefix| prefix.to_lowercase() == filter_text.to_lowercase())
                     {
                         cc.label = cc.label[filter_text.len()..].into();
                         Some(cc)
@@ -157,6 +253,28 @@ impl CodeCompletion {
         }
     }
 
+    fn receiver_member_completions(
+        doc: &DataFlexDocument,
+        position: Point,
+        kind: index::MethodKind,
+    ) -> Option<Vec<CompletionItem>> {
+        let reference_resolver = ReferenceResolver::new(doc);
+        let members = reference_resolver.resolve_explicit_receiver_members(position, kind)?;
+        Some(
+            members
+                .map(|member| CompletionItem {
+                    label: member.symbol.name().to_string(),
+                    kind: if VariableSymbol::from_index_symbol(member.symbol).is_some() {
+                        CompletionItemKind::Property
+                    } else {
+                        CompletionItemKind::Method
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+
     fn override_completions(
         doc: &DataFlexDocument,
         position: Point,
@@ -194,6 +312,7 @@ impl CodeCompletion {
                         kind: CompletionItemKind::Method,
                         details: Some(details.clone()),
                         insert_text: Some(format!("{}{}\n    ", m.symbol.name(), details)),
+                        ..Default::default()
                     }
                 })
                 .collect()
@@ -254,6 +373,17 @@ impl CodeCompletion {
     fn paren_expr_completions(doc: &DataFlexDocument, position: Point) -> Vec<CompletionItem> {
         Self::local_variable_completions(doc, position)
             .chain(Self::system_functions(doc))
+            .chain(
+                doc.index
+                    .get()
+                    .all_known_external_functions()
+                    .drain(..)
+                    .map(|function_name| CompletionItem {
+                        label: function_name.to_string(),
+                        kind: CompletionItemKind::Function,
+                        ..Default::default()
+                    }),
+            )
             .chain(
                 doc.index
                     .get()
@@ -405,7 +535,25 @@ impl CodeCompletion {
         }
     }
 
-    fn command_completions(doc: &DataFlexDocument) -> Vec<CompletionItem> {
+    /// Commands and types, except that a command starting with `End_` inside an unclosed block is
+    /// completed with just the footer of that block.
+    fn command_completions(doc: &DataFlexDocument, position: Point) -> Vec<CompletionItem> {
+        if let Some(range) = doc.identifier_at(position)
+            && doc
+                .line_map
+                .text_in_range(range.start, position)
+                .to_ascii_lowercase()
+                .starts_with("end_")
+            && let Some(close_scope) =
+                scope_balancer::ScopeBalancer::enclosing_close_scope(doc, position)
+        {
+            return vec![CompletionItem {
+                label: close_scope.to_string(),
+                kind: CompletionItemKind::Command,
+                ..Default::default()
+            }];
+        }
+
         Self::system_commands(doc)
             .chain(
                 doc.index
@@ -455,12 +603,12 @@ impl CodeCompletion {
         doc: &DataFlexDocument,
         position: Point,
     ) -> impl Iterator<Item = CompletionItem> {
-        let reference_resolver = ReferenceResolver::new(doc);
-        reference_resolver
-            .local_variables(position)
-            .map(|variable| CompletionItem {
-                label: variable.symbol_path.name().to_string(),
+        doc.locals_in_scope(position)
+            .into_iter()
+            .map(|(name, data_type)| CompletionItem {
+                label: name.to_string(),
                 kind: CompletionItemKind::LocalVariable,
+                details: Some(data_type),
                 ..Default::default()
             })
     }
@@ -580,4 +728,206 @@ End_Procedure
         let completions = CodeCompletion::code_completion(&doc, Point::new(11, 42), false).unwrap();
         assert_eq!(completions.len(), 1);
     }
+
+    #[test]
+    fn test_footer_completions() {
+        let test_content = "Class cTest is a cObject\n    Procedure Test\n        End_\n";
+        let index = index::IndexRef::make_test_index_ref();
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index);
+        let completions = CodeCompletion::code_completion(&doc, Point::new(2, 12), false).unwrap();
+        assert_eq!(completions[0].label, "End_Procedure");
+        assert_eq!(completions.len(), 1);
+    }
+
+    #[test]
+    fn test_local_variable_completions() {
+        let test_content = r#"
+Object oTest is a cObject
+    Procedure Test
+        String sName
+        Integer iCount
+        Move 1 to iC
+    End_Procedure
+
+    Procedure Other
+        Move 1 to iC
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        assert_eq!(
+            doc.locals_in_scope(Point::new(5, 20)),
+            vec![
+                ("sName".into(), "String".to_string()),
+                ("iCount".into(), "Integer".to_string())
+            ]
+        );
+        assert!(doc.locals_in_scope(Point::new(9, 20)).is_empty());
+
+        let completions = CodeCompletion::code_completion(&doc, Point::new(5, 20), false).unwrap();
+        let local = completions.iter().find(|c| c.label == "iCount").unwrap();
+        assert!(matches!(local.kind, CompletionItemKind::LocalVariable));
+        assert_eq!(local.details.as_deref(), Some("Integer"));
+
+        let completions = CodeCompletion::code_completion(&doc, Point::new(9, 20), false).unwrap();
+        assert!(!completions.iter().any(|c| c.label == "iCount"));
+    }
+
+    #[test]
+    fn test_completion_details_and_documentation() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            r#"
+{ Description = "Says hello" }
+Class cGreeter is a cObject
+    { Description = "Greets the user" }
+    Procedure Greet
+    End_Procedure
+
+    Procedure Shared
+    End_Procedure
+End_Class
+
+Class cOther is a cObject
+    Procedure Shared
+    End_Procedure
+End_Class
+            "#,
+            "greeter.pkg".into(),
+            &index,
+        );
+        let test_content = "Object oTest is a cGr\n    Procedure Test\n        Send Gr\n    End_Procedure\nEnd_Object\n";
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let completions = CodeCompletion::code_completion(&doc, Point::new(0, 21), false).unwrap();
+        let class = completions.iter().find(|c| c.label == "cGreeter").unwrap();
+        assert_eq!(class.details.as_deref(), Some(" is a cObject"));
+        let documentation = |item: &CompletionItem| {
+            item.symbol
+                .as_ref()
+                .and_then(|symbol| CodeCompletion::documentation(&index.get(), symbol))
+        };
+        assert_eq!(documentation(class).as_deref(), Some("Says hello"));
+
+        let completions = CodeCompletion::code_completion(&doc, Point::new(2, 15), false).unwrap();
+        let method = completions.iter().find(|c| c.label == "Greet").unwrap();
+        assert_eq!(method.details.as_deref(), Some(" (cGreeter)"));
+        assert_eq!(documentation(method).as_deref(), Some("Greets the user"));
+        let method = completions.iter().find(|c| c.label == "Shared").unwrap();
+        assert_eq!(method.details.as_deref(), Some(" (2 classes)"));
+        assert!(method.symbol.is_none());
+    }
+
+    #[test]
+    fn test_class_completions_merge_local_and_index() {
+        let test_content =
+            "Class cMyClass is a cObject\nEnd_Class\n\nObject oTest is a cMy\nEnd_Object\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            "Class cMyClass is a cObject\nEnd_Class\n",
+            "test.pkg".into(),
+            &index,
+        );
+        index::Indexer::index_test_content(
+            "Class cOtherClass is a cObject\nEnd_Class\n",
+            "other.pkg".into(),
+            &index,
+        );
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let completions = CodeCompletion::code_completion(&doc, Point::new(3, 21), false).unwrap();
+        let my_class: Vec<_> = completions
+            .iter()
+            .filter(|c| c.label == "cMyClass")
+            .collect();
+        assert_eq!(my_class.len(), 1);
+        assert!(my_class[0].local);
+        assert_eq!(completions[0].label, "cMyClass");
+        assert!(
+            completions
+                .iter()
+                .any(|c| c.label == "cOtherClass" && !c.local)
+        );
+    }
+
+    #[test]
+    fn test_send_completions_with_receiver() {
+        let test_content = r#"
+Class cChild is a cBaseClass
+    Procedure DoChild
+    End_Procedure
+End_Class
+
+Class cOther is a cBaseClass
+    Procedure DoOther
+    End_Procedure
+End_Class
+
+Object oMain is a cOther
+    Object oChild is a cChild
+    End_Object
+
+    Procedure Test
+        Send DoChild of oChild
+        Send DoChild of oUnknown
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        let labels = |position| {
+            let mut labels: Vec<String> = CodeCompletion::code_completion(&doc, position, false)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.label)
+                .collect();
+            labels.sort();
+            labels
+        };
+
+        assert_eq!(labels(Point::new(16, 15)), ["DoChild"]);
+        // Fall back to all known methods when the receiver can't be resolved.
+        assert_eq!(labels(Point::new(17, 15)), ["DoChild", "DoOther", "Test"]);
+    }
+
+    #[test]
+    fn test_set_completions_with_receiver() {
+        let test_content = r#"
+Class cChild is a cBaseClass
+    Procedure Construct_Object
+        Property Integer pValue 0
+    End_Procedure
+End_Class
+
+Class cOther is a cBaseClass
+    Procedure Construct_Object
+        Property Integer pValue 0
+        Property Integer pOther 0
+    End_Procedure
+End_Class
+
+Object oMain is a cOther
+    Object oChild is a cChild
+    End_Object
+
+    Procedure Test
+        Set pValue of oChild to 1
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        let completions = CodeCompletion::code_completion(&doc, Point::new(18, 14), false).unwrap();
+        assert_eq!(
+            completions
+                .iter()
+                .map(|c| c.label.as_str())
+                .collect::<Vec<_>>(),
+            vec!["pValue"]
+        );
+    }
 }
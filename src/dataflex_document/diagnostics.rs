@@ -0,0 +1,619 @@
+use std::sync::OnceLock;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosticsOptions {
+    pub mismatched_footers: bool,
+    pub unknown_classes: bool,
+    pub syntax_errors: bool,
+    pub inheritance_cycles: bool,
+    pub use_cycles: bool,
+}
+
+pub struct Diagnostics;
+
+impl Diagnostics {
+    /// Runs each enabled check and merges the results. The checks run from most to least
+    /// specific, and a diagnostic is dropped when the same check already reported one covering
+    /// its whole range.
+    pub fn diagnostics(
+        doc: &DataFlexDocument,
+        options: DiagnosticsOptions,
+    ) -> Vec<lsp_types::Diagnostic> {
+        let checks: [(bool, fn(&DataFlexDocument) -> Vec<lsp_types::Diagnostic>); 5] = [
+            (options.mismatched_footers, Self::mismatched_footers),
+            (options.inheritance_cycles, Self::inheritance_cycles),
+            (options.use_cycles, Self::use_cycles),
+            (options.unknown_classes, Self::unknown_classes),
+            (options.syntax_errors, Self::syntax_errors),
+        ];
+
+        let mut diagnostics: Vec<lsp_types::Diagnostic> = Vec::new();
+        for (_, check) in checks.iter().filter(|(enabled, _)| *enabled) {
+            let mut check_diagnostics: Vec<lsp_types::Diagnostic> = Vec::new();
+            for diagnostic in check(doc) {
+                if !check_diagnostics
+                    .iter()
+                    .any(|d| Self::is_containing(&d.range, &diagnostic.range))
+                {
+                    check_diagnostics.push(diagnostic);
+                }
+            }
+            diagnostics.extend(check_diagnostics);
+        }
+        diagnostics
+    }
+
+    pub fn mismatched_footers(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let mut diagnostics: Vec<lsp_types::Diagnostic> =
+            scope_balancer::ScopeBalancer::mismatched_close_scopes(doc)
+                .into_iter()
+                .map(|mismatched| {
+                    Self::diagnostic(
+                        doc,
+                        &mismatched.range,
+                        lsp_types::DiagnosticSeverity::WARNING,
+                        format!(
+                            "Mismatched {}, expected {}",
+                            mismatched.found, mismatched.expected
+                        ),
+                    )
+                })
+                .collect();
+        diagnostics.extend(Self::stray_statements(doc));
+        diagnostics
+    }
+
+    pub fn unknown_classes(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let Some(node) = doc.root_node() else {
+            return Vec::new();
+        };
+        let Some(query) = Self::unknown_classes_query() else {
+            return Vec::new();
+        };
+        let Some(superclass_capture_index) = query.capture_index_for_name("superclass") else {
+            return Vec::new();
+        };
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        let mut matches = query_cursor.matches(query, node, doc.line_map.text_provider());
+
+        let index = doc.index.get();
+        let mut diagnostics = Vec::new();
+        while let Some(query_match) = matches.next() {
+            for superclass_node in query_match.nodes_for_capture_index(superclass_capture_index) {
+                let superclass = doc.line_map.text_for_node(&superclass_node);
+                if !index.is_known_class(&superclass.as_str().into()) {
+                    diagnostics.push(Self::diagnostic(
+                        doc,
+                        &(superclass_node.start_position()..superclass_node.end_position()),
+                        lsp_types::DiagnosticSeverity::WARNING,
+                        format!("Unknown class {superclass}"),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    pub fn inheritance_cycles(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let Some(node) = doc.root_node() else {
+            return Vec::new();
+        };
+        let Some(query) = Self::inheritance_cycles_query() else {
+            return Vec::new();
+        };
+        let (Some(name_capture_index), Some(superclass_capture_index)) = (
+            query.capture_index_for_name("name"),
+            query.capture_index_for_name("superclass"),
+        ) else {
+            return Vec::new();
+        };
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        let mut matches = query_cursor.matches(query, node, doc.line_map.text_provider());
+
+        let index = doc.index.get();
+        let mut diagnostics = Vec::new();
+        while let Some(query_match) = matches.next() {
+            let name_node = query_match
+                .nodes_for_capture_index(name_capture_index)
+                .next();
+            let superclass_node = query_match
+                .nodes_for_capture_index(superclass_capture_index)
+                .next();
+            if let Some((name_node, superclass_node)) = name_node.zip(superclass_node)
+                && let Some(cycle) =
+                    index.inheritance_cycle(&doc.line_map.text_for_node(&name_node).into())
+            {
+                let cycle: Vec<String> = cycle.iter().map(|name| name.to_string()).collect();
+                diagnostics.push(Self::diagnostic(
+                    doc,
+                    &(superclass_node.start_position()..superclass_node.end_position()),
+                    lsp_types::DiagnosticSeverity::ERROR,
+                    format!("Inheritance cycle: {}", cycle.join(" is a ")),
+                ));
+            }
+        }
+        diagnostics
+    }
+
+    pub fn use_cycles(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let Some(node) = doc.root_node() else {
+            return Vec::new();
+        };
+        let Some(query) = Self::use_cycles_query() else {
+            return Vec::new();
+        };
+        let Some(path_capture_index) = query.capture_index_for_name("path") else {
+            return Vec::new();
+        };
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        let mut matches = query_cursor.matches(query, node, doc.line_map.text_provider());
+
+        let index = doc.index.get();
+        let file_ref = index::IndexFileRef::from(&doc.file_path);
+        let mut diagnostics = Vec::new();
+        while let Some(query_match) = matches.next() {
+            for path_node in query_match.nodes_for_capture_index(path_capture_index) {
+                let dependency = index::IndexFileRef::from(doc.line_map.text_for_node(&path_node));
+                if let Some(cycle) = index.dependency_cycle(&file_ref, &dependency) {
+                    let cycle: Vec<String> = cycle
+                        .iter()
+                        .map(|path| {
+                            path.file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into_owned()
+                        })
+                        .collect();
+                    diagnostics.push(Self::diagnostic(
+                        doc,
+                        &(path_node.start_position()..path_node.end_position()),
+                        lsp_types::DiagnosticSeverity::INFORMATION,
+                        format!("Use cycle: {}", cycle.join(" uses ")),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    pub fn syntax_errors(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let Some(mut cursor) = doc.tree().map(|tree| tree.walk()) else {
+            return Vec::new();
+        };
+
+        let mut diagnostics = Vec::new();
+        loop {
+            let node = cursor.node();
+            let range = node.start_position()..node.end_position();
+            let descend = if node.is_error() {
+                diagnostics.push(Self::diagnostic(
+                    doc,
+                    &range,
+                    lsp_types::DiagnosticSeverity::ERROR,
+                    String::from("Syntax error"),
+                ));
+                false
+            } else if node.is_missing() {
+                diagnostics.push(Self::diagnostic(
+                    doc,
+                    &range,
+                    lsp_types::DiagnosticSeverity::ERROR,
+                    format!("Syntax error, missing {}", node.kind()),
+                ));
+                false
+            } else {
+                node.has_error()
+            };
+
+            if descend && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return diagnostics;
+                }
+            }
+        }
+    }
+
+    /// Reports top level footers like `End_Object` that don't close anything, along with the
+    /// statements between them and the block they were meant to close. Those are usually the
+    /// result of an earlier footer closing the block too soon. Only footers preceded by a block of
+    /// the same kind are considered, to keep false positives down.
+    fn stray_statements(doc: &DataFlexDocument) -> Vec<lsp_types::Diagnostic> {
+        let Some(root) = doc.root_node() else {
+            return Vec::new();
+        };
+
+        let mut diagnostics = Vec::new();
+        for node in root.named_children(&mut root.walk()) {
+            let Some((close_scope, header_kind)) =
+                scope_balancer::ScopeBalancer::unmatched_close_scope(doc, &node)
+            else {
+                continue;
+            };
+            let mut statements = Vec::new();
+            let mut prev = node.prev_named_sibling();
+            while let Some(prev_node) = prev
+                && !prev_node
+                    .child(0)
+                    .is_some_and(|header| header.kind() == header_kind)
+            {
+                if !prev_node.is_extra() {
+                    statements.push(prev_node);
+                }
+                prev = prev_node.prev_named_sibling();
+            }
+            if prev.is_none() {
+                continue;
+            }
+            if statements.iter().any(|statement| {
+                statement.has_error()
+                    || scope_balancer::ScopeBalancer::unmatched_close_scope(doc, statement)
+                        .is_some()
+            }) {
+                continue;
+            }
+
+            for statement in statements.iter().rev() {
+                diagnostics.push(Self::diagnostic(
+                    doc,
+                    &(statement.start_position()..statement.end_position()),
+                    lsp_types::DiagnosticSeverity::WARNING,
+                    format!("Statement is outside of the block closed before {close_scope}"),
+                ));
+            }
+            diagnostics.push(Self::diagnostic(
+                doc,
+                &(node.start_position()..node.end_position()),
+                lsp_types::DiagnosticSeverity::WARNING,
+                format!("Unmatched {close_scope}"),
+            ));
+        }
+        diagnostics
+    }
+
+    fn diagnostic(
+        doc: &DataFlexDocument,
+        range: &std::ops::Range<Point>,
+        severity: lsp_types::DiagnosticSeverity,
+        message: String,
+    ) -> lsp_types::Diagnostic {
+        lsp_types::Diagnostic {
+            range: doc.lsp_range(range),
+            severity: Some(severity),
+            source: Some(String::from("dataflex-lsp")),
+            message,
+            ..Default::default()
+        }
+    }
+
+    fn unknown_classes_query() -> Option<&'static tree_sitter::Query> {
+        static UNKNOWN_CLASSES_QUERY: OnceLock<Option<tree_sitter::Query>> = OnceLock::new();
+        UNKNOWN_CLASSES_QUERY
+            .get_or_init(|| {
+                Self::load_query(
+                    "[(class_header superclass: (identifier) @superclass) (composite_header superclass: (identifier) @superclass) (object_header superclass: (identifier) @superclass)]",
+                )
+            })
+            .as_ref()
+    }
+
+    fn inheritance_cycles_query() -> Option<&'static tree_sitter::Query> {
+        static INHERITANCE_CYCLES_QUERY: OnceLock<Option<tree_sitter::Query>> = OnceLock::new();
+        INHERITANCE_CYCLES_QUERY
+            .get_or_init(|| {
+                Self::load_query(
+                    "[(class_header name: (identifier) @name superclass: (identifier) @superclass) (composite_header name: (identifier) @name superclass: (identifier) @superclass)]",
+                )
+            })
+            .as_ref()
+    }
+
+    fn use_cycles_query() -> Option<&'static tree_sitter::Query> {
+        static USE_CYCLES_QUERY: OnceLock<Option<tree_sitter::Query>> = OnceLock::new();
+        USE_CYCLES_QUERY
+            .get_or_init(|| Self::load_query("(use_statement (file_path) @path)"))
+            .as_ref()
+    }
+
+    fn load_query(source: &str) -> Option<tree_sitter::Query> {
+        tree_sitter::Query::new(&tree_sitter_dataflex::LANGUAGE.into(), source)
+            .inspect_err(|e| log::error!("Error loading diagnostics query: {e}"))
+            .ok()
+    }
+
+    fn is_containing(outer: &lsp_types::Range, inner: &lsp_types::Range) -> bool {
+        outer.start <= inner.start && inner.end <= outer.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_OPTIONS: DiagnosticsOptions = DiagnosticsOptions {
+        mismatched_footers: true,
+        unknown_classes: true,
+        syntax_errors: true,
+        inheritance_cycles: true,
+        use_cycles: true,
+    };
+
+    #[test]
+    fn test_diagnostics_options() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cObject\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let messages = |options: DiagnosticsOptions| {
+            Diagnostics::diagnostics(&doc, options)
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>()
+        };
+        assert!(
+            messages(ALL_OPTIONS)
+                .contains(&String::from("Mismatched End_Class, expected End_Object"))
+        );
+
+        let mut options = ALL_OPTIONS;
+        options.mismatched_footers = false;
+        assert!(
+            !messages(options)
+                .iter()
+                .any(|message| message.starts_with("Mismatched"))
+        );
+    }
+
+    #[test]
+    fn test_unknown_class() {
+        let test_content = r#"
+Class cKnown is a cObject
+End_Class
+
+Object oTest is a cUnknown
+End_Object
+
+Object oOther is a cKnown
+End_Object
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        index::Indexer::index_test_content(
+            "Class cObject is a cObject\nEnd_Class\n",
+            "object.pkg".into(),
+            &index,
+        );
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        assert_eq!(
+            Diagnostics::unknown_classes(&doc)
+                .iter()
+                .map(|d| (d.range, d.severity, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [(
+                lsp_types::Range::new(
+                    lsp_types::Position::new(4, 18),
+                    lsp_types::Position::new(4, 26),
+                ),
+                Some(lsp_types::DiagnosticSeverity::WARNING),
+                "Unknown class cUnknown"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_and_unknown_class() {
+        let test_content = r#"
+Class cKnown is a cObject
+End_Class
+
+Object oTest is a cUnknown
+End_Object
+
+Object oOther is a cKnown
+    Procedure Test
+        Move (1 + 2 to i
+    End_Procedure
+End_Object
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        index::Indexer::index_test_content(
+            "Class cObject is a cObject\nEnd_Class\n",
+            "object.pkg".into(),
+            &index,
+        );
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let diagnostics = Diagnostics::diagnostics(&doc, ALL_OPTIONS);
+        let unknown_class: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.message.starts_with("Unknown class"))
+            .collect();
+        assert_eq!(unknown_class.len(), 1);
+        assert_eq!(unknown_class[0].message, "Unknown class cUnknown");
+        assert_eq!(
+            unknown_class[0].range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(4, 18),
+                lsp_types::Position::new(4, 26),
+            )
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .any(|d| d.message.starts_with("Syntax error") && d.range.start.line >= 7)
+        );
+
+        let diagnostics = Diagnostics::diagnostics(
+            &doc,
+            DiagnosticsOptions {
+                unknown_classes: false,
+                ..ALL_OPTIONS
+            },
+        );
+        assert!(
+            !diagnostics
+                .iter()
+                .any(|d| d.message.starts_with("Unknown class"))
+        );
+    }
+
+    #[test]
+    fn test_dangling_object_header() {
+        let test_content = "Object oX is a\nEnd_Object\n";
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+
+        let diagnostics = Diagnostics::syntax_errors(&doc);
+        assert!(diagnostics.iter().any(|d| {
+            d.severity == Some(lsp_types::DiagnosticSeverity::ERROR)
+                && d.message.starts_with("Syntax error")
+                && d.range.start.line == 0
+        }));
+    }
+
+    #[test]
+    fn test_inheritance_cycle() {
+        let test_content = "Class cA is a cB\nEnd_Class\n\nClass cB is a cA\nEnd_Class\n";
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        assert_eq!(
+            Diagnostics::inheritance_cycles(&doc)
+                .iter()
+                .map(|d| (d.range.start.line, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (0, "Inheritance cycle: cA is a cB is a cA"),
+                (3, "Inheritance cycle: cB is a cA is a cB"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_use_cycle() {
+        let index = index::IndexRef::make_test_index_ref();
+        let a_content = "Use b.pkg\n";
+        let b_content = "Use Windows.pkg\nUse A.pkg\n";
+        index::Indexer::index_test_content(a_content, "a.pkg".into(), &index);
+        index::Indexer::index_test_content(b_content, "b.pkg".into(), &index);
+        let use_cycles = |path: &str, content| {
+            let doc = DataFlexDocument::new(path.into(), content, index.clone());
+            Diagnostics::use_cycles(&doc)
+                .iter()
+                .map(|d| (d.range.start.line, d.severity, d.message.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            use_cycles("a.pkg", a_content),
+            [(
+                0,
+                Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                String::from("Use cycle: a.pkg uses b.pkg uses a.pkg")
+            )]
+        );
+        assert_eq!(
+            use_cycles("b.pkg", b_content),
+            [(
+                1,
+                Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                String::from("Use cycle: b.pkg uses a.pkg uses b.pkg")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_stray_statement_after_footer() {
+        let test_content = r#"Object oTest is a cObject
+    Procedure Test
+    End_Procedure
+End_Object
+    Send Test
+End_Object
+
+Object oOther is a cObject
+End_Object
+"#;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            Diagnostics::mismatched_footers(&doc)
+                .iter()
+                .map(|d| (d.range.start, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    lsp_types::Position::new(4, 4),
+                    "Statement is outside of the block closed before End_Object"
+                ),
+                (lsp_types::Position::new(5, 0), "Unmatched End_Object"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_diagnostics_from_different_checks() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Class\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let diagnostics = Diagnostics::diagnostics(&doc, ALL_OPTIONS);
+        assert!(
+            diagnostics
+                .iter()
+                .any(|d| d.message == "Mismatched End_Class, expected End_Object")
+        );
+        assert!(
+            Diagnostics::syntax_errors(&doc)
+                .iter()
+                .all(|syntax_error| diagnostics.contains(syntax_error))
+        );
+    }
+
+    #[test]
+    fn test_touching_diagnostics() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cObject\nEnd_Object\n    Send Test\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let diagnostics = Diagnostics::diagnostics(
+            &doc,
+            DiagnosticsOptions {
+                mismatched_footers: true,
+                unknown_classes: false,
+                syntax_errors: false,
+                inheritance_cycles: false,
+                use_cycles: false,
+            },
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.range.start, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    lsp_types::Position::new(2, 4),
+                    "Statement is outside of the block closed before End_Object"
+                ),
+                (lsp_types::Position::new(3, 0), "Unmatched End_Object"),
+            ]
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use super::*;
 use index::MethodKind;
+use serde::Serialize;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize)]
 pub enum DocumentContext {
     ClassReference,
     MethodReference(MethodKind),
     Expression,
+    PropertyReference,
     ParenExpression,
     DotMemberExpression,
     CommandReference,
@@ -169,7 +171,12 @@ impl DocumentContext {
         cursor.goto_start_of_command_for_line(position.row);
 
         let scanner = ContextScanner::new(cursor, position);
-        Self::context_with_scanner(scanner, doc)
+        match Self::context_with_scanner(scanner, doc) {
+            Some(Self::Expression) if Self::is_property_reference(doc, position) => {
+                Some(Self::PropertyReference)
+            }
+            context => context,
+        }
     }
 
     pub fn can_reference_variables(&self) -> bool {
@@ -177,6 +184,7 @@ impl DocumentContext {
             Self::Expression => true,
             Self::ParenExpression => true,
             Self::ClassReference => false,
+            Self::PropertyReference => false,
             Self::MethodReference(_) => false,
             Self::DotMemberExpression => false,
             Self::CommandReference => false,
@@ -191,6 +199,7 @@ impl DocumentContext {
             Self::ClassReference => false,
             Self::MethodReference(_) => false,
             Self::Expression => true,
+            Self::PropertyReference => false,
             Self::ParenExpression => true,
             Self::DotMemberExpression => true,
             Self::CommandReference => false,
@@ -206,6 +215,7 @@ impl DocumentContext {
             Self::ClassReference => false,
             Self::MethodReference(_) => false,
             Self::Expression => false,
+            Self::PropertyReference => false,
             Self::ParenExpression => false,
             Self::DotMemberExpression => false,
             Self::CommandReference => false,
@@ -214,6 +224,17 @@ impl DocumentContext {
         }
     }
 
+    /// A plain identifier in an expression refers to a property of the enclosing object or class,
+    /// unless it's shadowed by a local variable.
+    fn is_property_reference(doc: &DataFlexDocument, position: Point) -> bool {
+        let resolver = ReferenceResolver::new(doc);
+        resolver.resolve_local_variable(position).is_none()
+            && resolver
+                .resolve_property_reference(position)
+                .next()
+                .is_some()
+    }
+
     fn context_with_scanner(mut scanner: ContextScanner, doc: &DataFlexDocument) -> Option<Self> {
         if scanner.cursor.node().end_position() >= scanner.end {
             return Some(Self::CommandReference);
@@ -307,8 +328,20 @@ impl DocumentContext {
                 scanner.cursor.goto_next_leaf_node();
                 Self::context_with_scanner(scanner, doc)
             }
-            // Default fallback to recognize expression context as appropriate for all other commands.
-            _ => context_scanner_match!(scanner, expr*),
+            _ => {
+                let command_kind = doc
+                    .index
+                    .get()
+                    .find_command(&text.as_str().into())
+                    .map(|command| command.kind);
+                if let Some(kind) = command_kind {
+                    // Custom commands defined with `#COMMAND` that expand to a send, get or set.
+                    context_scanner_match!(scanner, identifier -> Self::MethodReference(kind), expr*)
+                } else {
+                    // Default fallback to recognize expression context as appropriate for all other commands.
+                    context_scanner_match!(scanner, expr*)
+                }
+            }
         };
 
         context
@@ -1201,6 +1234,35 @@ mod test {
         assert_eq!(context, Some(DocumentContext::CommandReference));
     }
 
+    #[test]
+    fn test_custom_command_context() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            "#COMMAND Send_Refresh R\n    Send !1 of !2\n#ENDCOMMAND\n\n#COMMAND Show_Value\n    Showln !1\n#ENDCOMMAND\n",
+            "commands.pkg".into(),
+            &index,
+        );
+        assert_eq!(
+            index.get().find_command(&"send_refresh".into()),
+            Some(&index::CommandDefinition {
+                name: "Send_Refresh".into(),
+                kind: MethodKind::Msg
+            })
+        );
+        assert_eq!(index.get().find_command(&"Show_Value".into()), None);
+
+        let doc = DataFlexDocument::new("test.pkg".into(), "Send_Refresh Upd\n", index.clone());
+        let context = DocumentContext::context(&doc, Point { row: 0, column: 16 });
+        assert_eq!(
+            context,
+            Some(DocumentContext::MethodReference(MethodKind::Msg))
+        );
+
+        let doc = DataFlexDocument::new("test.pkg".into(), "Show_Value iVal\n", index.clone());
+        let context = DocumentContext::context(&doc, Point { row: 0, column: 15 });
+        assert_eq!(context, Some(DocumentContext::Expression));
+    }
+
     #[test]
     fn test_file_dependency_context() {
         let doc = DataFlexDocument::new(
@@ -1396,4 +1458,40 @@ mod test {
             Some(DocumentContext::MethodReference(MethodKind::Msg))
         );
     }
+
+    #[test]
+    fn test_class_reference_context_with_comment_in_header() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a ; // The class
+    cTest
+End_Object
+",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let context = DocumentContext::context(&doc, Point { row: 1, column: 6 });
+        assert_eq!(context, Some(DocumentContext::ClassReference));
+
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is ; // The class
+    a cTest
+End_Object
+",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let context = DocumentContext::context(&doc, Point { row: 1, column: 8 });
+        assert_eq!(context, Some(DocumentContext::ClassReference));
+
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Class cTest is a ; // The superclass
+    cBase
+End_Class
+",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let context = DocumentContext::context(&doc, Point { row: 1, column: 6 });
+        assert_eq!(context, Some(DocumentContext::ClassReference));
+    }
 }
@@ -0,0 +1,153 @@
+use super::*;
+
+pub struct FoldingRanges;
+
+impl FoldingRanges {
+    /// Folds for class, object and method definitions, from the header line to the footer line,
+    /// and for runs of consecutive comment lines.
+    pub fn folding_ranges(doc: &DataFlexDocument) -> Vec<lsp_types::FoldingRange> {
+        let mut ranges = Vec::new();
+        if let Some(root_node) = doc.root_node() {
+            Self::collect_definition_ranges(root_node, &mut ranges);
+        }
+        ranges.extend(Self::comment_ranges(doc));
+        ranges.sort_by_key(|range| (range.start_line, std::cmp::Reverse(range.end_line)));
+        ranges
+    }
+
+    fn collect_definition_ranges(
+        node: tree_sitter::Node,
+        ranges: &mut Vec<lsp_types::FoldingRange>,
+    ) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if matches!(
+                child.kind(),
+                "class_definition"
+                    | "object_definition"
+                    | "composite_definition"
+                    | "procedure_definition"
+                    | "function_definition"
+            ) {
+                let start_line = child.start_position().row;
+                let end = child.end_position();
+                // A definition ending with the line break after the footer ends on the footer line.
+                let end_line = if end.column == 0 && end.row > start_line {
+                    end.row - 1
+                } else {
+                    end.row
+                };
+                if end_line > start_line {
+                    ranges.push(Self::folding_range(
+                        start_line,
+                        end_line,
+                        lsp_types::FoldingRangeKind::Region,
+                    ));
+                }
+            }
+            Self::collect_definition_ranges(child, ranges);
+        }
+    }
+
+    fn comment_ranges(doc: &DataFlexDocument) -> Vec<lsp_types::FoldingRange> {
+        let is_comment_line = |line| {
+            doc.line_map
+                .line_text_with_ending(line)
+                .is_some_and(|text| text.trim_start().starts_with("//"))
+        };
+        let mut ranges = Vec::new();
+        let mut line = 0;
+        while line < doc.line_map.line_count() {
+            if is_comment_line(line) {
+                let start_line = line;
+                while is_comment_line(line + 1) {
+                    line += 1;
+                }
+                if line > start_line {
+                    ranges.push(Self::folding_range(
+                        start_line,
+                        line,
+                        lsp_types::FoldingRangeKind::Comment,
+                    ));
+                }
+            }
+            line += 1;
+        }
+        ranges
+    }
+
+    fn folding_range(
+        start_line: usize,
+        end_line: usize,
+        kind: lsp_types::FoldingRangeKind,
+    ) -> lsp_types::FoldingRange {
+        lsp_types::FoldingRange {
+            start_line: start_line as u32,
+            end_line: end_line as u32,
+            kind: Some(kind),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_ranges() {
+        let test_content = r#"// Test object
+// with a comment
+Object oTest is a cObject
+    Procedure Test
+        Send Foo
+    End_Procedure
+
+    Procedure Empty
+    End_Procedure
+
+    // Single line comment
+    Object oNested is a cObject
+        Function Value Returns Integer
+            Function_Return 1
+        End_Function
+    End_Object
+End_Object
+"#;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+        let ranges: Vec<(u32, u32, lsp_types::FoldingRangeKind)> =
+            FoldingRanges::folding_ranges(&doc)
+                .into_iter()
+                .map(|range| (range.start_line, range.end_line, range.kind.unwrap()))
+                .collect();
+        assert_eq!(
+            ranges,
+            [
+                (0, 1, lsp_types::FoldingRangeKind::Comment),
+                (2, 16, lsp_types::FoldingRangeKind::Region),
+                (3, 5, lsp_types::FoldingRangeKind::Region),
+                (7, 8, lsp_types::FoldingRangeKind::Region),
+                (11, 15, lsp_types::FoldingRangeKind::Region),
+                (12, 14, lsp_types::FoldingRangeKind::Region),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folding_ranges_without_trailing_line_break() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Class cTest is a cObject\nEnd_Class\nObject oTest is a cTest\nEnd_Object",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let ranges: Vec<(u32, u32)> = FoldingRanges::folding_ranges(&doc)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        assert_eq!(ranges, [(0, 1), (2, 3)]);
+    }
+}
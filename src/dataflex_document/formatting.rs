@@ -0,0 +1,189 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WhitespaceOptions {
+    pub trim_trailing_whitespace: bool,
+    pub insert_final_newline: bool,
+}
+
+pub struct WhitespaceFormatter;
+
+impl WhitespaceFormatter {
+    pub fn edits(doc: &DataFlexDocument, options: WhitespaceOptions) -> Vec<lsp_types::TextEdit> {
+        let line_map = &doc.line_map;
+        let last_row = line_map.line_count().saturating_sub(1);
+        let is_blank = |content: &str| {
+            if options.trim_trailing_whitespace {
+                content.trim_end_matches([' ', '\t']).is_empty()
+            } else {
+                content.is_empty()
+            }
+        };
+
+        // The last line that has content, any lines after it are trailing blank lines.
+        let last_content_row = (0..line_map.line_count())
+            .rev()
+            .find(|row| !is_blank(Self::line_content(line_map, *row)));
+
+        let mut edits = Vec::new();
+        for row in 0..line_map.line_count() {
+            if options.insert_final_newline && last_content_row.is_none_or(|last| row > last) {
+                break;
+            }
+
+            let content = Self::line_content(line_map, row);
+            let trimmed_len = if options.trim_trailing_whitespace {
+                content.trim_end_matches([' ', '\t']).len()
+            } else {
+                content.len()
+            };
+            let missing_final_newline =
+                options.insert_final_newline && Some(row) == last_content_row && row == last_row;
+
+            if trimmed_len < content.len() || missing_final_newline {
+                edits.push(lsp_types::TextEdit {
+                    range: lsp_types::Range::new(
+                        lsp_types::Position::new(
+                            row as u32,
+                            line_map.byte_column_to_utf16(row, trimmed_len) as u32,
+                        ),
+                        lsp_types::Position::new(
+                            row as u32,
+                            line_map.byte_column_to_utf16(row, content.len()) as u32,
+                        ),
+                    ),
+                    new_text: if missing_final_newline {
+                        String::from("\n")
+                    } else {
+                        String::new()
+                    },
+                });
+            }
+        }
+
+        if options.insert_final_newline
+            && let Some(last_content_row) = last_content_row
+            && last_content_row + 1 < last_row
+        {
+            edits.push(lsp_types::TextEdit {
+                range: lsp_types::Range::new(
+                    lsp_types::Position::new(last_content_row as u32 + 1, 0),
+                    lsp_types::Position::new(
+                        last_row as u32,
+                        line_map.byte_column_to_utf16(
+                            last_row,
+                            Self::line_content(line_map, last_row).len(),
+                        ) as u32,
+                    ),
+                ),
+                new_text: String::new(),
+            });
+        }
+
+        edits
+    }
+
+    fn line_content(line_map: &line_map::LineMap, row: usize) -> &str {
+        line_map
+            .line_text_with_ending(row)
+            .unwrap_or_default()
+            .trim_end_matches(['\r', '\n'])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_OPTIONS: WhitespaceOptions = WhitespaceOptions {
+        trim_trailing_whitespace: true,
+        insert_final_newline: true,
+    };
+
+    #[test]
+    fn test_trim_trailing_whitespace() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest  \n    Move 1 to i\nEnd_Object\t\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            format!("{:?}", WhitespaceFormatter::edits(&doc, ALL_OPTIONS)),
+            "[TextEdit { range: Range { start: Position { line: 0, character: 23 }, end: Position { line: 0, character: 25 } }, new_text: \"\" }, TextEdit { range: Range { start: Position { line: 2, character: 10 }, end: Position { line: 2, character: 11 } }, new_text: \"\" }]"
+        );
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace_after_multibyte_characters() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oCafé is a cTest // 😀  \nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            WhitespaceFormatter::edits(&doc, ALL_OPTIONS)
+                .iter()
+                .map(|edit| edit.range)
+                .collect::<Vec<_>>(),
+            [lsp_types::Range::new(
+                lsp_types::Position::new(0, 29),
+                lsp_types::Position::new(0, 31),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_insert_final_newline() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Object  ",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            format!("{:?}", WhitespaceFormatter::edits(&doc, ALL_OPTIONS)),
+            "[TextEdit { range: Range { start: Position { line: 1, character: 10 }, end: Position { line: 1, character: 12 } }, new_text: \"\\n\" }]"
+        );
+
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Object\n\n  \n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            format!("{:?}", WhitespaceFormatter::edits(&doc, ALL_OPTIONS)),
+            "[TextEdit { range: Range { start: Position { line: 2, character: 0 }, end: Position { line: 4, character: 0 } }, new_text: \"\" }]"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_options_disabled() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest  \nEnd_Object",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                WhitespaceFormatter::edits(
+                    &doc,
+                    WhitespaceOptions {
+                        trim_trailing_whitespace: false,
+                        insert_final_newline: false,
+                    }
+                )
+            ),
+            "[]"
+        );
+
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            format!("{:?}", WhitespaceFormatter::edits(&doc, ALL_OPTIONS)),
+            "[]"
+        );
+    }
+}
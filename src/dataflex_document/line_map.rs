@@ -2,6 +2,8 @@ use line_span::{LineSpan, LineSpanExt, LineSpanIter};
 use std::ops::{Bound, RangeBounds};
 use tree_sitter::{Node, Point};
 
+/// The document text split into lines, each stored with its `\n` or `\r\n` ending. Points use
+/// UTF-8 byte columns within a line, and the columns of a line's content don't include its ending.
 pub struct LineMap {
     lines: Vec<Line>,
 }
@@ -10,6 +12,33 @@ struct Line {
     text: String,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineEnding {
+    None,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn of(text: &str) -> Self {
+        if text.ends_with("\r\n") {
+            Self::CrLf
+        } else if text.ends_with('\n') {
+            Self::Lf
+        } else {
+            Self::None
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Lf => 1,
+            Self::CrLf => 2,
+        }
+    }
+}
+
 impl LineMap {
     pub fn new(text: &str) -> Self {
         let lines: Vec<Line> = text
@@ -137,24 +166,88 @@ impl LineMap {
         self.lines.len()
     }
 
+    /// Converts a point to a byte offset in the text. A column past the end of the line is
+    /// clamped to the line, so it can't end up in the middle of a `\r\n` or on the next line.
     pub fn offset_at_point(&self, point: Point) -> usize {
-        self.lines[..point.row]
+        let column = self
+            .lines
+            .get(point.row)
+            .map_or(point.column, |l| point.column.min(l.text.len()));
+        self.lines[..point.row.min(self.lines.len())]
             .iter()
             .fold(0, |offset, l| offset + l.text.len())
-            + point.column
+            + column
+    }
+
+    /// Converts a UTF-16 column, as used by LSP positions, to a byte column in the given line.
+    pub fn utf16_column_to_byte(&self, row: usize, utf16_column: usize) -> usize {
+        let Some(line) = self.line_text_with_ending(row) else {
+            return utf16_column;
+        };
+        let content = &line[..line.len() - LineEnding::of(line).len()];
+        let mut utf16_offset = 0;
+        for (byte_offset, c) in content.char_indices() {
+            if utf16_offset >= utf16_column {
+                return byte_offset;
+            }
+            utf16_offset += c.len_utf16();
+        }
+        content.len()
     }
 
+    /// Converts a byte column in the given line to a UTF-16 column, as used by LSP positions.
+    pub fn byte_column_to_utf16(&self, row: usize, byte_column: usize) -> usize {
+        let Some(line) = self.line_text_with_ending(row) else {
+            return byte_column;
+        };
+        line.char_indices()
+            .take_while(|(byte_offset, _)| *byte_offset < byte_column)
+            .map(|(_, c)| c.len_utf16())
+            .sum()
+    }
+
+    /// Expands a single line range to cover any adjacent identifier characters, including
+    /// non-ASCII letters.
+    pub fn identifier_range(&self, start: Point, end: Point) -> (Point, Point) {
+        let Some(line) = self
+            .line_text_with_ending(start.row)
+            .filter(|_| start.row == end.row)
+        else {
+            return (start, end);
+        };
+        let is_identifier_char = |c: &char| c.is_alphanumeric() || *c == '_';
+        let start_column = start.column
+            - line[..start.column]
+                .chars()
+                .rev()
+                .take_while(is_identifier_char)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        let end_column = end.column
+            + line[end.column..]
+                .chars()
+                .take_while(is_identifier_char)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        (
+            Point::new(start.row, start_column),
+            Point::new(end.row, end_column),
+        )
+    }
+
+    /// Converts a byte offset in the text to a point. An offset right after a line's ending is the
+    /// start of the next line, while one between the `\r` and `\n` of a `\r\n` stays on the line.
     pub fn point_at_offset(&self, offset: usize) -> Point {
         let mut byte_index = 0;
         for (line_index, line) in self.lines.iter().enumerate() {
-            let line_len = line.text.find_line_end(0);
-            if byte_index + line_len >= offset {
+            let line_end = byte_index + line.text.len();
+            if offset < line_end || (offset == line_end && !line.has_line_ending()) {
                 return Point {
                     row: line_index,
                     column: offset - byte_index,
                 };
             }
-            byte_index += line.text.len();
+            byte_index = line_end;
         }
         Point {
             row: self.lines.len(),
@@ -190,7 +283,7 @@ impl Line {
     }
 
     fn has_line_ending(&self) -> bool {
-        self.text.ends_with("\n")
+        LineEnding::of(&self.text) != LineEnding::None
     }
 }
 
@@ -527,4 +620,86 @@ mod tests {
         assert_eq!(line_map.point_at_offset(28), Point { row: 1, column: 4 });
         assert_eq!(line_map.point_at_offset(35), Point { row: 2, column: 0 });
     }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let mut line_map = LineMap::new("Object oTest is a cTest\r\nEnd_Object\r\n");
+        assert_eq!(line_map.line_count(), 3);
+        assert_eq!(line_map.line_text_with_ending(1), Some("End_Object\r\n"));
+
+        // Columns past the content don't count the ending.
+        assert_eq!(line_map.utf16_column_to_byte(0, 23), 23);
+        assert_eq!(line_map.utf16_column_to_byte(0, 100), 23);
+        assert_eq!(line_map.offset_at_point(Point::new(0, 23)), 23);
+        assert_eq!(line_map.offset_at_point(Point::new(0, 100)), 25);
+        assert_eq!(line_map.offset_at_point(Point::new(1, 0)), 25);
+        assert_eq!(line_map.point_at_offset(23), Point::new(0, 23));
+        assert_eq!(line_map.point_at_offset(24), Point::new(0, 24));
+        assert_eq!(line_map.point_at_offset(25), Point::new(1, 0));
+        assert_eq!(line_map.point_at_offset(37), Point::new(2, 0));
+
+        // Insert a line.
+        line_map.replace_range(
+            Point::new(0, 23),
+            Point::new(0, 23),
+            "\r\n    Procedure Foo",
+        );
+        assert_eq!(
+            line_map.text(),
+            "Object oTest is a cTest\r\n    Procedure Foo\r\nEnd_Object\r\n"
+        );
+        assert_eq!(line_map.line_count(), 4);
+        assert_eq!(
+            line_map.point_at_offset(23 + "\r\n    Procedure Foo".len()),
+            Point::new(1, 17)
+        );
+
+        // Join the lines again by deleting across the `\r\n`.
+        line_map.replace_range(Point::new(0, 23), Point::new(1, 17), "");
+        assert_eq!(line_map.text(), "Object oTest is a cTest\r\nEnd_Object\r\n");
+        line_map.replace_range(Point::new(0, 23), Point::new(1, 0), " ");
+        assert_eq!(line_map.text(), "Object oTest is a cTest End_Object\r\n");
+        assert_eq!(line_map.line_count(), 2);
+    }
+
+    #[test]
+    fn test_utf16_column_to_byte() {
+        let line_map = LineMap::new("Object oCafé is a cObject\nEnd_Object\n");
+        assert_eq!(line_map.utf16_column_to_byte(0, 11), 11);
+        assert_eq!(line_map.utf16_column_to_byte(0, 12), 13);
+        assert_eq!(line_map.utf16_column_to_byte(0, 18), 19);
+        assert_eq!(line_map.utf16_column_to_byte(0, 100), 26);
+        assert_eq!(line_map.utf16_column_to_byte(1, 4), 4);
+
+        // Characters outside the BMP are two UTF-16 code units.
+        let line_map = LineMap::new("// 😀 Smile\n");
+        assert_eq!(line_map.utf16_column_to_byte(0, 3), 3);
+        assert_eq!(line_map.utf16_column_to_byte(0, 5), 7);
+        assert_eq!(line_map.utf16_column_to_byte(0, 6), 8);
+    }
+
+    #[test]
+    fn test_byte_column_to_utf16() {
+        let line_map = LineMap::new("Object oCafé is a cObject\nEnd_Object\n");
+        assert_eq!(line_map.byte_column_to_utf16(0, 11), 11);
+        assert_eq!(line_map.byte_column_to_utf16(0, 13), 12);
+        assert_eq!(line_map.byte_column_to_utf16(0, 25), 24);
+        assert_eq!(line_map.byte_column_to_utf16(1, 4), 4);
+
+        let line_map = LineMap::new("// 😀 Smile\n");
+        assert_eq!(line_map.byte_column_to_utf16(0, 7), 5);
+    }
+
+    #[test]
+    fn test_identifier_range() {
+        let line_map = LineMap::new("Object oCafé is a cObject\nEnd_Object\n");
+        assert_eq!(
+            line_map.identifier_range(Point::new(0, 8), Point::new(0, 9)),
+            (Point::new(0, 7), Point::new(0, 13))
+        );
+        assert_eq!(
+            line_map.identifier_range(Point::new(0, 7), Point::new(0, 13)),
+            (Point::new(0, 7), Point::new(0, 13))
+        );
+    }
 }
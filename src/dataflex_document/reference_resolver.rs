@@ -1,8 +1,8 @@
 use super::*;
 use index::{
     ClassSymbol, DataFlexDataType, IndexFileRef, IndexSymbolIter, IndexSymbolType, MethodKind,
-    QualifiedDataFlexTableRef, QualifiedIndexSymbol, ReadableIndexRef, StructSymbol, SymbolName,
-    VariableSymbol,
+    MethodSymbol, QualifiedDataFlexTableRef, QualifiedIndexSymbol, ReadableIndexRef, StructSymbol,
+    SymbolName, VariableSymbol,
 };
 
 pub struct ReferenceResolver<'a> {
@@ -10,6 +10,16 @@ pub struct ReferenceResolver<'a> {
     index: ReadableIndexRef<'a>,
 }
 
+/// An owned copy of a resolved symbol, which unlike `QualifiedIndexSymbol` doesn't borrow from
+/// the index, so it can outlive the index read lock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedSymbol {
+    pub path: PathBuf,
+    pub location: Point,
+    pub name: SymbolName,
+    pub kind: lsp_types::SymbolKind,
+}
+
 impl<'a> ReferenceResolver<'a> {
     pub fn new(doc: &'a DataFlexDocument) -> Self {
         Self {
@@ -27,6 +37,7 @@ impl<'a> ReferenceResolver<'a> {
             DocumentContext::ClassReference => self.resolve_class_reference(position),
             DocumentContext::MethodReference(kind) => self.resolve_method_reference(position, kind),
             DocumentContext::Expression => self.resolve_expr_reference(position),
+            DocumentContext::PropertyReference => self.resolve_property_reference(position),
             DocumentContext::ParenExpression => self.resolve_paren_expr_reference(position),
             DocumentContext::DotMemberExpression => self.resolve_member_expr_reference(position),
             DocumentContext::CommandReference => self.resolve_type_reference(position),
@@ -36,6 +47,24 @@ impl<'a> ReferenceResolver<'a> {
         }
     }
 
+    pub fn resolve_symbols(
+        &self,
+        context: DocumentContext,
+        position: Point,
+    ) -> Vec<ResolvedSymbol> {
+        self.resolve_reference(context, position)
+            .map(|qualified_symbol| ResolvedSymbol::from(&qualified_symbol))
+            .collect()
+    }
+
+    /// The path of the indexed file referenced by a `Use` statement. Files that aren't indexed
+    /// yet are searched for on disk by the indexer, with its configured directory depth.
+    pub fn resolve_file_dependency(&self, position: Point) -> Option<PathBuf> {
+        let node = self.doc.node_at_position(position)?;
+        let file_ref = IndexFileRef::from(&PathBuf::from(self.doc.line_map.text_for_node(&node)));
+        self.index.find_file_path(&file_ref).cloned()
+    }
+
     pub fn resolve_type_of_variable(
         &self,
         scope: Point,
@@ -183,14 +212,36 @@ impl<'a> ReferenceResolver<'a> {
         vars.into_iter()
     }
 
+    /// The class declared by a class header with its name at the position. The name isn't a
+    /// reference, but resolves to the class itself.
+    pub fn resolve_class_declaration(&self, position: Point) -> Vec<ResolvedSymbol> {
+        let Some(mut cursor) = self.doc.cursor() else {
+            return Vec::new();
+        };
+        if !cursor.goto_descendant_for_point(position) || !cursor.is_identifier() {
+            return Vec::new();
+        }
+        let node = cursor.node();
+        if !node.parent().is_some_and(|header| {
+            matches!(header.kind(), "class_header" | "composite_header")
+                && header.child_by_field_name("name") == Some(node)
+        }) {
+            return Vec::new();
+        }
+        self.resolve_class_reference(position)
+            .map(|qualified_symbol| ResolvedSymbol::from(&qualified_symbol))
+            .collect()
+    }
+
     fn resolve_class_reference(&self, position: Point) -> IndexSymbolIter<'_> {
         let Some(name) = self.doc.symbol_at_position(position) else {
             return IndexSymbolIter::empty();
         };
 
+        let file_ref = IndexFileRef::from(&self.doc.file_path);
         IndexSymbolIter::new(
             self.index
-                .find_class(&name)
+                .find_class_from_file(&name, &file_ref)
                 .and_then(|s| self.index.resolve_symbol(s))
                 .into_iter(),
         )
@@ -201,20 +252,39 @@ impl<'a> ReferenceResolver<'a> {
             return IndexSymbolIter::empty();
         };
 
+        // Prefer methods whose parameter count matches the call, falling back to matching on the
+        // name only.
+        let argument_count = self.call_argument_count(position);
+        let matches_arguments = |member: &&index::IndexSymbolRef| {
+            argument_count.is_none_or(|count| {
+                self.index
+                    .resolve_symbol(member)
+                    .and_then(|s| MethodSymbol::from_index_symbol(s.symbol))
+                    .is_none_or(|method| method.parameters.len() == count)
+            })
+        };
+
         let member = self.resolve_call_receiver(position).and_then(|class| {
             let members: Vec<&index::IndexSymbolRef> =
                 self.index.find_members(&name, kind).collect();
-            self.index
+            let inherited_members: Vec<&index::IndexSymbolRef> = self
+                .index
                 .class_hierarchy(class)
                 .filter_map(|qualified_symbol| {
                     ClassSymbol::from_index_symbol(qualified_symbol.symbol)
                 })
-                .find_map(|class| {
+                .filter_map(|class| {
                     members.iter().find(|member| {
                         member.symbol_path.parent_slice() == class.symbol_path.as_slice()
                     })
                 })
                 .cloned()
+                .collect();
+            inherited_members
+                .iter()
+                .copied()
+                .find(matches_arguments)
+                .or(inherited_members.first().copied())
         });
 
         if let Some(member) = member {
@@ -223,13 +293,57 @@ impl<'a> ReferenceResolver<'a> {
                     .filter_map(|member_ref| self.index.resolve_symbol(member_ref)),
             )
         } else {
-            let members = self.index.find_members(&name, kind);
+            let members: Vec<&index::IndexSymbolRef> =
+                self.index.find_members(&name, kind).collect();
+            let matching_members: Vec<&index::IndexSymbolRef> =
+                members.iter().copied().filter(matches_arguments).collect();
+            let members = if matching_members.is_empty() {
+                members
+            } else {
+                matching_members
+            };
             IndexSymbolIter::new(
-                members.filter_map(|member_ref| self.index.resolve_symbol(member_ref)),
+                members
+                    .into_iter()
+                    .filter_map(|member_ref| self.index.resolve_symbol(member_ref)),
             )
         }
     }
 
+    /// The number of arguments passed by the method call statement at the position, where the
+    /// value of a `Set` counts as an argument since it's the last parameter of the method.
+    fn call_argument_count(&self, position: Point) -> Option<usize> {
+        let mut cursor = self.doc.cursor()?;
+        if !(cursor.goto_leaf_node_at_or_after_point(position)
+            && cursor.goto_enclosing_method_call())
+        {
+            return None;
+        }
+
+        let node = cursor.node();
+        let value = matches!(node.kind(), "set_statement" | "web_set_statement")
+            .then(|| node.child_by_field_name("result"))
+            .flatten();
+        Some(
+            node.children_by_field_name("argument", &mut node.walk())
+                .chain(value)
+                .filter(|n| !n.is_missing())
+                .map(|n| {
+                    if n.prev_sibling().is_some_and(|n| {
+                        self.doc
+                            .line_map
+                            .text_for_node(&n)
+                            .eq_ignore_ascii_case("File_Field")
+                    }) {
+                        2
+                    } else {
+                        1
+                    }
+                })
+                .sum(),
+        )
+    }
+
     fn resolve_call_receiver(&self, position: Point) -> Option<QualifiedIndexSymbol<'_>> {
         let mut cursor = self.doc.cursor()?;
         cursor
@@ -248,34 +362,174 @@ impl<'a> ReferenceResolver<'a> {
             .unwrap_or(String::from("self"));
 
         if receiver.eq_ignore_ascii_case("self") {
-            cursor
-                .goto_enclosing_object_or_class()
-                .then(|| {
-                    if cursor.is_object_definition() {
-                        index::SymbolPath::try_from(cursor.clone())
-                            .ok()
-                            .map(|symbol_path| index::IndexSymbolRef {
-                                file_ref: index::IndexFileRef::from(&self.doc.file_path),
-                                symbol_path,
-                            })
-                            .and_then(|symbol_ref| self.index.resolve_symbol(&symbol_ref))
-                    } else {
-                        cursor
-                            .node()
-                            .child(0)
-                            .and_then(|n| n.child_by_field_name("name"))
-                            .and_then(|n| {
-                                self.index
-                                    .find_class(&self.doc.line_map.text_for_node(&n).into())
-                            })
-                            .and_then(|symbol_ref| self.index.resolve_symbol(symbol_ref))
-                    }
-                })
-                .flatten()
+            self.resolve_enclosing_object_or_class(cursor)
         } else {
-            // FIXME: Handle non-self receiver.
-            None
+            let receiver = SymbolName::from(receiver);
+            self.resolve_object_reference(&receiver)
+                .or_else(|| self.resolve_handle_reference(position, &receiver))
+        }
+    }
+
+    fn resolve_enclosing_object_or_class(
+        &self,
+        mut cursor: DataFlexTreeCursor,
+    ) -> Option<QualifiedIndexSymbol<'_>> {
+        cursor
+            .goto_enclosing_object_or_class()
+            .then(|| {
+                if cursor.is_object_definition() {
+                    index::SymbolPath::try_from(cursor.clone())
+                        .ok()
+                        .map(|symbol_path| index::IndexSymbolRef {
+                            file_ref: index::IndexFileRef::from(&self.doc.file_path),
+                            symbol_path,
+                        })
+                        .and_then(|symbol_ref| self.index.resolve_symbol(&symbol_ref))
+                } else {
+                    cursor
+                        .node()
+                        .child(0)
+                        .and_then(|n| n.child_by_field_name("name"))
+                        .and_then(|n| {
+                            self.index
+                                .find_class(&self.doc.line_map.text_for_node(&n).into())
+                        })
+                        .and_then(|symbol_ref| self.index.resolve_symbol(symbol_ref))
+                }
+            })
+            .flatten()
+    }
+
+    /// Resolves an identifier used in an expression to a property declared by the enclosing
+    /// object or class, or one of its superclasses.
+    pub fn resolve_property_reference(&self, position: Point) -> IndexSymbolIter<'_> {
+        let Some(name) = self.doc.symbol_at_position(position) else {
+            return IndexSymbolIter::empty();
+        };
+        let Some(mut cursor) = self.doc.cursor() else {
+            return IndexSymbolIter::empty();
+        };
+        if !cursor.goto_descendant_for_point(position) {
+            return IndexSymbolIter::empty();
+        }
+
+        let property = self
+            .resolve_enclosing_object_or_class(cursor)
+            .and_then(|class| {
+                let properties: Vec<&index::IndexSymbolRef> =
+                    self.index.find_properties(&name).collect();
+                self.index
+                    .class_hierarchy(class)
+                    .filter_map(|qualified_symbol| {
+                        ClassSymbol::from_index_symbol(qualified_symbol.symbol)
+                    })
+                    .find_map(|class| {
+                        properties.iter().find(|property| {
+                            property.symbol_path.parent_slice() == class.symbol_path.as_slice()
+                        })
+                    })
+                    .cloned()
+            });
+        IndexSymbolIter::new(
+            property
+                .into_iter()
+                .filter_map(|property_ref| self.index.resolve_symbol(property_ref)),
+        )
+    }
+
+    pub fn resolve_explicit_receiver_members(
+        &self,
+        position: Point,
+        kind: MethodKind,
+    ) -> Option<IndexSymbolIter<'_>> {
+        let receiver = self.resolve_explicit_call_receiver(position)?;
+        Some(self.index.inherited_class_members(receiver, kind))
+    }
+
+    fn resolve_explicit_call_receiver(&self, position: Point) -> Option<QualifiedIndexSymbol<'_>> {
+        let mut cursor = self.doc.cursor()?;
+        cursor
+            .goto_leaf_node_at_or_after_point(position)
+            .then(|| cursor.goto_enclosing_method_call());
+
+        if cursor.is_method_call_with_dynamic_receiver() {
+            return None;
+        }
+
+        let receiver = cursor
+            .node()
+            .child_by_field_name("receiver")
+            .filter(|n| n.kind() == "identifier")
+            .map(|n| self.doc.line_map.text_for_node(&n))?;
+        if receiver.eq_ignore_ascii_case("self") {
+            return None;
+        }
+        let receiver = SymbolName::from(receiver);
+        self.resolve_object_reference(&receiver)
+            .or_else(|| self.resolve_handle_reference(position, &receiver))
+    }
+
+    fn resolve_object_reference(&self, name: &SymbolName) -> Option<QualifiedIndexSymbol<'_>> {
+        // Prefer objects declared in the current file, then top level objects from other files.
+        // A name registered with `Register_Object` may also refer to a nested object elsewhere.
+        let file_ref = IndexFileRef::from(&self.doc.file_path);
+        let mut objects = self.index.find_objects(name);
+        objects
+            .clone()
+            .find(|s| s.file_ref == file_ref)
+            .or_else(|| objects.clone().find(|s| s.symbol_path.is_top_level()))
+            .or_else(|| {
+                self.index
+                    .is_registered_object(&file_ref, name)
+                    .then(|| objects.next())
+                    .flatten()
+            })
+            .and_then(|s| self.index.resolve_symbol(s))
+    }
+
+    /// Best effort resolution of a handle variable bound by `Get ... to hHandle` earlier in the
+    /// same method, where the `Get` function returns a known class.
+    fn resolve_handle_reference(
+        &self,
+        position: Point,
+        name: &SymbolName,
+    ) -> Option<QualifiedIndexSymbol<'_>> {
+        let mut cursor = self.doc.cursor()?;
+        if !(cursor.goto_descendant_for_point(position)
+            && cursor.goto_enclosing_method_definition())
+        {
+            return None;
+        }
+
+        let mut binding: Option<tree_sitter::Node> = None;
+        let mut nodes = vec![cursor.node()];
+        while let Some(node) = nodes.pop() {
+            if node.start_position() >= position {
+                continue;
+            }
+            if node.kind() == "get_statement"
+                && node.end_position() <= position
+                && node
+                    .child_by_field_name("result")
+                    .is_some_and(|n| SymbolName::from(self.doc.line_map.text_for_node(&n)) == *name)
+                && binding.is_none_or(|b| b.start_position() < node.start_position())
+            {
+                binding = Some(node);
+            }
+            nodes.extend(node.children(&mut node.walk()));
         }
+
+        let get_name_position = binding?.child_by_field_name("name")?.start_position();
+        let return_type = self
+            .resolve_method_reference(get_name_position, MethodKind::Get)
+            .find_map(|s| {
+                MethodSymbol::from_index_symbol(s.symbol)?
+                    .return_type
+                    .as_ref()
+            })?;
+        self.index
+            .find_class(return_type.name())
+            .and_then(|s| self.index.resolve_symbol(s))
     }
 
     fn resolve_expr_reference(&self, position: Point) -> IndexSymbolIter<'_> {
@@ -416,6 +670,18 @@ impl<'a> ReferenceResolver<'a> {
     }
 }
 
+impl From<&QualifiedIndexSymbol<'_>> for ResolvedSymbol {
+    fn from(qualified_symbol: &QualifiedIndexSymbol) -> Self {
+        let location = qualified_symbol.symbol.location();
+        Self {
+            path: qualified_symbol.file.path.clone(),
+            location: Point::new(location.line, location.column),
+            name: qualified_symbol.symbol.name().clone(),
+            kind: lsp_types::SymbolKind::from(qualified_symbol.symbol),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -545,11 +811,125 @@ End_Object
         let mut symbol = reference_resolver.resolve_class_reference(Point::new(2, 25));
         assert_eq!(
             format!("{:?}", symbol.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 2, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 2, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: true, members: [], metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", symbol.next()), "None");
     }
 
+    #[test]
+    fn test_resolve_forward_declared_class_reference() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            r#"
+Class cMyClass is a cBaseClass
+End_Class
+            "#,
+            "declaration.pkg".into(),
+            &index,
+        );
+        index::Indexer::index_test_content(
+            r#"
+Class cMyClass is a cBaseClass
+    Procedure foo
+    End_Procedure
+End_Class
+            "#,
+            "test.pkg".into(),
+            &index,
+        );
+        let doc = DataFlexDocument::new(
+            "other.pkg".into(),
+            r#"
+Use declaration.pkg
+Object oMyObject is a cMyClass
+End_Object
+            "#,
+            index.clone(),
+        );
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol = reference_resolver.resolve_class_reference(Point::new(2, 25));
+        assert_eq!(
+            format!("{:?}", symbol.next()),
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 4, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.foo\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })], metadata: [] }) })"
+        );
+        assert_eq!(format!("{:?}", symbol.next()), "None");
+    }
+
+    #[test]
+    fn test_resolve_class_declaration() {
+        let test_content = r#"
+Class cBaseClass is a cObject
+End_Class
+
+Class cMyClass is a cBaseClass
+End_Class
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let resolved_symbols = reference_resolver.resolve_class_declaration(Point::new(4, 9));
+        assert_eq!(
+            resolved_symbols
+                .iter()
+                .map(|s| (s.name.to_string(), s.location))
+                .collect::<Vec<_>>(),
+            [(String::from("cMyClass"), Point::new(4, 6))]
+        );
+        assert!(
+            reference_resolver
+                .resolve_class_declaration(Point::new(4, 24))
+                .is_empty()
+        );
+        drop(reference_resolver);
+
+        // The superclass is a class reference, resolving to the base class.
+        assert_eq!(
+            DocumentContext::context(&doc, Point::new(4, 24)),
+            Some(DocumentContext::ClassReference)
+        );
+        let resolved_symbols = ReferenceResolver::new(&doc)
+            .resolve_symbols(DocumentContext::ClassReference, Point::new(4, 24));
+        assert_eq!(
+            resolved_symbols
+                .iter()
+                .map(|s| (s.name.to_string(), s.location))
+                .collect::<Vec<_>>(),
+            [(String::from("cBaseClass"), Point::new(1, 6))]
+        );
+    }
+
+    #[test]
+    fn test_resolved_symbol() {
+        let test_content = r#"
+Class cMyClass is a cBaseClass
+End_Class
+
+Object oMyObject is a cMyClass
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let qualified_symbol = reference_resolver
+            .resolve_class_reference(Point::new(4, 25))
+            .next()
+            .unwrap();
+        let resolved_symbols =
+            reference_resolver.resolve_symbols(DocumentContext::ClassReference, Point::new(4, 25));
+        assert_eq!(resolved_symbols, [ResolvedSymbol::from(&qualified_symbol)]);
+
+        let resolved_symbol = &resolved_symbols[0];
+        assert_eq!(resolved_symbol.path, qualified_symbol.file.path);
+        assert_eq!(resolved_symbol.location, Point::new(1, 6));
+        assert_eq!(&resolved_symbol.name, qualified_symbol.symbol.name());
+        assert_eq!(resolved_symbol.kind, lsp_types::SymbolKind::CLASS);
+    }
+
     #[test]
     fn test_resolve_method_reference() {
         let index = index::IndexRef::make_test_index_ref();
@@ -584,11 +964,51 @@ End_Object
             reference_resolver.resolve_method_reference(Point::new(4, 16), MethodKind::Msg);
         assert_eq!(
             format!("{:?}", symbol.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.testIt\"), kind: Msg, parameters: [], return_type: None, metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.testIt\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", symbol.next()), "None");
     }
 
+    #[test]
+    fn test_resolve_method_reference_from_used_mixin_package() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            "Class cGreeterMixin is a Mixin\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "greeter_mixin.pkg".into(),
+            &index,
+        );
+        index::Indexer::index_test_content(
+            "Class cOtherGreeter is a cObject\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "other_greeter.pkg".into(),
+            &index,
+        );
+        let doc_content = r#"
+Class cMyClass is a cObject
+    Use greeter_mixin.pkg
+
+    Procedure Test
+        Send SayHello
+    End_Procedure
+End_Class
+            "#;
+        index::Indexer::index_test_content(doc_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), doc_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol =
+            reference_resolver.resolve_method_reference(Point::new(5, 16), MethodKind::Msg);
+        assert_eq!(
+            symbol
+                .next()
+                .map(|s| (s.file.path.clone(), format!("{:?}", s.symbol.symbol_path()))),
+            Some((
+                PathBuf::from("greeter_mixin.pkg"),
+                String::from("SymbolPath(\"cGreeterMixin.SayHello\")")
+            ))
+        );
+        assert!(symbol.next().is_none());
+    }
+
     #[test]
     fn test_resolve_method_reference_with_self() {
         let test_content = r#"
@@ -613,11 +1033,309 @@ End_Object
             reference_resolver.resolve_method_reference(Point::new(9, 15), MethodKind::Msg);
         assert_eq!(
             format!("{:?}", symbol.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 5, column: 14 }, range: SourceRange { start: SourceLocation { line: 5, column: 4 }, end: SourceLocation { line: 6, column: 17 } }, symbol_path: SymbolPath(\"oMyObject.foo\"), kind: Msg, parameters: [], return_type: None, metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 5, column: 14 }, range: SourceRange { start: SourceLocation { line: 5, column: 4 }, end: SourceLocation { line: 6, column: 17 } }, symbol_path: SymbolPath(\"oMyObject.foo\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", symbol.next()), "None");
     }
 
+    #[test]
+    fn test_resolve_set_method_reference() {
+        let test_content = r#"
+Class cMyClass is a cBaseClass
+    Procedure Set pValue Integer iValue
+    End_Procedure
+End_Class
+
+Object oMyObject is a cMyClass
+    Procedure foo
+        Set pValue to 1
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let context = DocumentContext::context(&doc, Point::new(8, 14));
+        assert_eq!(
+            context,
+            Some(DocumentContext::MethodReference(MethodKind::Set))
+        );
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol = reference_resolver.resolve_reference(context.unwrap(), Point::new(8, 14));
+        assert_eq!(
+            format!("{:?}", symbol.next()),
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 2, column: 18 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.pValue\"), kind: Set, parameters: [(SymbolName(\"iValue\"), DataFlexDataType(\"Integer\"))], return_type: None, external: None, metadata: [] }) })"
+        );
+        assert_eq!(format!("{:?}", symbol.next()), "None");
+    }
+
+    #[test]
+    fn test_resolve_set_method_reference_with_receiver() {
+        let test_content = r#"
+Class cChild is a cBaseClass
+    Procedure Construct_Object
+        Property Integer pValue 0
+    End_Procedure
+End_Class
+
+Class cOther is a cBaseClass
+    Procedure Construct_Object
+        Property Integer pValue 0
+        Property Integer pOther 0
+    End_Procedure
+End_Class
+
+Object oMain is a cOther
+    Object oChild is a cChild
+    End_Object
+
+    Procedure Test
+        Set pValue of oChild to 1
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol =
+            reference_resolver.resolve_method_reference(Point::new(18, 14), MethodKind::Set);
+        assert_eq!(
+            format!("{:?}", symbol.next()),
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Property(VariableSymbol { location: SourceLocation { line: 3, column: 25 }, range: SourceRange { start: SourceLocation { line: 3, column: 8 }, end: SourceLocation { line: 4, column: 0 } }, symbol_path: SymbolPath(\"cChild.pValue\"), data_type: DataFlexDataType(\"Integer\"), metadata: [] }) })"
+        );
+        assert_eq!(format!("{:?}", symbol.next()), "None");
+    }
+
+    #[test]
+    fn test_resolve_method_reference_with_object_receiver_in_other_file() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            r#"
+Class cGreeter is a cObject
+    Procedure SayHello
+    End_Procedure
+End_Class
+
+Class cOtherGreeter is a cObject
+    Procedure SayHello
+    End_Procedure
+End_Class
+
+Object oGreeter is a cGreeter
+End_Object
+            "#,
+            "greeter.pkg".into(),
+            &index,
+        );
+        let doc_content = r#"
+Use greeter.pkg
+Object oMain is a cOtherGreeter
+    Procedure Test
+        Send SayHello to oGreeter
+    End_Procedure
+End_Object
+            "#;
+        index::Indexer::index_test_content(doc_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), doc_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol =
+            reference_resolver.resolve_method_reference(Point::new(4, 16), MethodKind::Msg);
+        assert_eq!(
+            symbol
+                .next()
+                .map(|s| (s.file.path.clone(), format!("{:?}", s.symbol.symbol_path()))),
+            Some((
+                PathBuf::from("greeter.pkg"),
+                String::from("SymbolPath(\"cGreeter.SayHello\")")
+            ))
+        );
+        assert!(symbol.next().is_none());
+    }
+
+    #[test]
+    fn test_resolve_method_reference_with_registered_object_receiver() {
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(
+            r#"
+Class cGreeter is a cObject
+    Procedure SayHello
+    End_Procedure
+End_Class
+
+Class cOtherGreeter is a cObject
+    Procedure SayHello
+    End_Procedure
+End_Class
+
+Object oPanel is a cObject
+    Object oGreeter is a cGreeter
+    End_Object
+End_Object
+            "#,
+            "panel.pkg".into(),
+            &index,
+        );
+        let doc_content = r#"
+Register_Object oGreeter
+Object oMain is a cOtherGreeter
+    Procedure Test
+        Send SayHello to oGreeter
+    End_Procedure
+End_Object
+            "#;
+        index::Indexer::index_test_content(doc_content, "test.pkg".into(), &index);
+        assert!(
+            index
+                .get()
+                .is_registered_object(&"test.pkg".into(), &"ogreeter".into())
+        );
+        let doc = DataFlexDocument::new("test.pkg".into(), doc_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let mut symbol =
+            reference_resolver.resolve_method_reference(Point::new(4, 16), MethodKind::Msg);
+        assert_eq!(
+            symbol
+                .next()
+                .map(|s| (s.file.path.clone(), format!("{:?}", s.symbol.symbol_path()))),
+            Some((
+                PathBuf::from("panel.pkg"),
+                String::from("SymbolPath(\"cGreeter.SayHello\")")
+            ))
+        );
+        assert!(symbol.next().is_none());
+    }
+
+    #[test]
+    fn test_resolve_method_reference_with_handle_receiver() {
+        let test_content = r#"
+Class cServer is a cObject
+    Procedure Refresh
+    End_Procedure
+End_Class
+
+Class cClient is a cObject
+    Procedure Refresh
+    End_Procedure
+End_Class
+
+Class cDataDictionary is a cObject
+    Function Server Returns cServer
+    End_Function
+End_Class
+
+Object oDD is a cDataDictionary
+End_Object
+
+Object oView is a cObject
+    Procedure Test
+        Handle hServer
+        Get Server of oDD to hServer
+        Send Refresh of hServer
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let symbols: Vec<String> = reference_resolver
+            .resolve_method_reference(Point::new(23, 14), MethodKind::Msg)
+            .map(|s| format!("{:?}", s.symbol.symbol_path()))
+            .collect();
+        assert_eq!(symbols, ["SymbolPath(\"cServer.Refresh\")"]);
+    }
+
+    #[test]
+    fn test_resolve_method_reference_by_argument_count() {
+        let test_content = r#"
+Class cServer is a cObject
+    Procedure Refresh
+    End_Procedure
+End_Class
+
+Class cClient is a cObject
+    Procedure Refresh Integer iMode
+    End_Procedure
+End_Class
+
+Object oView is a cObject
+    Procedure Test
+        Handle hObj
+        Send Refresh of hObj 1
+        Send Refresh of hObj
+        Send Refresh of hObj 1 2
+    End_Procedure
+End_Object
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let resolve = |line| {
+            let mut symbols: Vec<String> = reference_resolver
+                .resolve_method_reference(Point::new(line, 14), MethodKind::Msg)
+                .map(|s| format!("{:?}", s.symbol.symbol_path()))
+                .collect();
+            symbols.sort();
+            symbols
+        };
+        assert_eq!(resolve(14), ["SymbolPath(\"cClient.Refresh\")"]);
+        assert_eq!(resolve(15), ["SymbolPath(\"cServer.Refresh\")"]);
+        assert_eq!(
+            resolve(16),
+            [
+                "SymbolPath(\"cClient.Refresh\")",
+                "SymbolPath(\"cServer.Refresh\")"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_property_reference() {
+        let test_content = r#"
+Class cMyClass is a cObject
+    Procedure Construct_Object
+        Forward Send Construct_Object
+        Property Integer piValue 0
+    End_Procedure
+
+    Procedure Test
+        Integer iValue
+        Move piValue to iValue
+    End_Procedure
+
+    Procedure Shadowed
+        Integer piValue iValue
+        Move piValue to iValue
+    End_Procedure
+End_Class
+            "#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let context = DocumentContext::context(&doc, Point::new(9, 14));
+        assert_eq!(context, Some(DocumentContext::PropertyReference));
+
+        let reference_resolver = ReferenceResolver::new(&doc);
+        let symbols: Vec<String> = reference_resolver
+            .resolve_reference(context.unwrap(), Point::new(9, 14))
+            .map(|s| format!("{:?}", s.symbol.symbol_path()))
+            .collect();
+        assert_eq!(symbols, ["SymbolPath(\"cMyClass.piValue\")"]);
+
+        let context = DocumentContext::context(&doc, Point::new(14, 14));
+        assert_eq!(context, Some(DocumentContext::Expression));
+    }
+
     #[test]
     fn test_resolve_expr_reference_at_call_receiver() {
         let test_content = r#"
@@ -636,7 +1354,7 @@ Send foo of oMyObject
         let mut symbol = reference_resolver.resolve_expr_reference(Point::new(6, 16));
         assert_eq!(
             format!("{:?}", symbol.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Object(ClassSymbol { location: SourceLocation { line: 1, column: 7 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 4, column: 10 } }, symbol_path: SymbolPath(\"oMyObject\"), superclass: SymbolName(\"cObject\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"oMyObject.foo\"), kind: Msg, parameters: [], return_type: None, metadata: [] })], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Object(ClassSymbol { location: SourceLocation { line: 1, column: 7 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 4, column: 10 } }, symbol_path: SymbolPath(\"oMyObject\"), superclass: SymbolName(\"cObject\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"oMyObject.foo\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })], metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", symbol.next()), "None");
     }
@@ -813,7 +1531,7 @@ Move (MyMethod(oTest, "test", 1234)) to iTest
         let mut symbol = reference_resolver.resolve_paren_expr_reference(Point::new(7, 10));
         assert_eq!(
             format!("{:?}", symbol.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 2, column: 13 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 16 } }, symbol_path: SymbolPath(\"oTest.MyMethod\"), kind: Get, parameters: [(SymbolName(\"sArg1\"), DataFlexDataType(\"String\")), (SymbolName(\"iArg2\"), DataFlexDataType(\"Integer\"))], return_type: Some(DataFlexDataType(\"Integer\")), metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Method(MethodSymbol { location: SourceLocation { line: 2, column: 13 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 3, column: 16 } }, symbol_path: SymbolPath(\"oTest.MyMethod\"), kind: Get, parameters: [(SymbolName(\"sArg1\"), DataFlexDataType(\"String\")), (SymbolName(\"iArg2\"), DataFlexDataType(\"Integer\"))], return_type: Some(DataFlexDataType(\"Integer\")), external: None, metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", symbol.next()), "None");
     }
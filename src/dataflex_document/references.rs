@@ -0,0 +1,408 @@
+use super::*;
+use index::{ClassSymbol, IndexSymbolType, MethodKind, MethodSymbol, SymbolName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Class,
+    Method(MethodKind),
+}
+
+/// A class or method to find references to, identified by the locations of the definitions the
+/// reference resolves to, so that same-named symbols of unrelated classes aren't matched.
+#[derive(Debug, Clone)]
+pub struct ReferenceTarget {
+    pub name: SymbolName,
+    pub kind: ReferenceKind,
+    definitions: Vec<(PathBuf, index::SourceLocation)>,
+}
+
+impl ReferenceTarget {
+    /// The files declaring the symbols the target resolves to.
+    pub fn definition_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.definitions.iter().map(|(path, _)| path)
+    }
+}
+
+pub struct References;
+
+impl References {
+    /// The class or method named at the position, either by a reference or its declaration.
+    pub fn reference_target(doc: &DataFlexDocument, position: Point) -> Option<ReferenceTarget> {
+        let name = doc.symbol_at_position(position)?;
+        let kind = match DocumentContext::context(doc, position) {
+            Some(DocumentContext::ClassReference) => ReferenceKind::Class,
+            Some(
+                DocumentContext::MethodReference(kind) | DocumentContext::MethodDeclaration(kind),
+            ) => ReferenceKind::Method(kind),
+            // The name in a class header has no context.
+            None => ReferenceKind::Class,
+            _ => return None,
+        };
+        let node = Self::reference_nodes(doc, &name, kind)
+            .into_iter()
+            .find(|node| node.start_position() <= position && position <= node.end_position())?;
+        let definitions = Self::definitions(doc, node, kind);
+        (!definitions.is_empty()).then_some(ReferenceTarget {
+            name,
+            kind,
+            definitions,
+        })
+    }
+
+    /// The references to the target in the document, optionally including its declaration.
+    pub fn find_references(
+        doc: &DataFlexDocument,
+        target: &ReferenceTarget,
+        include_declaration: bool,
+    ) -> Vec<std::ops::Range<Point>> {
+        Self::reference_nodes(doc, &target.name, target.kind)
+            .into_iter()
+            .filter(|node| {
+                (include_declaration || !Self::is_declaration(*node))
+                    && Self::definitions(doc, *node, target.kind)
+                        .iter()
+                        .any(|definition| target.definitions.contains(definition))
+            })
+            .map(|node| node.start_position()..node.end_position())
+            .collect()
+    }
+
+    /// The range of the reference to the target at the position, which is replaced by a rename.
+    pub fn rename_range(
+        doc: &DataFlexDocument,
+        target: &ReferenceTarget,
+        position: Point,
+    ) -> Option<std::ops::Range<Point>> {
+        Self::find_references(doc, target, true)
+            .into_iter()
+            .find(|range| range.start <= position && position <= range.end)
+    }
+
+    /// Why the target can't be renamed to the name, if the name isn't an identifier, or is already
+    /// the name of another class, or of another method of the class declaring the method.
+    pub fn rename_error(
+        doc: &DataFlexDocument,
+        target: &ReferenceTarget,
+        new_name: &str,
+    ) -> Option<String> {
+        let mut chars = new_name.chars();
+        if !chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            || !chars.all(|c| c.is_alphanumeric() || c == '_')
+        {
+            return Some(format!("{new_name:?} isn't a valid identifier"));
+        }
+        let name = SymbolName::from(new_name);
+        if name == target.name {
+            return None;
+        }
+        let local_index_file = doc.local_index_file();
+        let index = doc.index.get();
+        match target.kind {
+            ReferenceKind::Class => index
+                .is_known_class(&name)
+                .then(|| format!("A class named {new_name} already exists")),
+            ReferenceKind::Method(kind) => {
+                target.definitions.iter().find_map(|(path, location)| {
+                    // The open document may have edits that aren't indexed yet.
+                    let container = if *path == doc.file_path {
+                        local_index_file
+                            .as_ref()?
+                            .find_member_container(*location)?
+                    } else {
+                        index
+                            .find_member_container(&index::IndexFileRef::from(path), *location)?
+                            .symbol
+                    };
+                    let class = ClassSymbol::from_index_symbol(container)?;
+                    class
+                        .members
+                        .iter()
+                        .filter_map(MethodSymbol::from_index_symbol)
+                        .any(|method| method.kind == kind && method.symbol_path.name() == &name)
+                        .then(|| {
+                            format!(
+                                "{} already has a method named {new_name}",
+                                class.symbol_path.name()
+                            )
+                        })
+                })
+            }
+        }
+    }
+
+    /// The definitions the name node resolves to, or the symbol itself for a declaration.
+    fn definitions(
+        doc: &DataFlexDocument,
+        node: tree_sitter::Node,
+        kind: ReferenceKind,
+    ) -> Vec<(PathBuf, index::SourceLocation)> {
+        if Self::is_declaration(node) {
+            return vec![(doc.file_path.clone(), node.start_position().into())];
+        }
+        let context = match kind {
+            ReferenceKind::Class => DocumentContext::ClassReference,
+            ReferenceKind::Method(kind) => DocumentContext::MethodReference(kind),
+        };
+        ReferenceResolver::new(doc)
+            .resolve_reference(context, node.start_position())
+            .map(|symbol| (symbol.file.path.clone(), symbol.symbol.location()))
+            .collect()
+    }
+
+    /// Whether the node is the name in a class or method header.
+    fn is_declaration(node: tree_sitter::Node) -> bool {
+        node.parent().is_some_and(|header| {
+            matches!(
+                header.kind(),
+                "class_header" | "composite_header" | "procedure_header" | "function_header"
+            ) && header.child_by_field_name("name") == Some(node)
+        })
+    }
+
+    /// All identifiers naming the symbol in the document, including its declaration if the
+    /// symbol is declared in the document.
+    pub fn references_in_file(
+        doc: &DataFlexDocument,
+        name: &SymbolName,
+        kind: ReferenceKind,
+    ) -> Vec<std::ops::Range<Point>> {
+        Self::reference_nodes(doc, name, kind)
+            .iter()
+            .map(|node| node.start_position()..node.end_position())
+            .collect()
+    }
+
+    fn reference_nodes<'a>(
+        doc: &'a DataFlexDocument,
+        name: &SymbolName,
+        kind: ReferenceKind,
+    ) -> Vec<tree_sitter::Node<'a>> {
+        let Some(mut cursor) = doc.tree().map(|tree| tree.walk()) else {
+            return Vec::new();
+        };
+
+        let mut references = Vec::new();
+        loop {
+            for name_node in Self::name_nodes(doc, cursor.node(), kind) {
+                if SymbolName::from(doc.line_map.text_for_node(&name_node)) == *name {
+                    references.push(name_node);
+                }
+            }
+
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return references;
+                }
+            }
+        }
+    }
+
+    fn name_nodes<'a>(
+        doc: &DataFlexDocument,
+        node: tree_sitter::Node<'a>,
+        kind: ReferenceKind,
+    ) -> Vec<tree_sitter::Node<'a>> {
+        match kind {
+            ReferenceKind::Class => match node.kind() {
+                "class_header" | "composite_header" => [
+                    node.child_by_field_name("name"),
+                    node.child_by_field_name("superclass"),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+                "object_header" => node.child_by_field_name("superclass").into_iter().collect(),
+                _ => Vec::new(),
+            },
+            ReferenceKind::Method(kind) => {
+                let node_kind = match node.kind() {
+                    "send_statement" => Some(MethodKind::Msg),
+                    "get_statement" | "web_get_statement" | "call_expression" => {
+                        Some(MethodKind::Get)
+                    }
+                    "set_statement" | "web_set_statement" => Some(MethodKind::Set),
+                    "function_header" => Some(MethodKind::Get),
+                    "procedure_header" if Self::is_set_procedure(doc, node) => {
+                        Some(MethodKind::Set)
+                    }
+                    "procedure_header" => Some(MethodKind::Msg),
+                    _ => None,
+                };
+                node_kind
+                    .filter(|node_kind| *node_kind == kind)
+                    .and_then(|_| node.child_by_field_name("name"))
+                    .into_iter()
+                    .collect()
+            }
+        }
+    }
+
+    fn is_set_procedure(doc: &DataFlexDocument, header: tree_sitter::Node) -> bool {
+        let name = header.child_by_field_name("name");
+        header
+            .children(&mut header.walk())
+            .take_while(|n| Some(*n) != name)
+            .any(|n| {
+                n.kind() == "keyword" && doc.line_map.text_for_node(&n).eq_ignore_ascii_case("set")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_references_in_file() {
+        let test_content = r#"
+Object oTest is a cObject
+    Procedure Refresh Integer iMode
+    End_Procedure
+
+    Procedure Test
+        Send Refresh 1
+        Send refresh 2
+        Get Refresh to i
+    End_Procedure
+End_Object
+"#;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+        let range = |line, start, end| {
+            lsp_types::Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, end),
+            )
+        };
+
+        assert_eq!(
+            doc.references_in_file(&"Refresh".into(), ReferenceKind::Method(MethodKind::Msg)),
+            [range(2, 14, 21), range(6, 13, 20), range(7, 13, 20)]
+        );
+        assert_eq!(
+            doc.references_in_file(&"Refresh".into(), ReferenceKind::Method(MethodKind::Get)),
+            [range(8, 12, 19)]
+        );
+        assert_eq!(
+            doc.references_in_file(&"cObject".into(), ReferenceKind::Class),
+            [range(1, 18, 25)]
+        );
+    }
+
+    #[test]
+    fn test_find_references_to_resolved_symbol() {
+        let test_content = r#"Class cA is a cObject
+    Procedure SayHello
+    End_Procedure
+
+    Procedure Test
+        Send SayHello
+    End_Procedure
+End_Class
+
+Class cB is a cObject
+    Procedure SayHello
+    End_Procedure
+
+    Procedure Test
+        Send SayHello
+    End_Procedure
+End_Class
+
+Object oA is a cA
+End_Object
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        let range = |line, start, end| Point::new(line, start)..Point::new(line, end);
+
+        // Only the method of cA is matched, not the method with the same name in cB.
+        let target = References::reference_target(&doc, Point::new(5, 15)).unwrap();
+        assert_eq!(
+            References::find_references(&doc, &target, true),
+            [range(1, 14, 22), range(5, 13, 21)]
+        );
+        assert_eq!(
+            References::find_references(&doc, &target, false),
+            [range(5, 13, 21)]
+        );
+
+        // From the declaration.
+        let target = References::reference_target(&doc, Point::new(10, 16)).unwrap();
+        assert_eq!(
+            References::find_references(&doc, &target, true),
+            [range(10, 14, 22), range(14, 13, 21)]
+        );
+
+        // A class from its header and from a superclass reference.
+        let target = References::reference_target(&doc, Point::new(0, 7)).unwrap();
+        assert_eq!(
+            References::find_references(&doc, &target, true),
+            [range(0, 6, 8), range(18, 15, 17)]
+        );
+        let target = References::reference_target(&doc, Point::new(18, 16)).unwrap();
+        assert_eq!(
+            References::find_references(&doc, &target, false),
+            [range(18, 15, 17)]
+        );
+    }
+
+    #[test]
+    fn test_rename_error() {
+        let test_content = r#"Class cA is a cObject
+    Procedure SayHello
+    End_Procedure
+
+    Procedure SayGoodbye
+    End_Procedure
+
+    Function SayHi Returns String
+    End_Function
+End_Class
+
+Class cB is a cObject
+    Procedure SayHi
+    End_Procedure
+End_Class
+"#;
+        let index = index::IndexRef::make_test_index_ref();
+        index::Indexer::index_test_content(test_content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+
+        let target = References::reference_target(&doc, Point::new(1, 16)).unwrap();
+        assert_eq!(
+            References::rename_range(&doc, &target, Point::new(1, 16)),
+            Some(Point::new(1, 14)..Point::new(1, 22))
+        );
+        assert_eq!(References::rename_error(&doc, &target, "SayHowdy"), None);
+        assert_eq!(References::rename_error(&doc, &target, "sayhello"), None);
+        assert_eq!(
+            References::rename_error(&doc, &target, "SayGoodbye"),
+            Some(String::from("cA already has a method named SayGoodbye"))
+        );
+        // Methods of other kinds and of other classes don't collide.
+        assert_eq!(References::rename_error(&doc, &target, "SayHi"), None);
+        assert_eq!(
+            References::rename_error(&doc, &target, "Say Hello"),
+            Some(String::from("\"Say Hello\" isn't a valid identifier"))
+        );
+        assert_eq!(
+            References::rename_error(&doc, &target, "1SayHello"),
+            Some(String::from("\"1SayHello\" isn't a valid identifier"))
+        );
+
+        let target = References::reference_target(&doc, Point::new(0, 7)).unwrap();
+        assert_eq!(References::rename_error(&doc, &target, "cC"), None);
+        assert_eq!(
+            References::rename_error(&doc, &target, "cB"),
+            Some(String::from("A class named cB already exists"))
+        );
+    }
+}
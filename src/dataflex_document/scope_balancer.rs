@@ -1,3 +1,5 @@
+use std::sync::OnceLock;
+
 use super::*;
 
 pub struct ScopeBalancer {}
@@ -8,6 +10,13 @@ pub struct TextEdit {
     pub text: String,
 }
 
+#[derive(Debug)]
+pub struct MismatchedCloseScope {
+    pub range: std::ops::Range<tree_sitter::Point>,
+    pub expected: &'static str,
+    pub found: String,
+}
+
 impl ScopeBalancer {
     const fn auto_close_scope_pairs() -> &'static [(&'static str, &'static str)] {
         &[
@@ -143,6 +152,114 @@ impl ScopeBalancer {
         }
     }
 
+    pub fn mismatched_close_scopes(doc: &DataFlexDocument) -> Vec<MismatchedCloseScope> {
+        let Some(node) = doc.root_node() else {
+            return Vec::new();
+        };
+        let Some(query) = Self::close_scopes_query() else {
+            return Vec::new();
+        };
+        let Some(cmd_capture_index) = query.capture_index_for_name("cmd") else {
+            return Vec::new();
+        };
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        let mut matches = query_cursor.matches(query, node, doc.line_map.text_provider());
+
+        let mut mismatched_close_scopes = Vec::new();
+        while let Some(query_match) = matches.next() {
+            for cmd_node in query_match.nodes_for_capture_index(cmd_capture_index) {
+                let found = doc.line_map.text_for_node(&cmd_node);
+                if let Some(expected) = Self::enclosing_unclosed_scope(&cmd_node)
+                    && !expected.eq_ignore_ascii_case(&found)
+                {
+                    mismatched_close_scopes.push(MismatchedCloseScope {
+                        range: cmd_node.start_position()..cmd_node.end_position(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+        }
+        mismatched_close_scopes
+    }
+
+    fn close_scopes_query() -> Option<&'static tree_sitter::Query> {
+        static CLOSE_SCOPES_QUERY: OnceLock<Option<tree_sitter::Query>> = OnceLock::new();
+        CLOSE_SCOPES_QUERY
+            .get_or_init(|| {
+                let close_scopes: Vec<&str> = Self::auto_close_scope_pairs()
+                    .iter()
+                    .map(|scope_pair| scope_pair.1)
+                    .filter(|close_scope| !close_scope.contains(char::is_whitespace))
+                    .collect();
+                tree_sitter::Query::new(
+                    &tree_sitter_dataflex::LANGUAGE.into(),
+                    &format!(
+                        "(other_command_statement (simple_identifier) @cmd (#match? @cmd \"(?i)^({})$\"))",
+                        close_scopes.join("|")
+                    ),
+                )
+                .inspect_err(|e| log::error!("Error loading close scopes query: {e}"))
+                .ok()
+            })
+            .as_ref()
+    }
+
+    /// Returns the close scope and the header node kind of its scope, if `node` is a close scope
+    /// command like `End_Object` that the parser didn't match with a header.
+    pub fn unmatched_close_scope(
+        doc: &DataFlexDocument,
+        node: &tree_sitter::Node,
+    ) -> Option<(&'static str, &'static str)> {
+        if node.kind() != "other_command_statement" {
+            return None;
+        }
+        let cmd_node = node
+            .children(&mut node.walk())
+            .find(|n| n.kind() == "simple_identifier")?;
+        let cmd = doc.line_map.text_for_node(&cmd_node);
+        Self::auto_close_scope_pairs()
+            .iter()
+            .position(|scope_pair| {
+                !scope_pair.1.contains(char::is_whitespace)
+                    && scope_pair.1.eq_ignore_ascii_case(&cmd)
+            })
+            .map(|index| {
+                (
+                    Self::auto_close_scope_pairs()[index].1,
+                    Self::scope_node_pairs()[index].0,
+                )
+            })
+    }
+
+    /// The close scope of the innermost block enclosing `position`, if that block is unclosed.
+    pub fn enclosing_close_scope(doc: &DataFlexDocument, position: Point) -> Option<&'static str> {
+        let mut cursor = doc.cursor()?;
+        if !cursor.goto_leaf_node_at_or_before_point(position) {
+            return None;
+        }
+        Self::enclosing_unclosed_scope(&cursor.node())
+    }
+
+    fn enclosing_unclosed_scope(node: &tree_sitter::Node) -> Option<&'static str> {
+        let mut current = node.parent();
+        while let Some(scope_node) = current {
+            if let Some(index) = scope_node.child(0).and_then(|header| {
+                Self::scope_node_pairs()
+                    .iter()
+                    .position(|node_pair| node_pair.0 == header.kind())
+            }) {
+                let footer_kind = Self::scope_node_pairs()[index].1;
+                let has_footer = scope_node
+                    .children(&mut scope_node.walk())
+                    .any(|n| n.kind() == footer_kind && !n.is_missing());
+                return (!has_footer).then_some(Self::auto_close_scope_pairs()[index].1);
+            }
+            current = scope_node.parent();
+        }
+        None
+    }
+
     fn find_balanced_corresponding_scope_node<'a>(
         scope_node: &tree_sitter::Node<'a>,
     ) -> Option<tree_sitter::Node<'a>> {
@@ -446,4 +563,22 @@ End_Object
             "Some((Point { row: 3, column: 15 }..Point { row: 3, column: 20 }, Point { row: 4, column: 8 }..Point { row: 4, column: 11 }))"
         );
     }
+
+    #[test]
+    fn test_mismatched_close_scopes() {
+        let test_content = "Object oTest is a cTest\nEnd_Class\n";
+        let index = index::IndexRef::make_test_index_ref();
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        assert_eq!(
+            format!("{:?}", ScopeBalancer::mismatched_close_scopes(&doc)),
+            "[MismatchedCloseScope { range: Point { row: 1, column: 0 }..Point { row: 1, column: 9 }, expected: \"End_Object\", found: \"End_Class\" }]"
+        );
+
+        let test_content = "Object oTest is a cTest\nEnd_Object\nEnd_Class\n";
+        let doc = DataFlexDocument::new("test.pkg".into(), test_content, index.clone());
+        assert_eq!(
+            format!("{:?}", ScopeBalancer::mismatched_close_scopes(&doc)),
+            "[]"
+        );
+    }
 }
@@ -0,0 +1,122 @@
+use super::*;
+
+pub struct SelectionRanges;
+
+impl SelectionRanges {
+    /// For each position, the ranges of the node at the position and its ancestors, e.g. an
+    /// identifier, its statement, the enclosing method and class, each wrapped by the next larger
+    /// one. Ancestors with the same range as their child are skipped.
+    pub fn selection_ranges(
+        doc: &DataFlexDocument,
+        positions: &[Point],
+    ) -> Vec<lsp_types::SelectionRange> {
+        positions
+            .iter()
+            .map(|position| Self::selection_range(doc, *position))
+            .collect()
+    }
+
+    fn selection_range(doc: &DataFlexDocument, position: Point) -> lsp_types::SelectionRange {
+        let mut ranges = Vec::new();
+        if let Some(mut cursor) = doc.cursor() {
+            cursor.goto_descendant_for_point(position);
+            loop {
+                let node = cursor.node();
+                let range = doc.lsp_range(&(node.start_position()..node.end_position()));
+                if ranges.last() != Some(&range) {
+                    ranges.push(range);
+                }
+                if !cursor.goto_parent() {
+                    break;
+                }
+            }
+        }
+
+        let mut ranges = ranges.into_iter().rev();
+        let outermost = ranges
+            .next()
+            .unwrap_or_else(|| doc.lsp_range(&(position..position)));
+        ranges.fold(
+            lsp_types::SelectionRange {
+                range: outermost,
+                parent: None,
+            },
+            |parent, range| lsp_types::SelectionRange {
+                range,
+                parent: Some(Box::new(parent)),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_ranges() {
+        let test_content = r#"Class cTest is a cObject
+    Procedure Test
+        Send Foo 1
+    End_Procedure
+End_Class
+"#;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+        let selection_ranges = SelectionRanges::selection_ranges(&doc, &[Point::new(2, 14)]);
+        assert_eq!(selection_ranges.len(), 1);
+
+        let mut ranges = Vec::new();
+        let mut selection_range = Some(&selection_ranges[0]);
+        while let Some(s) = selection_range {
+            ranges.push(s.range);
+            selection_range = s.parent.as_deref();
+        }
+        assert_eq!(
+            ranges[0],
+            lsp_types::Range::new(
+                lsp_types::Position::new(2, 13),
+                lsp_types::Position::new(2, 16),
+            )
+        );
+        assert!(
+            ranges
+                .windows(2)
+                .all(|w| w[0] != w[1] && w[1].start <= w[0].start && w[0].end <= w[1].end)
+        );
+        assert!(
+            ranges
+                .iter()
+                .any(|r| r.start == lsp_types::Position::new(1, 4))
+        );
+        assert_eq!(ranges.last().unwrap().start, lsp_types::Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_selection_ranges_after_non_ascii_characters() {
+        let test_content = r#"Class cTest is a cObject
+    Procedure Test
+        Send Privé 1
+    End_Procedure
+End_Class
+"#;
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            test_content,
+            index::IndexRef::make_test_index_ref(),
+        );
+
+        // The argument starts at byte column 20, but UTF-16 column 19.
+        let selection_ranges = SelectionRanges::selection_ranges(&doc, &[Point::new(2, 20)]);
+        assert_eq!(
+            selection_ranges[0].range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(2, 19),
+                lsp_types::Position::new(2, 20),
+            )
+        );
+    }
+}
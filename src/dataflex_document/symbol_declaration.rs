@@ -1,8 +1,10 @@
-use crate::index::{Index, QualifiedIndexSymbol};
+use crate::index::{Index, IndexSymbolType, MethodKind, MethodSymbol, QualifiedIndexSymbol};
 
 pub struct SymbolDeclaration {
     pub declaration: String,
+    pub returned_class: Option<String>,
     pub description: Option<String>,
+    pub file_name: Option<String>,
 }
 
 impl SymbolDeclaration {
@@ -14,13 +16,99 @@ impl SymbolDeclaration {
             .join("\n");
         Self {
             declaration: qualified_symbol.symbol.to_string(),
+            returned_class: Self::returned_class(qualified_symbol, index),
             description: if description.is_empty() {
                 None
             } else {
                 Some(description)
             },
+            file_name: qualified_symbol
+                .file
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
         }
     }
+
+    /// Brief documentation for the DataFlex keywords, shown on hover.
+    pub fn keyword_documentation(keyword: &str) -> Option<&'static str> {
+        const KEYWORD_DOCUMENTATION: &[(&str, &str)] = &[
+            (
+                "Object",
+                "Declares an object, an instance of the class after `is a`, ended by `End_Object`.",
+            ),
+            (
+                "Class",
+                "Declares a class that inherits from the class after `is a`, ended by `End_Class`.",
+            ),
+            (
+                "Is_a",
+                "Separates the name of a class or object from its superclass.",
+            ),
+            (
+                "Procedure",
+                "Declares a procedure, a method without a return value that is called with `Send`, ended by `End_Procedure`. `Procedure Set` declares a setter called with `Set`.",
+            ),
+            (
+                "Function",
+                "Declares a function, a method with a return value that is called with `Get`, ended by `End_Function`.",
+            ),
+            (
+                "Send",
+                "Calls a procedure, e.g. `Send DoSomething of oObject`. Without `of`, the message is sent to the current object.",
+            ),
+            (
+                "Get",
+                "Calls a function or reads a property and moves the result into a variable, e.g. `Get Value of oForm to sValue`.",
+            ),
+            (
+                "Set",
+                "Calls a `Procedure Set` or sets a property, e.g. `Set Value of oForm to sValue`.",
+            ),
+            (
+                "Property",
+                "Declares a property of a class, with its type, name and initial value.",
+            ),
+            (
+                "Forward",
+                "Sends the message to the superclass implementation, e.g. `Forward Send Construct_Object`.",
+            ),
+            (
+                "Delegate",
+                "Sends the message to the parent object, e.g. `Delegate Send DoSomething`.",
+            ),
+            ("Use", "Includes a package, e.g. `Use Windows.pkg`."),
+            (
+                "Move",
+                "Assigns a value to a variable, e.g. `Move 1 to iCount`.",
+            ),
+            ("Function_Return", "Returns a value from a function."),
+            ("Procedure_Return", "Returns from a procedure."),
+        ];
+        KEYWORD_DOCUMENTATION
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(keyword))
+            .map(|(_, documentation)| *documentation)
+    }
+
+    /// Describes the object returned by a `Get` function whose return type is a known class,
+    /// e.g. `Get Server : cConnection`.
+    fn returned_class(
+        qualified_symbol: &QualifiedIndexSymbol<'_>,
+        index: &Index,
+    ) -> Option<String> {
+        let method_symbol = MethodSymbol::from_index_symbol(qualified_symbol.symbol)?;
+        let return_type = method_symbol.return_type.as_ref()?;
+        (method_symbol.kind == MethodKind::Get && index.is_known_class(return_type.name())).then(
+            || {
+                format!(
+                    "Get {} : {}",
+                    method_symbol.symbol_path.name(),
+                    return_type.name()
+                )
+            },
+        )
+    }
 }
 
 impl std::fmt::Display for SymbolDeclaration {
@@ -28,6 +116,14 @@ impl std::fmt::Display for SymbolDeclaration {
         writeln!(f, "```dataflex")?;
         writeln!(f, "{}", self.declaration)?;
         writeln!(f, "```")?;
+        if let Some(returned_class) = &self.returned_class {
+            writeln!(f)?;
+            writeln!(f, "`{returned_class}`")?;
+        }
+        if let Some(file_name) = &self.file_name {
+            writeln!(f)?;
+            writeln!(f, "Defined in `{file_name}`")?;
+        }
         if let Some(description) = &self.description {
             writeln!(f)?;
             writeln!(f, "---")?;
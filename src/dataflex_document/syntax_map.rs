@@ -1,6 +1,7 @@
-use std::ops::{Bound, RangeBounds};
+use std::ops::{Bound, Range, RangeBounds};
+use std::sync::OnceLock;
 use streaming_iterator::StreamingIterator;
-use tower_lsp::lsp_types::SemanticToken;
+use tower_lsp::lsp_types::{SemanticToken, SemanticTokensEdit};
 use tree_sitter::{Point, Query, QueryCursor};
 
 use super::*;
@@ -8,6 +9,8 @@ use index::{MethodKind, SymbolName};
 
 pub struct SyntaxMap {
     lines: Vec<Line>,
+    /// Lines replaced by edits since the tokens were last generated, which need new tokens.
+    edited_rows: Option<Range<usize>>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -20,8 +23,13 @@ struct SyntaxToken {
     delta_start: u32,
     length: u32,
     kind: u32,
+    modifiers: u32,
 }
 
+/// Modifier bit for the names declared by class and object headers, matching the order of the
+/// token modifiers in the legend.
+const DECLARATION_MODIFIER: u32 = 1 << 0;
+
 impl SyntaxToken {
     fn new(start: Point, end: Point, kind: u32, prev: Point) -> Self {
         Self {
@@ -32,6 +40,29 @@ impl SyntaxToken {
             },
             length: (end.column - start.column) as u32,
             kind: kind,
+            modifiers: 0,
+        }
+    }
+
+    /// Marks the token as a declaration if the node is the name of a class or object header.
+    fn with_declaration_modifier(mut self, node: &tree_sitter::Node) -> Self {
+        if let Some(parent) = node.parent()
+            && matches!(parent.kind(), "class_header" | "object_header")
+            && parent.child_by_field_name("name") == Some(*node)
+        {
+            self.modifiers |= DECLARATION_MODIFIER;
+        }
+        self
+    }
+
+    /// Precedence when several captures produce a token for the same span, so that specific
+    /// symbol kinds (methods, properties, classes, ...) win over generic ones and keywords.
+    fn priority(&self) -> u8 {
+        match self.kind {
+            0 => 0,
+            4 | 7 | 8 => 1,
+            1 | 6 => 2,
+            _ => 3,
         }
     }
 }
@@ -40,35 +71,83 @@ impl SyntaxMap {
     pub fn new(doc: &DataFlexDocument) -> Self {
         let lines = Self::generate_lines(doc);
 
-        Self { lines }
+        Self {
+            lines,
+            edited_rows: None,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Replaces the tokens of the edited lines with empty lines for the rows the edit inserted,
+    /// shifting the lines below, so their tokens don't have to be generated again.
+    pub fn edit_lines(&mut self, old_rows: Range<usize>, new_row_count: usize) {
+        let old_rows = old_rows.start.min(self.lines.len())..old_rows.end.min(self.lines.len());
+        let new_rows = old_rows.start..old_rows.start + new_row_count;
+        self.lines.splice(
+            old_rows.clone(),
+            std::iter::repeat_with(|| Line { tokens: Vec::new() }).take(new_row_count),
+        );
+
+        let shift = |row: usize, inside: usize| {
+            if row <= old_rows.start {
+                row
+            } else if row >= old_rows.end {
+                row + new_rows.end - old_rows.end
+            } else {
+                inside
+            }
+        };
+        self.edited_rows = Some(match self.edited_rows.take() {
+            Some(edited_rows) => {
+                shift(edited_rows.start, new_rows.start).min(new_rows.start)
+                    ..shift(edited_rows.end, new_rows.end).max(new_rows.end)
+            }
+            None => new_rows,
+        });
+    }
+
+    /// Generates the tokens of the edited lines and the rows whose syntax changed, keeping the
+    /// tokens of all other lines.
+    pub fn update_lines(
+        &mut self,
+        doc: &DataFlexDocument,
+        changed_rows: impl IntoIterator<Item = Range<usize>>,
+    ) {
+        let query = Self::highlights_query();
+        for rows in self.edited_rows.take().into_iter().chain(changed_rows) {
+            let rows = rows.start.min(self.lines.len())..rows.end.min(self.lines.len());
+            let lines = Self::generate_lines_in_rows(doc, query, rows.clone());
+            self.lines.splice(rows, lines);
+        }
     }
 
     pub fn get_all_tokens(&self) -> Vec<SemanticToken> {
         self.get_tokens_for_lines(0..self.lines.len())
     }
 
+    /// The tokens of the lines in the range, encoded like a full result, so the first token's
+    /// line is relative to the start of the document, as expected for a range request. Lines
+    /// past the end of the document are ignored.
     pub fn get_tokens_for_lines(&self, line_range: impl RangeBounds<usize>) -> Vec<SemanticToken> {
+        let end = match line_range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.lines.len(),
+        }
+        .min(self.lines.len());
         let line_range = match line_range.start_bound() {
             Bound::Included(start) => *start,
             Bound::Excluded(start) => start + 1,
             Bound::Unbounded => 0,
-        }..match line_range.end_bound() {
-            Bound::Included(end) => end + 1,
-            Bound::Excluded(end) => *end,
-            Bound::Unbounded => self.lines.len(),
-        };
+        }
+        .min(end)..end;
 
-        let prev_row = if line_range.start > 0 {
-            (0..line_range.start)
-                .rev()
-                .find(|i| !self.lines[*i].tokens.is_empty())
-                .unwrap_or(0)
-        } else {
-            0
-        };
         let row_offset = line_range.start;
         let (sem_tokens, _) = self.lines[line_range].iter().enumerate().fold(
-            (Vec::new(), prev_row),
+            (Vec::new(), 0),
             |(sem_tokens, prev_row), (relative_row, line)| {
                 line.tokens.iter().fold(
                     (sem_tokens, prev_row),
@@ -79,7 +158,7 @@ impl SyntaxMap {
                             delta_start: token.delta_start,
                             length: token.length,
                             token_type: token.kind,
-                            token_modifiers_bitset: 0,
+                            token_modifiers_bitset: token.modifiers,
                         });
                         (sem_tokens, row)
                     },
@@ -89,37 +168,108 @@ impl SyntaxMap {
         sem_tokens
     }
 
+    /// The edit turning `previous` into `current`, replacing the tokens between their common
+    /// prefix and suffix. Edit offsets count integers, five per token, as in the LSP encoding.
+    pub fn token_edits(
+        previous: &[SemanticToken],
+        current: &[SemanticToken],
+    ) -> Vec<SemanticTokensEdit> {
+        let prefix_len = previous
+            .iter()
+            .zip(current)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix_len = previous[prefix_len..]
+            .iter()
+            .rev()
+            .zip(current[prefix_len..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let deleted = &previous[prefix_len..previous.len() - suffix_len];
+        let inserted = &current[prefix_len..current.len() - suffix_len];
+        if deleted.is_empty() && inserted.is_empty() {
+            return Vec::new();
+        }
+        vec![SemanticTokensEdit {
+            start: (prefix_len * 5) as u32,
+            delete_count: (deleted.len() * 5) as u32,
+            data: (!inserted.is_empty()).then(|| inserted.to_vec()),
+        }]
+    }
+
     fn generate_lines(doc: &DataFlexDocument) -> Vec<Line> {
-        let query = Query::new(
-            &tree_sitter_dataflex::LANGUAGE.into(),
-            tree_sitter_dataflex::HIGHLIGHTS_QUERY,
-        )
-        .expect("Error loading HIGHLIGHTS_QUERY");
+        Self::generate_lines_in_rows(doc, Self::highlights_query(), 0..doc.line_map.line_count())
+    }
+
+    #[cfg(test)]
+    fn generate_lines_with_query(doc: &DataFlexDocument, query_source: &str) -> Vec<Line> {
+        let query = Self::load_query(query_source);
+        Self::generate_lines_in_rows(doc, query.as_ref(), 0..doc.line_map.line_count())
+    }
+
+    fn highlights_query() -> Option<&'static Query> {
+        static HIGHLIGHTS_QUERY: OnceLock<Option<Query>> = OnceLock::new();
+        HIGHLIGHTS_QUERY
+            .get_or_init(|| Self::load_query(tree_sitter_dataflex::HIGHLIGHTS_QUERY))
+            .as_ref()
+    }
+
+    fn load_query(source: &str) -> Option<Query> {
+        Query::new(&tree_sitter_dataflex::LANGUAGE.into(), source)
+            .inspect_err(|e| {
+                log::error!("Error loading HIGHLIGHTS_QUERY, semantic tokens are disabled: {e}")
+            })
+            .ok()
+    }
+
+    /// The lines of the rows, with tokens for the captures on those rows.
+    fn generate_lines_in_rows(
+        doc: &DataFlexDocument,
+        query: Option<&Query>,
+        rows: Range<usize>,
+    ) -> Vec<Line> {
+        let mut lines = Vec::with_capacity(rows.len());
+        lines.resize_with(rows.len(), || Line { tokens: Vec::new() });
+
+        let Some(query) = query else {
+            return lines;
+        };
+        let Some(root_node) = doc.root_node() else {
+            return lines;
+        };
 
         let mut query_cursor = QueryCursor::new();
-        let captures = query_cursor.captures(
-            &query,
-            doc.root_node().unwrap(),
-            doc.line_map.text_provider(),
-        );
+        query_cursor.set_point_range(Point::new(rows.start, 0)..Point::new(rows.end, 0));
+        let captures = query_cursor.captures(query, root_node, doc.line_map.text_provider());
         let capture_names = query.capture_names();
 
-        let mut lines = Vec::with_capacity(doc.line_map.line_count());
-        lines.resize_with(doc.line_map.line_count(), || Line { tokens: Vec::new() });
-
         let index = doc.index.get();
 
         let (lines, _) = captures.fold(
-            (lines, Point { row: 0, column: 0 }),
+            (
+                lines,
+                Point {
+                    row: rows.start,
+                    column: 0,
+                },
+            ),
             |(lines, prev_pos), query_match| {
                 query_match.0.captures.iter().fold(
                     (lines, prev_pos),
                     |(mut lines, prev_pos), capture| {
                         let start = capture.node.start_position();
                         let end = capture.node.end_position();
-                        if start.row == end.row {
-                            let token = match capture_names[capture.index as usize] {
+                        let capture_name = capture_names[capture.index as usize];
+                        let is_multi_line_comment = capture_name == "comment"
+                            && start.row != end.row
+                            && start.row < rows.end
+                            && end.row >= rows.start;
+                        if !rows.contains(&start.row) && !is_multi_line_comment {
+                            (lines, prev_pos)
+                        } else if start.row == end.row {
+                            let token = match capture_name {
                                 "keyword" => Some(SyntaxToken::new(start, end, 0, prev_pos)),
+                                "comment" => Some(SyntaxToken::new(start, end, 9, prev_pos)),
                                 "entity.other.inherited-class" => {
                                     let name =
                                         SymbolName::from(doc.line_map.text_in_range(start, end));
@@ -254,13 +404,53 @@ impl SyntaxMap {
                                 _ => None,
                             };
                             if let Some(token) = token {
-                                lines[start.row].tokens.push(token);
-                                (lines, start)
+                                let token = token.with_declaration_modifier(&capture.node);
+                                let prev_pos = Self::push_token(
+                                    &mut lines[start.row - rows.start],
+                                    token,
+                                    start,
+                                    prev_pos,
+                                );
+                                (lines, prev_pos)
                             } else {
                                 (lines, prev_pos)
                             }
+                        } else if is_multi_line_comment {
+                            // Block comments are broken up into a token per row, since clients
+                            // don't have to support tokens spanning rows.
+                            let prev_pos = (start.row..=end.row)
+                                .filter(|row| rows.contains(row))
+                                .fold(prev_pos, |prev_pos, row| {
+                                    let row_start = if row == start.row {
+                                        start
+                                    } else {
+                                        Point::new(row, 0)
+                                    };
+                                    let row_end = if row == end.row {
+                                        end
+                                    } else {
+                                        Point::new(
+                                            row,
+                                            doc.line_map
+                                                .line_text_with_ending(row)
+                                                .map(|text| {
+                                                    text.trim_end_matches(['\r', '\n']).len()
+                                                })
+                                                .unwrap_or_default(),
+                                        )
+                                    };
+                                    if row_end.column <= row_start.column {
+                                        return prev_pos;
+                                    }
+                                    Self::push_token(
+                                        &mut lines[row - rows.start],
+                                        SyntaxToken::new(row_start, row_end, 9, prev_pos),
+                                        row_start,
+                                        prev_pos,
+                                    )
+                                });
+                            (lines, prev_pos)
                         } else {
-                            //FIXME: Break up multi-line tokens
                             (lines, prev_pos)
                         }
                     },
@@ -270,12 +460,52 @@ impl SyntaxMap {
 
         lines
     }
+
+    /// Adds the token to the line unless it overlaps the previous token, returning the start of
+    /// the last token. A token for the same span as the previous one replaces it if it has higher
+    /// priority, keeping its `delta_start` relative to the token before it.
+    fn push_token(line: &mut Line, token: SyntaxToken, start: Point, prev_pos: Point) -> Point {
+        if start.row == prev_pos.row
+            && let Some(last) = line.tokens.last_mut()
+        {
+            if start == prev_pos {
+                if token.priority() > last.priority() {
+                    last.length = token.length;
+                    last.kind = token.kind;
+                    last.modifiers = token.modifiers;
+                }
+                return prev_pos;
+            } else if start.column < prev_pos.column + last.length as usize {
+                return prev_pos;
+            }
+        }
+        line.tokens.push(token);
+        start
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Decodes delta encoded semantic tokens into absolute `(line, column, length, type)` tuples.
+    fn decode_tokens(tokens: &[SemanticToken]) -> Vec<(u32, u32, u32, u32)> {
+        let mut line = 0;
+        let mut column = 0;
+        tokens
+            .iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    line += token.delta_line;
+                    column = token.delta_start;
+                } else {
+                    column += token.delta_start;
+                }
+                (line, column, token.length, token.token_type)
+            })
+            .collect()
+    }
+
     #[test]
     fn test_lines() {
         let doc = DataFlexDocument::new(
@@ -291,17 +521,20 @@ mod tests {
                         SyntaxToken {
                             delta_start: 0,
                             length: 6,
-                            kind: 0
+                            kind: 0,
+                            modifiers: 0
                         },
                         SyntaxToken {
                             delta_start: 13,
                             length: 2,
-                            kind: 0
+                            kind: 0,
+                            modifiers: 0
                         },
                         SyntaxToken {
                             delta_start: 3,
                             length: 1,
-                            kind: 0
+                            kind: 0,
+                            modifiers: 0
                         }
                     ]
                 },
@@ -309,7 +542,8 @@ mod tests {
                     tokens: vec![SyntaxToken {
                         delta_start: 0,
                         length: 10,
-                        kind: 0
+                        kind: 0,
+                        modifiers: 0
                     }]
                 },
                 Line { tokens: vec![] }
@@ -317,6 +551,145 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_overlapping_captures() {
+        let index = index::IndexRef::make_test_index_ref();
+        let content = "Object oTest is a cTest\nEnd_Object\n";
+        index::Indexer::index_test_content(content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), content, index);
+        for query in [
+            "(object_header name: (identifier) @keyword)
+             (object_header name: (identifier) @entity.name.tag.object)",
+            "(object_header name: (identifier) @entity.name.tag.object)
+             (object_header name: (identifier) @keyword)",
+        ] {
+            assert_eq!(
+                SyntaxMap::generate_lines_with_query(&doc, query),
+                [
+                    Line {
+                        tokens: vec![SyntaxToken {
+                            delta_start: 7,
+                            length: 5,
+                            kind: 4,
+                            modifiers: DECLARATION_MODIFIER
+                        }]
+                    },
+                    Line { tokens: vec![] },
+                    Line { tokens: vec![] }
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_declaration_modifier() {
+        let index = index::IndexRef::make_test_index_ref();
+        let content = "Class cFoo is a cObject\nEnd_Class\n\nObject oFoo is a cFoo\nEnd_Object\n";
+        index::Indexer::index_test_content(content, "test.pkg".into(), &index);
+        let doc = DataFlexDocument::new("test.pkg".into(), content, index);
+        let tokens = doc.syntax_map.unwrap().get_all_tokens();
+        let names = decode_tokens(&tokens)
+            .into_iter()
+            .zip(tokens.iter().map(|token| token.token_modifiers_bitset))
+            .filter(|((_, _, _, token_type), _)| *token_type != 0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ((0, 6, 4, 1), DECLARATION_MODIFIER),
+                ((3, 7, 4, 4), DECLARATION_MODIFIER),
+                ((3, 17, 4, 1), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comment_tokens() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "// Line comment\nMove 1 /* block\n  comment */ to iValue\nMove 2 /* inline */ to iValue\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let tokens = doc.syntax_map.unwrap().get_all_tokens();
+        let comments = decode_tokens(&tokens)
+            .into_iter()
+            .filter(|(_, _, _, token_type)| *token_type == 9)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            [(0, 0, 15, 9), (1, 7, 8, 9), (2, 0, 12, 9), (3, 7, 12, 9)]
+        );
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let content = (0..10).fold(String::from("Object oTest is a cObject\n"), |content, i| {
+            content + &format!("    Procedure Test{i}\n    End_Procedure\n")
+        }) + "End_Object\n";
+        let mut doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            &content,
+            index::IndexRef::make_test_index_ref(),
+        );
+        let tokens_ptr = |doc: &DataFlexDocument, row: usize| {
+            doc.syntax_map.as_ref().unwrap().lines[row].tokens.as_ptr()
+        };
+        let edit = |line, start, end, text: &str| lsp_types::TextDocumentContentChangeEvent {
+            range: Some(lsp_types::Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, end),
+            )),
+            range_length: None,
+            text: text.into(),
+        };
+        let first_line = tokens_ptr(&doc, 0);
+        let unrelated_line = tokens_ptr(&doc, 7);
+
+        // Editing a name within a line keeps the tokens of the other lines.
+        doc.edit_content(&vec![edit(3, 14, 19, "Other")]);
+        assert_eq!(tokens_ptr(&doc, 0), first_line);
+        assert_eq!(tokens_ptr(&doc, 7), unrelated_line);
+        assert_eq!(
+            doc.syntax_map.as_ref().unwrap().lines,
+            SyntaxMap::new(&doc).lines
+        );
+
+        // Inserting lines shifts the tokens of the lines below.
+        doc.edit_content(&vec![edit(
+            5,
+            0,
+            0,
+            "    Procedure Extra\n    End_Procedure\n",
+        )]);
+        assert_eq!(tokens_ptr(&doc, 0), first_line);
+        assert_eq!(tokens_ptr(&doc, 9), unrelated_line);
+        assert_eq!(
+            doc.syntax_map.as_ref().unwrap().lines,
+            SyntaxMap::new(&doc).lines
+        );
+    }
+
+    #[test]
+    fn test_invalid_highlights_query() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        assert_eq!(
+            SyntaxMap::generate_lines_with_query(&doc, "(not_a_node_kind) @keyword"),
+            [
+                Line { tokens: vec![] },
+                Line { tokens: vec![] },
+                Line { tokens: vec![] }
+            ]
+        );
+        assert_eq!(
+            doc.node_kind_at(lsp_types::Position::new(0, 7)),
+            Some("identifier")
+        );
+    }
+
     #[test]
     fn test_get_all_tokens() {
         let doc = DataFlexDocument::new(
@@ -406,4 +779,44 @@ mod tests {
             }]
         );
     }
+
+    #[test]
+    fn test_decoded_token_positions() {
+        let doc = DataFlexDocument::new(
+            "test.pkg".into(),
+            "Object oTest is a cTest\n\n\n    Procedure DoIt\n    End_Procedure\n\nEnd_Object\n",
+            index::IndexRef::make_test_index_ref(),
+        );
+        let syntax_map = doc.syntax_map.as_ref().unwrap();
+        let keywords = |tokens: Vec<SemanticToken>| {
+            decode_tokens(&tokens)
+                .into_iter()
+                .filter(|(_, _, _, token_type)| *token_type == 0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keywords(syntax_map.get_all_tokens()),
+            [
+                (0, 0, 6, 0),
+                (0, 13, 2, 0),
+                (0, 16, 1, 0),
+                (3, 4, 9, 0),
+                (4, 4, 13, 0),
+                (6, 0, 10, 0)
+            ]
+        );
+
+        // A range is encoded relative to the start of the document.
+        assert_eq!(
+            decode_tokens(&syntax_map.get_tokens_for_lines(3..)),
+            decode_tokens(&syntax_map.get_all_tokens())[3..]
+        );
+        assert_eq!(
+            keywords(syntax_map.get_tokens_for_lines(4..=6)),
+            [(4, 4, 13, 0), (6, 0, 10, 0)]
+        );
+        assert_eq!(syntax_map.get_tokens_for_lines(4..=6)[0].delta_line, 4);
+        assert!(syntax_map.get_tokens_for_lines(20..30).is_empty());
+    }
 }
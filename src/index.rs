@@ -1,34 +1,43 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::path::PathBuf;
 
 use multimap::MultiMap;
-use rayon::prelude::*;
 use streaming_iterator::StreamingIterator;
 use strum::EnumString;
 
 mod index_file;
 mod index_symbol;
 mod indexer;
+mod int_file;
 mod lookup_tables;
 mod symbols_diff;
 mod workspace;
 
 pub use index_symbol::*;
 
-pub use indexer::{Indexer, IndexerConfig, IndexerObserver, IndexerState};
+pub use indexer::{
+    Indexer, IndexerConfig, IndexerObserver, IndexerState, IndexingPhase, IndexingProgress,
+};
 pub use workspace::{DataFlexVersion, WorkspaceInfo};
 
-pub use index_file::{DataFlexTable, IndexFile, IndexFileRef, QualifiedDataFlexTableRef};
+pub use index_file::{
+    ClassImport, CommandDefinition, DataFlexTable, IndexFile, IndexFileRef,
+    QualifiedDataFlexTableRef,
+};
 
 use lookup_tables::LookupTables;
 
 #[derive(Debug)]
 pub struct Index {
     workspace: WorkspaceInfo,
+    system_paths: Vec<PathBuf>,
     files: HashMap<IndexFileRef, IndexFile>,
+    /// Dependencies that weren't found on disk by `Indexer::index_missing_dependencies()`.
+    missing_files: HashSet<IndexFileRef>,
     lookup_tables: LookupTables,
     updated_file_count: usize,
+    indexing_progress: Option<IndexingProgress>,
 }
 
 #[derive(Clone)]
@@ -43,14 +52,62 @@ impl Index {
     pub fn new(workspace: WorkspaceInfo) -> Self {
         Self {
             workspace,
+            system_paths: Vec::new(),
             files: HashMap::new(),
+            missing_files: HashSet::new(),
             lookup_tables: LookupTables::new(),
             updated_file_count: 0,
+            indexing_progress: None,
         }
     }
 
+    /// The class with the name, preferring a full definition over a forward declaration.
     pub fn find_class(&self, name: &SymbolName) -> Option<&IndexSymbolRef> {
-        self.lookup_tables.class_lookup_table().get(name)
+        let classes = self.lookup_tables.class_lookup_table().get_vec(name)?;
+        classes
+            .iter()
+            .find(|class| !self.is_class_declaration(class))
+            .or_else(|| classes.first())
+    }
+
+    /// The class with the name as seen from the file. When several files define a class with the
+    /// same name, a class defined in the file itself is preferred, then one defined in the nearest
+    /// file it uses, directly or through other files. Forward declarations are only considered
+    /// when the class has no full definition.
+    pub fn find_class_from_file(
+        &self,
+        name: &SymbolName,
+        file: &IndexFileRef,
+    ) -> Option<&IndexSymbolRef> {
+        let all_classes = self.lookup_tables.class_lookup_table().get_vec(name)?;
+        let mut classes: Vec<&IndexSymbolRef> = all_classes
+            .iter()
+            .filter(|class| !self.is_class_declaration(class))
+            .collect();
+        if classes.is_empty() {
+            classes = all_classes.iter().collect();
+        }
+        if let [class] = classes.as_slice() {
+            return Some(*class);
+        }
+        classes
+            .iter()
+            .find(|class| class.file_ref == *file)
+            .or_else(|| {
+                self.dependency_files(file)
+                    .into_iter()
+                    .find_map(|dependency| {
+                        classes.iter().find(|class| class.file_ref == dependency)
+                    })
+            })
+            .or_else(|| classes.first())
+            .copied()
+    }
+
+    fn is_class_declaration(&self, class: &IndexSymbolRef) -> bool {
+        self.resolve_symbol(class)
+            .and_then(|qualified_symbol| ClassSymbol::from_index_symbol(qualified_symbol.symbol))
+            .is_some_and(|class_symbol| class_symbol.is_declaration)
     }
 
     pub fn is_known_class(&self, name: &SymbolName) -> bool {
@@ -103,6 +160,44 @@ impl Index {
             .collect()
     }
 
+    /// All methods of the kind as (class, method) pairs, where the class is the object or class
+    /// declaring the method. Top level methods don't have an owner and are not included.
+    pub fn methods_of_kind(
+        &self,
+        kind: MethodKind,
+    ) -> impl Iterator<Item = (&SymbolName, &SymbolName)> + use<'_> {
+        self.lookup_tables
+            .method_lookup_table(kind)
+            .iter_all()
+            .flat_map(|(name, symbol_refs)| {
+                symbol_refs.iter().filter_map(move |symbol_ref| {
+                    symbol_ref
+                        .symbol_path
+                        .parent_name()
+                        .map(|class_name| (class_name, name))
+                })
+            })
+    }
+
+    pub fn all_known_external_functions(&self) -> Vec<SymbolName> {
+        self.lookup_tables
+            .method_lookup_table(MethodKind::Get)
+            .iter_all()
+            .filter(|(_, symbol_refs)| {
+                symbol_refs.iter().any(|s| {
+                    s.symbol_path.is_top_level()
+                        && self
+                            .resolve_symbol(s)
+                            .and_then(|qualified_symbol| {
+                                MethodSymbol::from_index_symbol(qualified_symbol.symbol)
+                            })
+                            .is_some_and(|s| s.external.is_some())
+                })
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
     pub fn find_methods(
         &self,
         name: &SymbolName,
@@ -148,6 +243,13 @@ impl Index {
             .unwrap_or_default()
     }
 
+    /// Whether the file declares the object name with `Register_Object`.
+    pub fn is_registered_object(&self, file: &IndexFileRef, name: &SymbolName) -> bool {
+        self.find_file(file)
+            .and_then(|index_file| index_file.registered_objects.as_deref())
+            .is_some_and(|names| names.contains(name))
+    }
+
     pub fn all_known_global_variables(&self) -> Vec<SymbolName> {
         self.lookup_tables
             .global_variable_lookup_table()
@@ -220,6 +322,17 @@ impl Index {
             })
     }
 
+    pub fn find_command(&self, name: &SymbolName) -> Option<&CommandDefinition> {
+        self.lookup_tables
+            .command_lookup_table()
+            .get(name)
+            .and_then(|f| self.files.get(f))?
+            .commands
+            .as_ref()?
+            .iter()
+            .find(|c| c.name == *name)
+    }
+
     pub fn is_known_dataflex_table(&self, name: &SymbolName) -> bool {
         self.lookup_tables
             .dataflex_table_lookup_table()
@@ -236,7 +349,111 @@ impl Index {
     }
 
     pub fn find_file_path(&self, file: &IndexFileRef) -> Option<&PathBuf> {
-        self.files.get(file).map(|index_file| &index_file.path)
+        self.find_file(file).map(|index_file| &index_file.path)
+    }
+
+    pub fn set_system_paths(&mut self, system_paths: Vec<PathBuf>) {
+        self.system_paths = system_paths;
+    }
+
+    /// The directories files are indexed from: the workspace root folder, the root folders of
+    /// local workspace dependencies and the system paths.
+    pub fn search_paths(&self) -> Vec<PathBuf> {
+        std::iter::once(self.workspace.get_root_folder().clone())
+            .filter(|path| !path.as_os_str().is_empty())
+            .chain(
+                self.workspace
+                    .local_workspace_dependencies()
+                    .into_iter()
+                    .map(|ws| ws.get_root_folder().clone()),
+            )
+            .chain(self.system_paths.iter().cloned())
+            .collect()
+    }
+
+    fn find_file(&self, file: &IndexFileRef) -> Option<&IndexFile> {
+        self.files.get(file).or_else(|| {
+            file.with_default_extension()
+                .and_then(|file| self.files.get(&file))
+        })
+    }
+
+    /// The shortest chain of `Use` dependencies leading from the file through the dependency back
+    /// to the file, if the dependency uses the file directly or indirectly. Each file is searched
+    /// once, so other cycles among the dependencies don't keep the search from terminating.
+    pub fn dependency_cycle(
+        &self,
+        file: &IndexFileRef,
+        dependency: &IndexFileRef,
+    ) -> Option<Vec<&PathBuf>> {
+        let start = self.find_file(file)?;
+        let start_ref = IndexFileRef::from(&start.path);
+        let mut visited = HashSet::new();
+        let mut chains = VecDeque::from([vec![start, self.find_file(dependency)?]]);
+        while let Some(chain) = chains.pop_front() {
+            let last = chain[chain.len() - 1];
+            let last_ref = IndexFileRef::from(&last.path);
+            if last_ref == start_ref {
+                return Some(chain.iter().map(|index_file| &index_file.path).collect());
+            }
+            if !visited.insert(last_ref) {
+                continue;
+            }
+            for next in last
+                .dependencies
+                .iter()
+                .filter_map(|dep| self.find_file(dep))
+            {
+                let mut next_chain = chain.clone();
+                next_chain.push(next);
+                chains.push_back(next_chain);
+            }
+        }
+        None
+    }
+
+    /// The files used by the file, directly or through other files, nearest first.
+    fn dependency_files(&self, file: &IndexFileRef) -> Vec<IndexFileRef> {
+        let mut visited = HashSet::from([file.clone()]);
+        let mut queue = VecDeque::from([file.clone()]);
+        let mut dependencies = Vec::new();
+        while let Some(current) = queue.pop_front() {
+            for dependency in self
+                .find_file(&current)
+                .into_iter()
+                .flat_map(|index_file| &index_file.dependencies)
+                .filter_map(|dep| self.find_file(dep))
+                .map(|index_file| IndexFileRef::from(&index_file.path))
+            {
+                if visited.insert(dependency.clone()) {
+                    dependencies.push(dependency.clone());
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// The files that use the file, directly or through other files, e.g. to find the files
+    /// whose diagnostics may change when the file changes.
+    pub fn dependent_files(&self, file: &IndexFileRef) -> Vec<IndexFileRef> {
+        let users = self.lookup_tables.dependent_lookup_table();
+        let mut visited = HashSet::from([file.clone()]);
+        let mut queue = VecDeque::from([file.clone()]);
+        let mut dependents = Vec::new();
+        while let Some(current) = queue.pop_front() {
+            for user in users.get_vec(&current).into_iter().flatten() {
+                if visited.insert(user.clone()) {
+                    dependents.push(user.clone());
+                    queue.push_back(user.clone());
+                }
+            }
+        }
+        dependents
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
     }
 
     pub fn all_known_files(&self) -> Vec<IndexFileRef> {
@@ -714,34 +931,45 @@ impl Index {
             .is_some()
     }
 
-    pub fn matching_symbols<'a>(&'a self, query: &'a str) -> IndexSymbolIter<'a> {
-        IndexSymbolIter::new(self.files.values().flat_map(|index_file| {
-            let symbols: Vec<QualifiedIndexSymbol<'_>> = index_file
-                .symbols
-                .par_iter()
-                .flat_map(|s| rayon::iter::walk_tree(s, |s| s.children()))
-                .filter(|s| s.name().starts_with(query))
-                .map(|s| QualifiedIndexSymbol {
+    /// All symbols whose name matches the query, with the best matches first.
+    pub fn matching_symbols<'a>(
+        &'a self,
+        query: &str,
+        mode: SymbolMatchMode,
+    ) -> IndexSymbolIter<'a> {
+        // Symbols are walked sequentially rather than on the rayon pool, since the caller holds
+        // the index read lock and the pool workers may be blocked waiting for the write lock.
+        let mut symbols: Vec<(usize, QualifiedIndexSymbol<'a>)> = self
+            .files
+            .values()
+            .flat_map(|index_file| {
+                let mut stack: Vec<&IndexSymbol> = index_file.symbols.iter().rev().collect();
+                std::iter::from_fn(move || {
+                    let symbol = stack.pop()?;
+                    stack.extend(symbol.children().rev());
+                    Some(symbol)
+                })
+                .map(move |s| QualifiedIndexSymbol {
                     file: index_file,
                     symbol: s,
                 })
-                .collect();
-            symbols.into_iter()
-        }))
+            })
+            .filter_map(|s| Some((s.symbol.name().match_score(query, mode)?, s)))
+            .collect();
+        symbols.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        IndexSymbolIter::new(symbols.into_iter().map(|(_, s)| s))
     }
 
     pub fn top_level_class_and_object_symbols<'a>(&'a self) -> IndexSymbolIter<'a> {
         IndexSymbolIter::new(self.files.values().flat_map(|index_file| {
-            let symbols: Vec<QualifiedIndexSymbol<'_>> = index_file
+            index_file
                 .symbols
-                .par_iter()
+                .iter()
                 .filter(|s| matches!(s, IndexSymbol::Class(_) | IndexSymbol::Object(_)))
-                .map(|s| QualifiedIndexSymbol {
+                .map(move |s| QualifiedIndexSymbol {
                     file: index_file,
                     symbol: s,
                 })
-                .collect();
-            symbols.into_iter()
         }))
     }
 
@@ -784,17 +1012,115 @@ impl Index {
             .filter(move |tag| tag.name == tag_name)
     }
 
+    /// The class or object in the file declaring the member at the location.
+    pub fn find_member_container(
+        &self,
+        file: &IndexFileRef,
+        location: SourceLocation,
+    ) -> Option<QualifiedIndexSymbol<'_>> {
+        let file = self.find_file(file)?;
+        file.find_member_container(location)
+            .map(|symbol| QualifiedIndexSymbol { file, symbol })
+    }
+
+    /// The direct superclass of the class, resolved from the file that declares the class.
+    pub fn supertypes(&self, class: &IndexSymbolRef) -> Vec<QualifiedIndexSymbol<'_>> {
+        self.resolve_symbol(class)
+            .and_then(|qualified_symbol| ClassSymbol::from_index_symbol(qualified_symbol.symbol))
+            .and_then(|class_symbol| {
+                self.find_class_from_file(&class_symbol.superclass, &class.file_ref)
+            })
+            .and_then(|symbol_ref| self.resolve_symbol(symbol_ref))
+            .into_iter()
+            .collect()
+    }
+
+    /// The classes whose superclass resolves to the class from the file declaring them.
+    pub fn subtypes(&self, class: &IndexSymbolRef) -> Vec<QualifiedIndexSymbol<'_>> {
+        self.lookup_tables
+            .subclass_lookup_table()
+            .get_vec(class.symbol_path.name())
+            .into_iter()
+            .flatten()
+            .filter(|subclass| {
+                self.find_class_from_file(class.symbol_path.name(), &subclass.file_ref)
+                    .is_some_and(|superclass| {
+                        superclass.file_ref == class.file_ref
+                            && superclass.symbol_path == class.symbol_path
+                    })
+            })
+            .filter_map(|symbol_ref| self.resolve_symbol(symbol_ref))
+            .collect()
+    }
+
     pub fn class_hierarchy<'a>(
         &'a self,
         class: QualifiedIndexSymbol<'a>,
     ) -> ClassHierarchyIter<'a> {
         ClassHierarchyIter {
             index: self,
+            visited: HashSet::from([class.symbol.name()]),
             current: Some(class),
             mixins: Default::default(),
         }
     }
 
+    /// The mixins of the class, imported with `Import_Class_Protocol` or defined in packages
+    /// `Use`d inside the class body.
+    fn class_mixins<'a>(
+        &'a self,
+        class: QualifiedIndexSymbol<'a>,
+    ) -> Vec<QualifiedIndexSymbol<'a>> {
+        let Some(class_symbol) = ClassSymbol::from_index_symbol(class.symbol) else {
+            return Vec::new();
+        };
+        let imported_classes = class
+            .file
+            .class_imports
+            .iter()
+            .flat_map(|imports| imports.iter())
+            .filter(|import| &import.class_name == class_symbol.symbol_path.name())
+            .filter_map(|import| self.find_file(&import.file))
+            .flat_map(|file| {
+                file.symbols
+                    .iter()
+                    .filter(|symbol| matches!(symbol, IndexSymbol::Class(_)))
+                    .map(move |symbol| QualifiedIndexSymbol { file, symbol })
+            });
+        let file_ref = IndexFileRef::from(&class.file.path);
+        class_symbol
+            .mixins
+            .iter()
+            .filter_map(|class_name| self.find_class_from_file(class_name, &file_ref))
+            .filter_map(|symbol_ref| self.resolve_symbol(symbol_ref))
+            .chain(imported_classes)
+            .collect()
+    }
+
+    /// The chain of superclasses leading from the class back to itself, if the class inherits from
+    /// itself in a malformed workspace.
+    pub fn inheritance_cycle(&self, name: &SymbolName) -> Option<Vec<SymbolName>> {
+        let mut chain = vec![name.clone()];
+        let mut visited = HashSet::new();
+        let mut current = name;
+        loop {
+            let class = self
+                .find_class(current)
+                .and_then(|symbol_ref| self.resolve_symbol(symbol_ref))
+                .and_then(|qualified_symbol| {
+                    ClassSymbol::from_index_symbol(qualified_symbol.symbol)
+                })?;
+            current = &class.superclass;
+            chain.push(current.clone());
+            if current == name {
+                return Some(chain);
+            }
+            if !visited.insert(current) {
+                return None;
+            }
+        }
+    }
+
     pub fn inherited_class_members<'a>(
         &'a self,
         class: QualifiedIndexSymbol<'a>,
@@ -825,42 +1151,63 @@ impl Index {
             None
         }
     }
+
+    /// Looks up a symbol by its full path, e.g. a method by its class and name, starting from a
+    /// class or top level object. Unlike the lookups by name, this finds one exact symbol.
+    pub fn find_by_path(&self, path: &SymbolPath) -> Option<QualifiedIndexSymbol<'_>> {
+        let name = path.as_slice().first()?;
+        let classes = self
+            .lookup_tables
+            .class_lookup_table()
+            .get_vec(name)
+            .map(|classes| classes.iter())
+            .unwrap_or_default();
+        let objects = self
+            .find_objects(name)
+            .filter(|object| object.symbol_path.is_top_level());
+        classes.chain(objects).find_map(|symbol_ref| {
+            self.resolve_symbol(&IndexSymbolRef::new(
+                symbol_ref.file_ref.clone(),
+                path.clone(),
+            ))
+        })
+    }
 }
 
+/// Walks the superclasses and mixins of a class, stopping at a class that was already visited,
+/// since a malformed workspace can contain inheritance cycles.
 pub struct ClassHierarchyIter<'a> {
     index: &'a Index,
+    visited: HashSet<&'a SymbolName>,
     current: Option<QualifiedIndexSymbol<'a>>,
-    mixins: core::slice::Iter<'a, SymbolName>,
+    mixins: std::vec::IntoIter<QualifiedIndexSymbol<'a>>,
 }
 
 impl<'a> Iterator for ClassHierarchyIter<'a> {
     type Item = QualifiedIndexSymbol<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(mixin) = self
-            .mixins
-            .next()
-            .and_then(|class_name| self.index.find_class(class_name))
-            .and_then(|symbol_ref| self.index.resolve_symbol(symbol_ref))
-        {
+        if let Some(mixin) = self.mixins.next() {
             Some(mixin)
         } else {
             self.mixins = self
                 .current
-                .as_ref()
-                .and_then(|qualified_symbol| {
-                    ClassSymbol::from_index_symbol(qualified_symbol.symbol)
-                })
-                .map(|class| class.mixins.iter())
-                .unwrap_or_default();
+                .map(|qualified_symbol| self.index.class_mixins(qualified_symbol))
+                .unwrap_or_default()
+                .into_iter();
             let next = self
                 .current
                 .as_ref()
                 .and_then(|qualified_symbol| {
                     ClassSymbol::from_index_symbol(qualified_symbol.symbol)
+                        .map(|class| (class, IndexFileRef::from(&qualified_symbol.file.path)))
                 })
-                .and_then(|class| self.index.find_class(&class.superclass))
-                .and_then(|symbol_ref| self.index.resolve_symbol(symbol_ref));
+                .and_then(|(class, file_ref)| {
+                    self.index
+                        .find_class_from_file(&class.superclass, &file_ref)
+                })
+                .and_then(|symbol_ref| self.index.resolve_symbol(symbol_ref))
+                .filter(|next| self.visited.insert(next.symbol.name()));
             if let Some(next) = next {
                 self.current.replace(next)
             } else {
@@ -1001,6 +1348,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_matching_symbols() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cWebButton is a cWebBaseControl\nEnd_Class\nClass cWebView is a cWebBaseView\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let matching = |query, mode| {
+            index
+                .matching_symbols(query, mode)
+                .map(|s| s.symbol.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching("cWB", SymbolMatchMode::Fuzzy), ["cWebButton"]);
+        assert_eq!(matching("cwb", SymbolMatchMode::Fuzzy).len(), 2);
+        assert_eq!(matching("cWB", SymbolMatchMode::Substring), [] as [&str; 0]);
+        assert_eq!(
+            matching("button", SymbolMatchMode::Substring),
+            ["cWebButton"]
+        );
+        assert_eq!(matching("WebV", SymbolMatchMode::Substring), ["cWebView"]);
+    }
+
+    #[test]
+    fn test_find_file_path_without_extension() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cWebView is a cWebBaseView\nEnd_Class\n",
+            "/src/cWebView.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content("Use cWebView\n", "/src/test.vw".into(), &index_ref);
+
+        let index = index_ref.get();
+        let dependencies = &index.files[&IndexFileRef::from("test.vw")].dependencies;
+        assert_eq!(dependencies, &[IndexFileRef::from("cWebView")]);
+        assert_eq!(
+            index.find_file_path(&dependencies[0]),
+            Some(&PathBuf::from("/src/cWebView.pkg"))
+        );
+        assert_eq!(
+            index.find_file_path(&IndexFileRef::from("cWebView.vw")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_methods_of_kind() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cBaseClass\n    Procedure SayHello\n    End_Procedure\n    Procedure SayGoodbye\n    End_Procedure\n    Function Greeting Returns String\n    End_Function\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+
+        let index = index_ref.get();
+        let mut methods: Vec<String> = index
+            .methods_of_kind(MethodKind::Msg)
+            .map(|(class, method)| format!("{class}.{method}"))
+            .collect();
+        methods.sort();
+        assert_eq!(methods, ["cMyClass.SayGoodbye", "cMyClass.SayHello"]);
+    }
+
     #[test]
     fn test_class_hierarchy() {
         let index_ref = IndexRef::make_test_index_ref();
@@ -1018,15 +1432,373 @@ mod tests {
         let mut class_hierarchy = index.class_hierarchy(class);
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 2, column: 6 }, range: SourceRange { start: SourceLocation { line: 2, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMySubClass\"), superclass: SymbolName(\"cMyBaseClass\"), mixins: [], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 2, column: 6 }, range: SourceRange { start: SourceLocation { line: 2, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMySubClass\"), superclass: SymbolName(\"cMyBaseClass\"), mixins: [], is_declaration: true, members: [], metadata: [] }) })"
         );
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 9 } }, symbol_path: SymbolPath(\"cMyBaseClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 9 } }, symbol_path: SymbolPath(\"cMyBaseClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: true, members: [], metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", class_hierarchy.next()), "None");
     }
 
+    #[test]
+    fn test_dependency_cycle() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content("Use b.pkg\n", "a.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use c\nUse d.pkg\n", "b.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use b.pkg\nUse a.pkg\n", "c.pkg".into(), &index_ref);
+        Indexer::index_test_content("", "d.pkg".into(), &index_ref);
+        let index = index_ref.get();
+        let file_names = |chain: Option<Vec<&PathBuf>>| {
+            chain.map(|chain| {
+                chain
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            file_names(index.dependency_cycle(&"a.pkg".into(), &"b.pkg".into())),
+            Some(vec![
+                "a.pkg".into(),
+                "b.pkg".into(),
+                "c.pkg".into(),
+                "a.pkg".into()
+            ])
+        );
+        assert_eq!(
+            file_names(index.dependency_cycle(&"b.pkg".into(), &"c".into())),
+            Some(vec!["b.pkg".into(), "c.pkg".into(), "b.pkg".into()])
+        );
+        assert_eq!(
+            index.dependency_cycle(&"b.pkg".into(), &"d.pkg".into()),
+            None
+        );
+        assert_eq!(
+            index.dependency_cycle(&"a.pkg".into(), &"missing.pkg".into()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_class_hierarchy_with_used_mixin_package() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cGreeterMixin is a Mixin\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "greeter_mixin.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Use cOther.pkg\nClass cFoo is a cObject\n    Use Greeter_Mixin.pkg\nEnd_Class\n",
+            "foo.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let class = index
+            .find_class(&"cFoo".into())
+            .and_then(|symbol_ref| index.resolve_symbol(symbol_ref))
+            .unwrap();
+
+        assert_eq!(
+            index
+                .class_hierarchy(class)
+                .map(|c| c.symbol.name().to_string())
+                .collect::<Vec<_>>(),
+            ["cFoo", "cGreeterMixin"]
+        );
+    }
+
+    #[test]
+    fn test_dependent_files() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content("Use B.pkg\n", "a.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use c\n", "b.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use a.pkg\n", "c.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use b.pkg\n", "d.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use e.pkg\n", "e.pkg".into(), &index_ref);
+        let index = index_ref.get();
+        let dependents = |file| {
+            let mut dependents: Vec<String> = index
+                .dependent_files(&IndexFileRef::from(file))
+                .iter()
+                .map(|file_ref| format!("{file_ref:?}"))
+                .collect();
+            dependents.sort();
+            dependents
+        };
+
+        // Direct and indirect users, where the cycle back to the file itself is ignored.
+        assert_eq!(
+            dependents("c.pkg"),
+            [
+                "IndexFileRef(\"a.pkg\")",
+                "IndexFileRef(\"b.pkg\")",
+                "IndexFileRef(\"d.pkg\")"
+            ]
+        );
+        assert!(dependents("d.pkg").is_empty());
+        assert!(dependents("e.pkg").is_empty());
+    }
+
+    #[test]
+    fn test_find_by_path() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cObject\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n\nClass cOtherClass is a cObject\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Object oMain is a cMyClass\n    Procedure SayHello\n    End_Procedure\nEnd_Object\n",
+            "main.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let find = |path: &[&str]| {
+            let path = path
+                .iter()
+                .map(|name| SymbolName::from(*name))
+                .collect::<Vec<_>>()
+                .into();
+            index.find_by_path(&path).map(|s| {
+                format!(
+                    "{} {:?} {:?}",
+                    s.file.path.display(),
+                    s.symbol.symbol_path(),
+                    s.symbol.location()
+                )
+            })
+        };
+
+        assert_eq!(
+            find(&["cMyClass", "SayHello"]).as_deref(),
+            Some(
+                "test.pkg SymbolPath(\"cMyClass.SayHello\") SourceLocation { line: 1, column: 14 }"
+            )
+        );
+        assert_eq!(
+            find(&["cOtherClass", "SayHello"]).as_deref(),
+            Some(
+                "test.pkg SymbolPath(\"cOtherClass.SayHello\") SourceLocation { line: 6, column: 14 }"
+            )
+        );
+        assert_eq!(
+            find(&["oMain", "SayHello"]).as_deref(),
+            Some("main.pkg SymbolPath(\"oMain.SayHello\") SourceLocation { line: 1, column: 14 }")
+        );
+        assert_eq!(find(&["cMyClass", "Missing"]), None);
+        assert_eq!(find(&["cMissing", "SayHello"]), None);
+    }
+
+    #[test]
+    fn test_find_class_from_file() {
+        let index_ref = IndexRef::make_test_index_ref();
+        let class = "Class cFoo is a cObject\nEnd_Class\n";
+        Indexer::index_test_content(class, "a.pkg".into(), &index_ref);
+        Indexer::index_test_content(class, "b.pkg".into(), &index_ref);
+        Indexer::index_test_content(
+            &format!("Use b\n{class}Class cBar is a cFoo\nEnd_Class\n"),
+            "c.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Use b\nClass cBar is a cFoo\nEnd_Class\n",
+            "d.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let file_of = |file: &str| {
+            index
+                .find_class_from_file(&"cFoo".into(), &file.into())
+                .map(|class| format!("{:?}", class.file_ref))
+        };
+
+        assert_eq!(file_of("c.pkg").as_deref(), Some("IndexFileRef(\"c.pkg\")"));
+        assert_eq!(file_of("d.pkg").as_deref(), Some("IndexFileRef(\"b.pkg\")"));
+        assert_eq!(
+            file_of("other.pkg").as_deref(),
+            Some("IndexFileRef(\"a.pkg\")")
+        );
+        assert!(
+            index
+                .find_class_from_file(&"cMissing".into(), &"c.pkg".into())
+                .is_none()
+        );
+
+        // The superclass resolves to the class visible from the subclass.
+        let class = index
+            .resolve_symbol(&IndexSymbolRef::new(
+                "d.pkg".into(),
+                SymbolPath::with_name("cBar"),
+            ))
+            .unwrap();
+        assert_eq!(
+            index
+                .class_hierarchy(class)
+                .map(|c| c.file.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>(),
+            ["d.pkg", "b.pkg"]
+        );
+    }
+
+    #[test]
+    fn test_class_hierarchy_through_indirect_dependencies() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cBase is a cObject\n    Procedure Other\n    End_Procedure\nEnd_Class\n",
+            "other.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Class cBase is a cObject\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "base.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content("Use Base.pkg\n", "mid.pkg".into(), &index_ref);
+        Indexer::index_test_content(
+            "Use mid\nClass cDerived is a cBase\nEnd_Class\n",
+            "derived.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let class = index
+            .resolve_symbol(&IndexSymbolRef::new(
+                "derived.pkg".into(),
+                SymbolPath::with_name("cDerived"),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            index
+                .class_hierarchy(class)
+                .map(|c| c.file.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>(),
+            ["derived.pkg", "base.pkg"]
+        );
+        assert_eq!(
+            index
+                .inherited_class_members(class, MethodKind::Msg)
+                .map(|m| m.symbol.name().to_string())
+                .collect::<Vec<_>>(),
+            ["SayHello"]
+        );
+    }
+
+    #[test]
+    fn test_supertypes_and_subtypes() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cBase is a cObject\nEnd_Class\n",
+            "base.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Use base.pkg\nClass cFirst is a cBase\nEnd_Class\nClass cSecond is a cBase\nEnd_Class\n",
+            "derived.pkg".into(),
+            &index_ref,
+        );
+        // Another class with the same name, in an unrelated file.
+        Indexer::index_test_content(
+            "Class cBase is a cObject\nEnd_Class\nClass cOther is a cBase\nEnd_Class\n",
+            "other.pkg".into(),
+            &index_ref,
+        );
+
+        let class = |file: &str, name: &str| {
+            IndexSymbolRef::new(file.into(), SymbolPath::with_name(name.into()))
+        };
+        let names = |symbols: Vec<QualifiedIndexSymbol>| {
+            symbols
+                .iter()
+                .map(|symbol| symbol.symbol.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        let index = index_ref.get();
+        assert_eq!(
+            names(index.supertypes(&class("derived.pkg", "cFirst"))),
+            ["cBase"]
+        );
+        assert_eq!(
+            index.supertypes(&class("derived.pkg", "cFirst"))[0]
+                .file
+                .path,
+            PathBuf::from("base.pkg")
+        );
+        assert_eq!(
+            names(index.supertypes(&class("base.pkg", "cBase"))),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names(index.subtypes(&class("base.pkg", "cBase"))),
+            ["cFirst", "cSecond"]
+        );
+        assert_eq!(
+            names(index.subtypes(&class("other.pkg", "cBase"))),
+            ["cOther"]
+        );
+        assert_eq!(
+            names(index.subtypes(&class("derived.pkg", "cFirst"))),
+            Vec::<String>::new()
+        );
+        drop(index);
+
+        // Changing the superclass moves the class to the subtypes of the new superclass.
+        Indexer::index_test_content(
+            "Use base.pkg\nClass cFirst is a cBase\nEnd_Class\nClass cSecond is a cFirst\nEnd_Class\n",
+            "derived.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        assert_eq!(
+            names(index.subtypes(&class("base.pkg", "cBase"))),
+            ["cFirst"]
+        );
+        assert_eq!(
+            names(index.subtypes(&class("derived.pkg", "cFirst"))),
+            ["cSecond"]
+        );
+        assert_eq!(
+            names(index.supertypes(&class("derived.pkg", "cSecond"))),
+            ["cFirst"]
+        );
+    }
+
+    #[test]
+    fn test_class_hierarchy_with_cycle() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cA is a cB\n    Procedure SayHello\n    End_Procedure\nEnd_Class\nClass cB is a cA\n    Procedure SayGoodbye\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        let class = index
+            .find_class(&"cA".into())
+            .and_then(|symbol_ref| index.resolve_symbol(symbol_ref))
+            .unwrap();
+
+        assert_eq!(
+            index
+                .class_hierarchy(class)
+                .map(|c| c.symbol.name().to_string())
+                .collect::<Vec<_>>(),
+            ["cA", "cB"]
+        );
+        assert_eq!(
+            index
+                .inherited_class_members(class, MethodKind::Msg)
+                .map(|m| m.symbol.name().to_string())
+                .collect::<Vec<_>>(),
+            ["SayHello", "SayGoodbye"]
+        );
+        assert_eq!(
+            index.inheritance_cycle(&"cA".into()),
+            Some(vec!["cA".into(), "cB".into(), "cA".into()])
+        );
+        assert_eq!(index.inheritance_cycle(&"cUnknown".into()), None);
+    }
+
     #[test]
     fn test_class_hierarchy_with_mixins() {
         let index_ref = IndexRef::make_test_index_ref();
@@ -1059,19 +1831,19 @@ End_Class
         let mut class_hierarchy = index.class_hierarchy(class);
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 12, column: 6 }, range: SourceRange { start: SourceLocation { line: 12, column: 0 }, end: SourceLocation { line: 14, column: 9 } }, symbol_path: SymbolPath(\"cMySubClass\"), superclass: SymbolName(\"cMyBaseClass\"), mixins: [SymbolName(\"cMyOtherMixin\")], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 12, column: 6 }, range: SourceRange { start: SourceLocation { line: 12, column: 0 }, end: SourceLocation { line: 14, column: 9 } }, symbol_path: SymbolPath(\"cMySubClass\"), superclass: SymbolName(\"cMyBaseClass\"), mixins: [SymbolName(\"cMyOtherMixin\")], is_declaration: false, members: [], metadata: [] }) })"
         );
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 4, column: 6 }, range: SourceRange { start: SourceLocation { line: 4, column: 0 }, end: SourceLocation { line: 5, column: 9 } }, symbol_path: SymbolPath(\"cMyOtherMixin\"), superclass: SymbolName(\"cMixin\"), mixins: [], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 4, column: 6 }, range: SourceRange { start: SourceLocation { line: 4, column: 0 }, end: SourceLocation { line: 5, column: 9 } }, symbol_path: SymbolPath(\"cMyOtherMixin\"), superclass: SymbolName(\"cMixin\"), mixins: [], is_declaration: true, members: [], metadata: [] }) })"
         );
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 7, column: 6 }, range: SourceRange { start: SourceLocation { line: 7, column: 0 }, end: SourceLocation { line: 9, column: 9 } }, symbol_path: SymbolPath(\"cMyBaseClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [SymbolName(\"cMyMixin\")], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 7, column: 6 }, range: SourceRange { start: SourceLocation { line: 7, column: 0 }, end: SourceLocation { line: 9, column: 9 } }, symbol_path: SymbolPath(\"cMyBaseClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [SymbolName(\"cMyMixin\")], is_declaration: false, members: [], metadata: [] }) })"
         );
         assert_eq!(
             format!("{:?}", class_hierarchy.next()),
-            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 2, column: 9 } }, symbol_path: SymbolPath(\"cMyMixin\"), superclass: SymbolName(\"cMixin\"), mixins: [], members: [], metadata: [] }) })"
+            "Some(QualifiedIndexSymbol { file.path: \"test.pkg\", symbol: Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 2, column: 9 } }, symbol_path: SymbolPath(\"cMyMixin\"), superclass: SymbolName(\"cMixin\"), mixins: [], is_declaration: true, members: [], metadata: [] }) })"
         );
         assert_eq!(format!("{:?}", class_hierarchy.next()), "None");
     }
@@ -1157,4 +1929,66 @@ End_Class
         );
         assert_eq!(format!("{:?}", tags.next()), "None");
     }
+
+    #[test]
+    fn test_concurrent_indexing_and_lookup() {
+        const FILE_COUNT: usize = 4;
+        const ITERATIONS: usize = 50;
+
+        let index_ref = IndexRef::make_test_index_ref();
+        let content = |i: usize, method: &str| {
+            format!(
+                "Class cStress{i} is a cBaseClass\n    Procedure {method}{i}\n    End_Procedure\nEnd_Class\n"
+            )
+        };
+        for i in 0..FILE_COUNT {
+            Indexer::index_test_content(
+                &content(i, "MethodA"),
+                format!("stress{i}.pkg").into(),
+                &index_ref,
+            );
+        }
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                        let index = index_ref.get();
+                        assert_eq!(
+                            index
+                                .matching_symbols("cStress", SymbolMatchMode::Substring)
+                                .count(),
+                            FILE_COUNT
+                        );
+                        let methods = index.all_known_methods(MethodKind::Msg);
+                        for i in 0..FILE_COUNT {
+                            assert!(index.find_class(&format!("cStress{i}").into()).is_some());
+                            let has_method_a = methods.contains(&format!("MethodA{i}").into());
+                            let has_method_b = methods.contains(&format!("MethodB{i}").into());
+                            assert!(has_method_a != has_method_b);
+                        }
+                    }
+                });
+            }
+
+            rayon::scope(|rayon_scope| {
+                for i in 0..FILE_COUNT {
+                    let content = &content;
+                    let index_ref = &index_ref;
+                    rayon_scope.spawn(move |_| {
+                        for n in 0..ITERATIONS {
+                            let method = if n % 2 == 0 { "MethodB" } else { "MethodA" };
+                            Indexer::index_test_content(
+                                &content(i, method),
+                                format!("stress{i}.pkg").into(),
+                                index_ref,
+                            );
+                        }
+                    });
+                }
+            });
+            done.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+    }
 }
@@ -7,6 +7,13 @@ pub struct IndexFile {
     pub dependencies: Vec<IndexFileRef>,
     pub symbols: Vec<IndexSymbol>,
     pub tables: Option<Box<Vec<DataFlexTable>>>,
+    pub commands: Option<Box<Vec<CommandDefinition>>>,
+    pub class_imports: Option<Box<Vec<ClassImport>>>,
+    /// Object names declared with `Register_Object`, which may be defined elsewhere.
+    pub registered_objects: Option<Box<Vec<SymbolName>>>,
+    /// The modification time of the file on disk when it was indexed, so that files loaded from
+    /// a saved index aren't parsed again unless they changed. `None` for edited buffers.
+    pub modified: Option<std::time::SystemTime>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -19,6 +26,10 @@ impl IndexFile {
             dependencies: Vec::new(),
             symbols: Vec::new(),
             tables: None,
+            commands: None,
+            class_imports: None,
+            registered_objects: None,
+            modified: None,
         }
     }
 
@@ -34,6 +45,26 @@ impl IndexFile {
             None
         }
     }
+
+    /// The class or object declaring the member at the location, searching nested objects.
+    pub fn find_member_container(&self, location: SourceLocation) -> Option<&IndexSymbol> {
+        fn find_container(
+            symbols: &[IndexSymbol],
+            location: SourceLocation,
+        ) -> Option<&IndexSymbol> {
+            symbols.iter().find_map(|symbol| {
+                let class = ClassSymbol::from_index_symbol(symbol)?;
+                find_container(&class.members, location).or_else(|| {
+                    class
+                        .members
+                        .iter()
+                        .any(|member| member.location() == location)
+                        .then_some(symbol)
+                })
+            })
+        }
+        find_container(&self.symbols, location)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,12 +73,81 @@ pub struct DataFlexTable {
     pub columns: Vec<SymbolName>,
 }
 
+/// A `#COMMAND` definition whose expansion sends, gets or sets the message passed as its first
+/// argument, like `#COMMAND Send_Refresh` expanding to `Send !1 ...`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandDefinition {
+    pub name: SymbolName,
+    pub kind: MethodKind,
+}
+
+/// A package `Use`d inside a class body, which brings the classes defined in the package into
+/// the class like mixins.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClassImport {
+    pub class_name: SymbolName,
+    pub file: IndexFileRef,
+}
+
 #[derive(Debug)]
 pub struct QualifiedDataFlexTableRef<'a> {
     pub file: &'a IndexFile,
     pub table: &'a DataFlexTable,
 }
 
+impl CommandDefinition {
+    /// Best-effort scan for `#COMMAND` blocks, since the parser doesn't expand commands. The
+    /// expansion kind is taken from the first statement of the block, and commands that don't
+    /// forward their first argument as a message are skipped.
+    pub fn commands_in_content(content: &[u8]) -> Vec<Self> {
+        let content = String::from_utf8_lossy(content);
+        let mut commands = Vec::new();
+        let mut lines = content.lines().map(str::trim);
+        while let Some(line) = lines.next() {
+            let mut words = line.split_whitespace();
+            if !words
+                .next()
+                .is_some_and(|w| w.eq_ignore_ascii_case("#command"))
+            {
+                continue;
+            }
+            let Some(name) = words.next() else {
+                continue;
+            };
+            let Some(body) = lines
+                .by_ref()
+                .find(|line| !line.is_empty() && !line.starts_with("//"))
+            else {
+                break;
+            };
+            let mut words = body.split_whitespace();
+            let kind = match words.next().map(str::to_lowercase).as_deref() {
+                Some("send") => MethodKind::Msg,
+                Some("get") => MethodKind::Get,
+                Some("set") => MethodKind::Set,
+                _ => continue,
+            };
+            if words.next() == Some("!1") {
+                commands.push(Self {
+                    name: name.into(),
+                    kind,
+                });
+            }
+        }
+        commands
+    }
+}
+
+impl IndexFileRef {
+    /// The conventional `.pkg` file for a reference without an extension, as in `Use cWebView`.
+    pub fn with_default_extension(&self) -> Option<Self> {
+        let path = std::path::Path::new(&self.0);
+        path.extension()
+            .is_none()
+            .then(|| Self(path.with_extension("pkg").into_os_string()))
+    }
+}
+
 impl From<&PathBuf> for IndexFileRef {
     fn from(value: &PathBuf) -> Self {
         Self(value.file_name().unwrap_or_default().into())
@@ -20,6 +20,7 @@ pub struct ClassSymbol {
     pub symbol_path: SymbolPath,
     pub superclass: SymbolName,
     pub mixins: Vec<SymbolName>,
+    pub is_declaration: bool,
     pub members: Vec<IndexSymbol>,
     pub metadata: Vec<MetadataTagSet>,
 }
@@ -40,9 +41,16 @@ pub struct MethodSymbol {
     pub kind: MethodKind,
     pub parameters: Vec<(SymbolName, DataFlexDataType)>,
     pub return_type: Option<DataFlexDataType>,
+    pub external: Option<ExternalBinding>,
     pub metadata: Vec<MetadataTagSet>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExternalBinding {
+    pub entry: String,
+    pub library: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct VariableSymbol {
     pub location: SourceLocation,
@@ -79,7 +87,7 @@ pub enum MethodKind {
     Set,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum DataFlexDataType {
     Simple(SymbolName),
     Array(SymbolName, usize),
@@ -97,6 +105,13 @@ pub struct SymbolName(String);
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SymbolPath(Vec<SymbolName>);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SymbolMatchMode {
+    Substring,
+    Fuzzy,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MetadataTagSet {
     pub tags: Vec<MetadataTag>,
@@ -114,7 +129,7 @@ pub struct QualifiedIndexSymbol<'a> {
     pub symbol: &'a IndexSymbol,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IndexSymbolRef {
     pub file_ref: IndexFileRef,
     pub symbol_path: SymbolPath,
@@ -285,6 +300,46 @@ impl SymbolName {
     pub fn starts_with(&self, pat: &str) -> bool {
         self.0.starts_with(pat)
     }
+
+    /// Case-insensitive match against the pattern, returning a score where a higher score is a
+    /// better match, or `None` if the name doesn't match.
+    pub fn match_score(&self, pattern: &str, mode: SymbolMatchMode) -> Option<usize> {
+        match mode {
+            SymbolMatchMode::Substring => self
+                .0
+                .to_lowercase()
+                .find(&pattern.to_lowercase())
+                .map(|position| if position == 0 { 2 } else { 1 }),
+            SymbolMatchMode::Fuzzy => self.fuzzy_match_score(pattern),
+        }
+    }
+
+    /// Matches the pattern as a subsequence of the name, so that e.g. `cWB` matches `cWebButton`.
+    /// Uppercase pattern characters only match at the start of a CamelCase or underscore
+    /// separated word. Word starts and consecutive matched characters score higher.
+    fn fuzzy_match_score(&self, pattern: &str) -> Option<usize> {
+        let mut pattern_chars = pattern.chars().peekable();
+        let mut score = 0;
+        let mut previous: Option<char> = None;
+        let mut previous_matched = false;
+        for c in self.0.chars() {
+            let Some(&p) = pattern_chars.peek() else {
+                break;
+            };
+            let word_start = previous.is_none_or(|previous| {
+                !previous.is_alphanumeric() || (previous.is_lowercase() && c.is_uppercase())
+            });
+            let matched =
+                c.to_lowercase().eq(p.to_lowercase()) && (word_start || !p.is_uppercase());
+            if matched {
+                score += 1 + if word_start { 2 } else { 0 } + usize::from(previous_matched);
+                pattern_chars.next();
+            }
+            previous_matched = matched;
+            previous = Some(c);
+        }
+        pattern_chars.peek().is_none().then_some(score)
+    }
 }
 
 impl PartialEq for SymbolName {
@@ -484,6 +539,16 @@ impl IndexSymbolType for StructSymbol {
     }
 }
 
+impl MethodSymbol {
+    /// Whether the methods take the same parameters and return the same type. Unlike
+    /// `IndexSymbol::is_matching()`, this compares more than the name.
+    pub fn has_same_signature(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.parameters == other.parameters
+            && self.return_type == other.return_type
+    }
+}
+
 impl IndexSymbolType for MethodSymbol {
     fn from_index_symbol(index_symbol: &IndexSymbol) -> Option<&Self> {
         if let IndexSymbol::Method(method_symbol) = index_symbol {
@@ -552,16 +617,26 @@ impl std::fmt::Display for IndexSymbol {
                 writeln!(f, "End_Struct")
             }
             Self::Method(method_symbol) => {
-                write!(
-                    f,
-                    "{} {}",
-                    match method_symbol.kind {
-                        MethodKind::Msg => "Procedure",
-                        MethodKind::Get => "Function",
-                        MethodKind::Set => "Procedure Set",
-                    },
-                    method_symbol.symbol_path.name()
-                )?;
+                if let Some(external) = &method_symbol.external {
+                    write!(
+                        f,
+                        "External_Function {} \"{}\" {}",
+                        method_symbol.symbol_path.name(),
+                        external.entry,
+                        external.library
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "{} {}",
+                        match method_symbol.kind {
+                            MethodKind::Msg => "Procedure",
+                            MethodKind::Get => "Function",
+                            MethodKind::Set => "Procedure Set",
+                        },
+                        method_symbol.symbol_path.name()
+                    )?;
+                }
                 for (name, data_type) in &method_symbol.parameters {
                     write!(f, " {} {}", data_type, name)?;
                 }
@@ -4,6 +4,7 @@ use std::sync::{OnceLock, mpsc};
 use crate::dataflex_parser::DataFlexTreeParser;
 
 use super::*;
+use int_file::IntFile;
 use symbols_diff::SymbolsDiff;
 
 pub struct Indexer {
@@ -17,6 +18,9 @@ pub struct Indexer {
 pub struct IndexerConfig {
     versioned_system_paths: HashMap<DataFlexVersion, Vec<PathBuf>>,
     default_version: DataFlexVersion,
+    max_directory_depth: usize,
+    index_cache: bool,
+    file_extensions: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -36,11 +40,68 @@ enum IndexerMessage {
     StopIndexing,
 }
 
+/// The phases of the initial indexing, whose files are counted separately.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IndexingPhase {
+    SystemPaths,
+    Workspace,
+}
+
+/// Progress of an initial indexing phase, based on the indexable files of all phases found before
+/// the first phase starts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IndexingProgress {
+    pub phase: IndexingPhase,
+    pub indexed_file_count: usize,
+    pub total_file_count: usize,
+    /// The files indexed by the earlier phases.
+    pub earlier_file_count: usize,
+    /// The files found for the later phases.
+    pub later_file_count: usize,
+    pub done: bool,
+}
+
+impl IndexingProgress {
+    /// The percentage over all phases, so it keeps growing from one phase to the next. Files can
+    /// be added while indexing, so the percentage stays below 100 until the last phase is done.
+    pub fn percentage(&self) -> u32 {
+        let (indexed_file_count, total_file_count) = if self.done {
+            let file_count = self.indexed_file_count.max(self.total_file_count);
+            (file_count, file_count)
+        } else {
+            (self.indexed_file_count, self.total_file_count)
+        };
+        let indexed_file_count = self.earlier_file_count + indexed_file_count;
+        let total_file_count = self.earlier_file_count + total_file_count + self.later_file_count;
+        if self.done && self.later_file_count == 0 {
+            100
+        } else if total_file_count == 0 {
+            0
+        } else {
+            (indexed_file_count * 100 / total_file_count).min(99) as u32
+        }
+    }
+}
+
 pub trait IndexerObserver {
     fn state_transition(&self, old_state: IndexerState, new_state: IndexerState);
+
+    /// Called on the indexer thread when a file is removed from the index, so this should not block.
+    fn file_removed(&self, _path: &PathBuf) {}
+
+    /// Called on the indexer thread when the buffer of an edited file has been indexed, so this
+    /// should not block.
+    fn file_buffer_indexed(&self, _path: &PathBuf) {}
+
+    /// Called when the workspace root folder doesn't exist on disk, only system paths are indexed.
+    fn workspace_root_missing(&self, _path: &PathBuf) {}
 }
 
-const CURRENT_SERIALIZED_VERSION: usize = 2;
+const CURRENT_SERIALIZED_VERSION: usize = 8;
+
+/// How long to wait for more modified files before re-indexing, so that a burst of file system
+/// notifications, like saving all files or switching branches, is indexed in a single pass.
+const MODIFIED_FILES_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
 
 #[derive(Deserialize)]
 struct DeserializedIndex {
@@ -70,6 +131,9 @@ impl Indexer {
     }
 
     pub fn load_index(&self) -> bool {
+        if !self.config.index_cache {
+            return false;
+        }
         if self.channel.get().is_some() {
             log::error!(
                 "Indexer::load_index() cannot be called after indexer is started with Indexer::start_indexing()"
@@ -96,6 +160,9 @@ impl Indexer {
     }
 
     pub fn save_index(&self) {
+        if !self.config.index_cache {
+            return;
+        }
         let index = self.index.get();
 
         let content = rmp_serde::to_vec(&SerializableIndex {
@@ -127,18 +194,23 @@ impl Indexer {
             .config
             .system_path(self.dataflex_version.as_ref())
             .cloned();
+        let max_directory_depth = self.config.max_directory_depth;
+        let file_extensions = self.config.file_extensions.clone();
         rayon::spawn(move || {
-            observer.state_transition(IndexerState::Initializing, IndexerState::InitialIndexing);
-            if let Some(system_paths) = system_paths {
-                log::info!("Indexing system paths");
-                Self::index_system_paths(&system_paths, &index);
-            }
-            log::info!("Indexing workspace");
-            Self::index_workspace(&index);
-            log::info!("Finished indexing: {} files", index.get().files.len());
-            log::trace!("{:#?}", index.get());
-            observer.state_transition(IndexerState::InitialIndexing, IndexerState::Inactive);
-            Self::watch_and_index_changed_files(&index, receiver, &observer);
+            Self::initial_indexing(
+                &index,
+                system_paths.as_ref(),
+                max_directory_depth,
+                &file_extensions,
+                &observer,
+            );
+            Self::watch_and_index_changed_files(
+                &index,
+                receiver,
+                max_directory_depth,
+                &file_extensions,
+                &observer,
+            );
             observer.state_transition(IndexerState::Inactive, IndexerState::Stopped);
             log::info!("Indexer exiting");
         });
@@ -179,6 +251,48 @@ impl Indexer {
         _ = channel.send(IndexerMessage::IndexModifiedFiles(paths));
     }
 
+    /// Indexes `Use` dependencies that aren't indexed yet, like files in a directory that wasn't
+    /// indexed, on the calling thread, along with the files they use in turn. Returns whether any
+    /// file was indexed. Dependencies that aren't found on disk are remembered, so that they
+    /// aren't searched for again until they're indexed some other way. This blocks while
+    /// searching the directories and reading the files, but the index isn't locked meanwhile.
+    pub fn index_missing_dependencies(&self, files: &[IndexFileRef]) -> bool {
+        let search_paths = self.index.get().search_paths();
+        let mut pending = files.to_vec();
+        let mut indexed = false;
+        while let Some(file) = pending.pop() {
+            {
+                let index = self.index.get();
+                if index.find_file(&file).is_some() || index.missing_files.contains(&file) {
+                    continue;
+                }
+            }
+            let content = Self::find_file_in_directories(
+                &search_paths,
+                &file,
+                self.config.max_directory_depth,
+            )
+            .and_then(|path| Some((std::fs::read(&path).ok()?, path)));
+            let Some((content, path)) = content else {
+                log::info!("Dependency {:?} not found", file);
+                self.index.get_mut().missing_files.insert(file);
+                continue;
+            };
+            log::info!("Indexing missing dependency {:?}", path);
+            let modified = path.metadata().and_then(|m| m.modified()).ok();
+            Self::index_file_content(&content, path, modified, &self.index);
+            let index = self.index.get();
+            if let Some(index_file) = index.find_file(&file) {
+                indexed = true;
+                pending.extend(index_file.dependencies.iter().cloned());
+            } else {
+                drop(index);
+                self.index.get_mut().missing_files.insert(file);
+            }
+        }
+        indexed
+    }
+
     pub fn remove_indexed_files(&self, paths: Vec<PathBuf>) {
         let Some(channel) = self.channel.get() else {
             log::error!(
@@ -193,80 +307,349 @@ impl Indexer {
         self.index.get().updated_file_count
     }
 
-    fn index_system_paths(paths: &Vec<PathBuf>, index: &IndexRef) {
+    pub fn indexing_progress(&self) -> Option<IndexingProgress> {
+        self.index.get().indexing_progress
+    }
+
+    fn initial_indexing(
+        index: &IndexRef,
+        system_paths: Option<&Vec<PathBuf>>,
+        max_directory_depth: usize,
+        file_extensions: &[String],
+        observer: &impl IndexerObserver,
+    ) {
+        observer.state_transition(IndexerState::Initializing, IndexerState::InitialIndexing);
+        let system_paths: Option<Vec<PathBuf>> = system_paths.map(|system_paths| {
+            system_paths
+                .iter()
+                .filter(|path| path.is_absolute())
+                .cloned()
+                .collect()
+        });
+        let root_folder = index.get().workspace.get_root_folder().clone();
+        let workspace_paths = (root_folder.as_os_str().is_empty() || root_folder.is_dir())
+            .then(|| Self::workspace_paths(index));
+
+        // Count the files of both phases up front, so the progress spans the whole indexing.
+        let file_count = |paths: &Option<Vec<PathBuf>>| {
+            paths.as_ref().map_or(0, |paths| {
+                Self::indexable_file_count(paths, max_directory_depth, file_extensions)
+            })
+        };
+        let system_file_count = file_count(&system_paths);
+        let workspace_file_count = file_count(&workspace_paths);
+
+        if let Some(system_paths) = system_paths {
+            log::info!("Indexing system paths");
+            index.get_mut().set_system_paths(system_paths.clone());
+            index.get_mut().start_indexing_phase(
+                IndexingPhase::SystemPaths,
+                system_file_count,
+                workspace_file_count,
+            );
+            Self::index_system_paths(&system_paths, max_directory_depth, file_extensions, index);
+            index.get_mut().finish_indexing_phase();
+        }
+        if let Some(workspace_paths) = workspace_paths {
+            log::info!("Indexing workspace");
+            index
+                .get_mut()
+                .start_indexing_phase(IndexingPhase::Workspace, workspace_file_count, 0);
+            Self::index_workspace(
+                &workspace_paths,
+                max_directory_depth,
+                file_extensions,
+                index,
+            );
+            index.get_mut().finish_indexing_phase();
+        } else {
+            log::warn!(
+                "Workspace root {:?} does not exist, skipping workspace indexing",
+                root_folder
+            );
+            observer.workspace_root_missing(&root_folder);
+        }
+        log::info!("Finished indexing: {} files", index.get().files.len());
+        log::trace!("{:#?}", index.get());
+        observer.state_transition(IndexerState::InitialIndexing, IndexerState::Inactive);
+    }
+
+    fn index_system_paths(
+        paths: &Vec<PathBuf>,
+        max_directory_depth: usize,
+        file_extensions: &[String],
+        index: &IndexRef,
+    ) {
         rayon::in_place_scope(|scope| {
-            for path in paths {
-                if path.is_absolute() {
-                    log::trace!("Indexing {:?}", path);
-                    Self::index_directory(path, index, scope);
-                }
-            }
+            Self::index_directories(paths, max_directory_depth, file_extensions, index, scope);
         });
     }
 
-    fn index_workspace(index: &IndexRef) {
-        let root_folder = index.get().workspace.get_root_folder().clone();
-        let local_dependencies: Vec<PathBuf> = index
-            .get()
-            .workspace
-            .local_workspace_dependencies()
-            .into_iter()
-            .map(|ws| ws.get_root_folder().clone())
-            .collect();
+    /// The workspace root folder followed by the root folders of local workspace dependencies.
+    fn workspace_paths(index: &IndexRef) -> Vec<PathBuf> {
+        let index = index.get();
+        std::iter::once(index.workspace.get_root_folder().clone())
+            .chain(
+                index
+                    .workspace
+                    .local_workspace_dependencies()
+                    .into_iter()
+                    .map(|ws| ws.get_root_folder().clone()),
+            )
+            .collect()
+    }
+
+    fn index_workspace(
+        paths: &Vec<PathBuf>,
+        max_directory_depth: usize,
+        file_extensions: &[String],
+        index: &IndexRef,
+    ) {
         rayon::in_place_scope(|scope| {
-            Self::index_directory(&root_folder, index, scope);
-            for path in local_dependencies {
-                Self::index_directory(&path, index, scope);
-            }
+            Self::index_directories(paths, max_directory_depth, file_extensions, index, scope);
         });
     }
 
-    fn index_directory<'a>(path: &PathBuf, index: &'a IndexRef, scope: &rayon::Scope<'a>) {
+    /// Counts the files that indexing the directories would index, without reading them.
+    fn indexable_file_count(
+        paths: &[PathBuf],
+        max_directory_depth: usize,
+        file_extensions: &[String],
+    ) -> usize {
+        let mut visited = HashMap::new();
+        paths
+            .iter()
+            .map(|path| {
+                Self::indexable_file_count_once(
+                    path,
+                    max_directory_depth,
+                    file_extensions,
+                    &mut visited,
+                )
+            })
+            .sum()
+    }
+
+    fn indexable_file_count_once(
+        path: &PathBuf,
+        remaining_depth: usize,
+        file_extensions: &[String],
+        visited: &mut HashMap<PathBuf, usize>,
+    ) -> usize {
+        let Some(first_visit) = Self::visit_directory(path, remaining_depth, visited) else {
+            return 0;
+        };
+        let Some(path_entries) = path.read_dir().ok() else {
+            return 0;
+        };
+        path_entries
+            .filter_map(|p| Some(p.ok()?.path()))
+            .map(|path| {
+                if path.is_dir() {
+                    remaining_depth.checked_sub(1).map_or(0, |depth| {
+                        Self::indexable_file_count_once(&path, depth, file_extensions, visited)
+                    })
+                } else {
+                    usize::from(
+                        first_visit
+                            && Self::is_indexable_file(&path, file_extensions)
+                            && path.is_file(),
+                    )
+                }
+            })
+            .sum()
+    }
+
+    /// Records a visit of the directory with the remaining depth. Returns `None` if the directory
+    /// was already visited with at least that depth, since symlinks can form cycles and roots can
+    /// overlap, and otherwise whether this is the first visit, as a directory first reached
+    /// through a deeper path is visited again for its subdirectories.
+    fn visit_directory(
+        path: &PathBuf,
+        remaining_depth: usize,
+        visited: &mut HashMap<PathBuf, usize>,
+    ) -> Option<bool> {
+        let canonical_path = path.canonicalize().ok()?;
+        let visited_depth = visited.get(&canonical_path).copied();
+        if visited_depth.is_some_and(|depth| depth >= remaining_depth) {
+            return None;
+        }
+        visited.insert(canonical_path, remaining_depth);
+        Some(visited_depth.is_none())
+    }
+
+    fn index_directory<'a>(
+        path: &PathBuf,
+        max_directory_depth: usize,
+        file_extensions: &[String],
+        index: &'a IndexRef,
+        scope: &rayon::Scope<'a>,
+    ) {
+        Self::index_directories(
+            std::slice::from_ref(path),
+            max_directory_depth,
+            file_extensions,
+            index,
+            scope,
+        );
+    }
+
+    /// Indexes the directories, sharing the visited directories, so that files in directories
+    /// reached through more than one of them are indexed once.
+    fn index_directories<'a>(
+        paths: &[PathBuf],
+        max_directory_depth: usize,
+        file_extensions: &[String],
+        index: &'a IndexRef,
+        scope: &rayon::Scope<'a>,
+    ) {
+        let mut visited = HashMap::new();
+        for path in paths {
+            log::trace!("Indexing {:?}", path);
+            Self::index_directory_once(
+                path,
+                max_directory_depth,
+                file_extensions,
+                index,
+                scope,
+                &mut visited,
+            );
+        }
+    }
+
+    /// Recursively indexes the directory, skipping directories that were already visited with at
+    /// least the same depth, and directories nested deeper than `remaining_depth` levels below it.
+    fn index_directory_once<'a>(
+        path: &PathBuf,
+        remaining_depth: usize,
+        file_extensions: &[String],
+        index: &'a IndexRef,
+        scope: &rayon::Scope<'a>,
+        visited: &mut HashMap<PathBuf, usize>,
+    ) {
+        let Some(first_visit) = Self::visit_directory(path, remaining_depth, visited) else {
+            log::info!("Skipping already visited directory {:?}", path);
+            return;
+        };
         let Some(path_entries) = path.read_dir().ok() else {
             return;
         };
         for path in path_entries.filter_map(|p| Some(p.ok()?.path())) {
             if path.is_dir() {
-                Self::index_directory(&path, index, scope);
-            } else if Self::should_index_file(&path) {
+                if let Some(depth) = remaining_depth.checked_sub(1) {
+                    Self::index_directory_once(
+                        &path,
+                        depth,
+                        file_extensions,
+                        index,
+                        scope,
+                        visited,
+                    );
+                } else {
+                    log::warn!("Skipping directory {:?}, exceeds max directory depth", path);
+                }
+            } else if first_visit && Self::is_indexable_file(&path, file_extensions) {
                 Self::index_file(path, index, scope);
             }
         }
     }
 
+    /// Searches the directories on disk for the file, for files that aren't indexed yet. Files
+    /// in a directory are checked before its subdirectories.
+    fn find_file_in_directories(
+        paths: &[PathBuf],
+        file: &IndexFileRef,
+        max_directory_depth: usize,
+    ) -> Option<PathBuf> {
+        let mut visited = HashSet::new();
+        paths.iter().find_map(|path| {
+            Self::find_file_in_directory_once(path, file, max_directory_depth, &mut visited)
+        })
+    }
+
+    fn find_file_in_directory_once(
+        path: &PathBuf,
+        file: &IndexFileRef,
+        remaining_depth: usize,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Option<PathBuf> {
+        if !visited.insert(path.canonicalize().ok()?) {
+            return None;
+        }
+        let (dirs, files): (Vec<PathBuf>, Vec<PathBuf>) = path
+            .read_dir()
+            .ok()?
+            .filter_map(|p| Some(p.ok()?.path()))
+            .partition(|p| p.is_dir());
+        let default_file = file.with_default_extension();
+        files
+            .into_iter()
+            .find(|p| {
+                let file_ref = IndexFileRef::from(p);
+                file_ref == *file || default_file.as_ref() == Some(&file_ref)
+            })
+            .or_else(|| {
+                let depth = remaining_depth.checked_sub(1)?;
+                dirs.iter()
+                    .find_map(|dir| Self::find_file_in_directory_once(dir, file, depth, visited))
+            })
+    }
+
+    /// Indexes the file unless the index already has it from the same path and modification
+    /// time, like when it was loaded from a saved index.
     fn index_file<'a>(path: PathBuf, index: &'a IndexRef, scope: &rayon::Scope<'a>) {
         if !path.is_file() || !path.exists() {
             return;
         }
+        let modified = path.metadata().and_then(|m| m.modified()).ok();
+        if modified.is_some() && index.get().is_file_unchanged(&path, modified) {
+            log::trace!("Skipping unchanged file {:?}", path);
+            index.get_mut().skip_unchanged_file();
+            return;
+        }
         let Some(content) = std::fs::read(&path).ok() else {
             return;
         };
         scope.spawn(move |_| {
-            Self::index_file_content(&content, path, index);
+            Self::index_file_content(&content, path, modified, index);
         });
     }
 
-    fn index_file_content(content: &[u8], path: PathBuf, index: &IndexRef) {
+    fn index_file_content(
+        content: &[u8],
+        path: PathBuf,
+        modified: Option<std::time::SystemTime>,
+        index: &IndexRef,
+    ) {
         log::trace!("Indexing file content for {:?}", path);
-        let mut parser = DataFlexTreeParser::new();
-
-        let Some(tree) = parser.parse(content, None) else {
-            return;
+        let mut index_file = if IntFile::is_int_file(&path) {
+            IntFile::index_content(content, path)
+        } else {
+            let mut parser = DataFlexTreeParser::new();
+            let Some(tree) = parser.parse(content, None) else {
+                return;
+            };
+            Self::index_parse_tree(&tree, content, path)
         };
-
-        let index_file = Self::index_parse_tree(&tree, content, path);
+        index_file.modified = modified;
         index.get_mut().update_file(index_file);
     }
 
     fn index_parse_tree(tree: &tree_sitter::Tree, content: &[u8], path: PathBuf) -> IndexFile {
         log::trace!("Indexing file parse tree for {:?}", path);
 
-        let query = tree_sitter::Query::new(
-            &tree_sitter_dataflex::LANGUAGE.into(),
-            Self::indexer_query(),
-        )
-        .expect("Error loading indexer query");
+        let Some(query) = Self::indexer_query() else {
+            return IndexFile::new(path);
+        };
+        Self::index_parse_tree_with_query(tree, content, path, query)
+    }
 
+    fn index_parse_tree_with_query(
+        tree: &tree_sitter::Tree,
+        content: &[u8],
+        path: PathBuf,
+        query: &tree_sitter::Query,
+    ) -> IndexFile {
         let pattern_index_element_map: Vec<Option<TagsQueryIndexElement>> =
             (0..query.pattern_count())
                 .map(|pattern_index| {
@@ -278,20 +661,41 @@ impl Indexer {
                     })
                 })
                 .collect();
-        let name_capture_index = query.capture_index_for_name("name").unwrap();
-        let superclass_capture_index = query.capture_index_for_name("superclass").unwrap();
-        let type_capture_index = query.capture_index_for_name("type").unwrap();
-        let array_capture_index = query.capture_index_for_name("array").unwrap();
-        let value_ref_capture_index = query.capture_index_for_name("value_reference").unwrap();
-        let name_ref_capture_index = query.capture_index_for_name("name_reference").unwrap();
-        let arg_ref_capture_index = query.capture_index_for_name("arg_reference").unwrap();
-        let parameter_capture_index = query.capture_index_for_name("parameter").unwrap();
-        let return_type_capture_index = query.capture_index_for_name("return_type").unwrap();
-        let element_node_capture_index = query.capture_index_for_name("element_node").unwrap();
+        let [
+            Some(name_capture_index),
+            Some(superclass_capture_index),
+            Some(type_capture_index),
+            Some(array_capture_index),
+            Some(value_ref_capture_index),
+            Some(name_ref_capture_index),
+            Some(arg_ref_capture_index),
+            Some(parameter_capture_index),
+            Some(return_type_capture_index),
+            Some(element_node_capture_index),
+        ] = [
+            "name",
+            "superclass",
+            "type",
+            "array",
+            "value_reference",
+            "name_reference",
+            "arg_reference",
+            "parameter",
+            "return_type",
+            "element_node",
+        ]
+        .map(|name| query.capture_index_for_name(name))
+        else {
+            log::error!(
+                "Indexer query is missing expected captures, skipping {:?}",
+                path
+            );
+            return IndexFile::new(path);
+        };
         let mut query_cursor = tree_sitter::QueryCursor::new();
-        let matches = query_cursor.matches(&query, tree.root_node(), content);
+        let matches = query_cursor.matches(query, tree.root_node(), content);
 
-        let (index_file, _) = matches.fold(
+        let (mut index_file, _) = matches.fold(
             (IndexFile::new(path), Vec::<IndexSymbol>::new()),
             |(mut index_file, mut stack), query_match| {
                 let element_node = query_match
@@ -309,6 +713,14 @@ impl Indexer {
                             index_file
                                 .dependencies
                                 .push(IndexFileRef::from(file_dependency));
+                            if let Some(IndexSymbol::Class(class_symbol)) = stack.last() {
+                                index_file.class_imports.get_or_insert_default().push(
+                                    ClassImport {
+                                        class_name: class_symbol.symbol_path.name().clone(),
+                                        file: IndexFileRef::from(file_dependency),
+                                    },
+                                );
+                            }
                         }
                     }
                     Some(TagsQueryIndexElement::ClassDefinition) => {
@@ -328,6 +740,8 @@ impl Indexer {
                                 symbol_path: SymbolPath::with_name(name),
                                 superclass: superclass.into(),
                                 mixins: Vec::new(),
+                                is_declaration: element_node
+                                    .is_some_and(|node| Self::is_class_declaration(&node)),
                                 members: Vec::new(),
                                 metadata: element_node
                                     .as_ref()
@@ -364,6 +778,7 @@ impl Indexer {
                                     .unwrap_or_else(|| SymbolPath::with_name(name)),
                                 superclass: superclass.into(),
                                 mixins: Vec::new(),
+                                is_declaration: false,
                                 members: Vec::new(),
                                 metadata: Vec::new(),
                             };
@@ -422,14 +837,25 @@ impl Indexer {
                                 .push(IndexSymbol::Variable(variable_symbol));
                         }
                     }
-                    Some(TagsQueryIndexElement::MethodProcedureDefinition) => {
+                    Some(
+                        TagsQueryIndexElement::MethodProcedureDefinition
+                        | TagsQueryIndexElement::GlobalProcedureDefinition,
+                    ) => {
+                        // Global methods are defined at the top level and have no enclosing class.
+                        let class_symbol = match pattern_index_element_map
+                            [query_match.pattern_index]
+                        {
+                            Some(TagsQueryIndexElement::GlobalProcedureDefinition) => Some(None),
+                            _ => stack
+                                .last_mut()
+                                .and_then(ClassSymbol::from_index_symbol_mut)
+                                .map(Some),
+                        };
                         if let Some(name_node) = query_match
                             .nodes_for_capture_index(name_capture_index)
                             .next()
                             && let Some(name) = name_node.utf8_text(content).ok()
-                            && let Some(class_symbol) = stack
-                                .last_mut()
-                                .and_then(ClassSymbol::from_index_symbol_mut)
+                            && let Some(class_symbol) = class_symbol
                         {
                             let parameters = query_match
                                 .nodes_for_capture_index(parameter_capture_index)
@@ -458,13 +884,19 @@ impl Indexer {
                             let method_symbol = MethodSymbol {
                                 location: name_node.start_position().into(),
                                 range: element_range.unwrap_or_else(|| name_node.range().into()),
-                                symbol_path: SymbolPath::with_parent_and_name(
-                                    &class_symbol.symbol_path,
-                                    name,
-                                ),
+                                symbol_path: class_symbol
+                                    .as_ref()
+                                    .map(|class_symbol| {
+                                        SymbolPath::with_parent_and_name(
+                                            &class_symbol.symbol_path,
+                                            name,
+                                        )
+                                    })
+                                    .unwrap_or_else(|| SymbolPath::with_name(name)),
                                 kind: method_kind,
                                 parameters: parameters,
                                 return_type: None,
+                                external: None,
                                 metadata: element_node
                                     .as_ref()
                                     .and_then(|symbol_node| {
@@ -475,19 +907,33 @@ impl Indexer {
                                     })
                                     .unwrap_or_default(),
                             };
-                            class_symbol
-                                .members
-                                .push(IndexSymbol::Method(method_symbol));
+                            if let Some(class_symbol) = class_symbol {
+                                class_symbol
+                                    .members
+                                    .push(IndexSymbol::Method(method_symbol));
+                            } else {
+                                index_file.symbols.push(IndexSymbol::Method(method_symbol));
+                            }
                         }
                     }
-                    Some(TagsQueryIndexElement::MethodFunctionDefinition) => {
+                    Some(
+                        TagsQueryIndexElement::MethodFunctionDefinition
+                        | TagsQueryIndexElement::GlobalFunctionDefinition,
+                    ) => {
+                        // Global methods are defined at the top level and have no enclosing class.
+                        let class_symbol =
+                            match pattern_index_element_map[query_match.pattern_index] {
+                                Some(TagsQueryIndexElement::GlobalFunctionDefinition) => Some(None),
+                                _ => stack
+                                    .last_mut()
+                                    .and_then(ClassSymbol::from_index_symbol_mut)
+                                    .map(Some),
+                            };
                         if let Some(name_node) = query_match
                             .nodes_for_capture_index(name_capture_index)
                             .next()
                             && let Some(name) = name_node.utf8_text(content).ok()
-                            && let Some(class_symbol) = stack
-                                .last_mut()
-                                .and_then(ClassSymbol::from_index_symbol_mut)
+                            && let Some(class_symbol) = class_symbol
                         {
                             let parameters = query_match
                                 .nodes_for_capture_index(parameter_capture_index)
@@ -513,13 +959,19 @@ impl Indexer {
                             let method_symbol = MethodSymbol {
                                 location: name_node.start_position().into(),
                                 range: element_range.unwrap_or_else(|| name_node.range().into()),
-                                symbol_path: SymbolPath::with_parent_and_name(
-                                    &class_symbol.symbol_path,
-                                    name,
-                                ),
+                                symbol_path: class_symbol
+                                    .as_ref()
+                                    .map(|class_symbol| {
+                                        SymbolPath::with_parent_and_name(
+                                            &class_symbol.symbol_path,
+                                            name,
+                                        )
+                                    })
+                                    .unwrap_or_else(|| SymbolPath::with_name(name)),
                                 kind: MethodKind::Get,
                                 parameters: parameters,
                                 return_type: return_type,
+                                external: None,
                                 metadata: element_node
                                     .as_ref()
                                     .and_then(|symbol_node| {
@@ -530,9 +982,13 @@ impl Indexer {
                                     })
                                     .unwrap_or_default(),
                             };
-                            class_symbol
-                                .members
-                                .push(IndexSymbol::Method(method_symbol));
+                            if let Some(class_symbol) = class_symbol {
+                                class_symbol
+                                    .members
+                                    .push(IndexSymbol::Method(method_symbol));
+                            } else {
+                                index_file.symbols.push(IndexSymbol::Method(method_symbol));
+                            }
                         }
                     }
                     Some(TagsQueryIndexElement::PropertyDefinition) => {
@@ -685,6 +1141,26 @@ impl Indexer {
                             class_symbol.mixins.push(name.into());
                         }
                     }
+                    Some(TagsQueryIndexElement::ObjectRegistration) => {
+                        if let Some(name) = query_match
+                            .nodes_for_capture_index(name_capture_index)
+                            .next()
+                            .and_then(|node| node.utf8_text(content).ok())
+                        {
+                            index_file
+                                .registered_objects
+                                .get_or_insert_default()
+                                .push(name.into());
+                        }
+                    }
+                    Some(TagsQueryIndexElement::ExternalFunctionDeclaration) => {
+                        if let Some(element_node) = element_node
+                            && let Some(method_symbol) =
+                                MethodSymbol::with_external_function_node(element_node, content)
+                        {
+                            index_file.symbols.push(IndexSymbol::Method(method_symbol));
+                        }
+                    }
                     Some(TagsQueryIndexElement::PopStackSymbol) => {
                         if let Some(symbol) = stack.pop() {
                             match stack.last_mut() {
@@ -710,29 +1186,66 @@ impl Indexer {
                 (index_file, stack)
             },
         );
+        let commands = CommandDefinition::commands_in_content(content);
+        if !commands.is_empty() {
+            index_file.commands = Some(Box::new(commands));
+        }
         index_file
     }
 
+    /// A forward class declaration has nothing but its header and footer. It makes the class known
+    /// before the file with the full definition is used.
+    fn is_class_declaration(node: &tree_sitter::Node) -> bool {
+        let mut cursor = node.walk();
+        node.named_children(&mut cursor).all(|child| {
+            child.is_extra() || matches!(child.kind(), "class_header" | "class_footer")
+        })
+    }
+
     fn watch_and_index_changed_files(
         index: &IndexRef,
         channel: mpsc::Receiver<IndexerMessage>,
+        max_directory_depth: usize,
+        file_extensions: &[String],
         observer: &impl IndexerObserver,
     ) {
         log::info!("Watching workspace files");
-        for msg in channel {
+        let mut pending = None;
+        while let Some(msg) = pending.take().or_else(|| channel.recv().ok()) {
             match msg {
                 IndexerMessage::IndexModifiedFileBuffer(path, tree, content) => {
                     log::trace!("Request to index file buffer for {path:?}");
-                    let index_file = Self::index_parse_tree(&tree, content.as_bytes(), path);
+                    let index_file =
+                        Self::index_parse_tree(&tree, content.as_bytes(), path.clone());
                     index.get_mut().update_file(index_file);
+                    observer.file_buffer_indexed(&path);
                 }
-                IndexerMessage::IndexModifiedFiles(paths) => {
+                IndexerMessage::IndexModifiedFiles(mut paths) => {
+                    while let Ok(msg) = channel.recv_timeout(MODIFIED_FILES_DEBOUNCE) {
+                        match msg {
+                            IndexerMessage::IndexModifiedFiles(more_paths) => {
+                                paths.extend(more_paths)
+                            }
+                            msg => {
+                                pending = Some(msg);
+                                break;
+                            }
+                        }
+                    }
+                    paths.sort();
+                    paths.dedup();
                     log::trace!("Request to index files {paths:?}");
                     observer.state_transition(IndexerState::Inactive, IndexerState::Indexing);
                     rayon::in_place_scope(|scope| {
                         for path in paths {
                             if path.is_dir() {
-                                Self::index_directory(&path, index, scope);
+                                Self::index_directory(
+                                    &path,
+                                    max_directory_depth,
+                                    file_extensions,
+                                    index,
+                                    scope,
+                                );
                             } else {
                                 Self::index_file(path, index, scope);
                             }
@@ -745,18 +1258,20 @@ impl Indexer {
                     observer.state_transition(IndexerState::Inactive, IndexerState::Indexing);
                     for path in paths {
                         if path.is_dir() {
-                            let files: Vec<IndexFileRef> = index
+                            let files: Vec<(IndexFileRef, PathBuf)> = index
                                 .get()
                                 .files
                                 .iter()
                                 .filter(|(_, file)| file.path.starts_with(&path))
-                                .map(|(file_ref, _)| file_ref.clone())
+                                .map(|(file_ref, file)| (file_ref.clone(), file.path.clone()))
                                 .collect();
-                            for file in files {
-                                index.get_mut().remove_file(file);
+                            for (file, file_path) in files {
+                                if index.get_mut().remove_file(file) {
+                                    observer.file_removed(&file_path);
+                                }
                             }
-                        } else {
-                            index.get_mut().remove_file(IndexFileRef::from(&path));
+                        } else if index.get_mut().remove_file(IndexFileRef::from(&path)) {
+                            observer.file_removed(&path);
                         }
                     }
                     observer.state_transition(IndexerState::Indexing, IndexerState::Inactive);
@@ -768,15 +1283,31 @@ impl Indexer {
         }
     }
 
-    pub fn should_index_file(path: &PathBuf) -> bool {
-        matches!(
-            path.extension().and_then(OsStr::to_str),
-            Some("pkg" | "vw" | "wo" | "sl" | "dd" | "src" | "dg" | "bp" | "rv" | "fd" | "inc")
-        )
+    pub fn should_index_file(&self, path: &PathBuf) -> bool {
+        Self::is_indexable_file(path, &self.config.file_extensions)
+    }
+
+    fn is_indexable_file(path: &PathBuf, file_extensions: &[String]) -> bool {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| {
+                file_extensions
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(extension))
+            })
+    }
+
+    fn indexer_query() -> Option<&'static tree_sitter::Query> {
+        static INDEXER_QUERY: OnceLock<Option<tree_sitter::Query>> = OnceLock::new();
+        INDEXER_QUERY
+            .get_or_init(|| Self::load_query(include_str!("indexer.scm")))
+            .as_ref()
     }
 
-    fn indexer_query() -> &'static str {
-        include_str!("indexer.scm")
+    fn load_query(source: &str) -> Option<tree_sitter::Query> {
+        tree_sitter::Query::new(&tree_sitter_dataflex::LANGUAGE.into(), source)
+            .inspect_err(|e| log::error!("Error loading indexer query, indexing is disabled: {e}"))
+            .ok()
     }
 }
 
@@ -791,13 +1322,123 @@ impl IndexerConfig {
             Self {
                 versioned_system_paths,
                 default_version,
+                max_directory_depth: Self::default_max_directory_depth(),
+                index_cache: Self::default_index_cache(),
+                file_extensions: Self::default_file_extensions(),
             }
         } else {
             Self {
                 versioned_system_paths: HashMap::new(),
                 default_version: Default::default(),
+                max_directory_depth: Self::default_max_directory_depth(),
+                index_cache: Self::default_index_cache(),
+                file_extensions: Self::default_file_extensions(),
+            }
+        }
+    }
+
+    /// Limits how deep directories are indexed below a system path or workspace root, so that
+    /// pathologically deep directory trees don't exhaust the stack.
+    pub fn with_max_directory_depth(mut self, max_directory_depth: usize) -> Self {
+        self.max_directory_depth = max_directory_depth;
+        self
+    }
+
+    pub const fn default_max_directory_depth() -> usize {
+        32
+    }
+
+    /// Turns off loading and saving the index in the workspace `IdeSrc` folder, so that every
+    /// file is indexed from scratch, which helps when debugging the indexer.
+    pub fn with_index_cache(mut self, index_cache: bool) -> Self {
+        self.index_cache = index_cache;
+        self
+    }
+
+    pub const fn default_index_cache() -> bool {
+        true
+    }
+
+    /// Replaces the extensions of the files that are indexed when found in a system path or
+    /// workspace directory. Extensions are matched case-insensitively, without the leading dot.
+    pub fn with_file_extensions(mut self, file_extensions: Vec<String>) -> Self {
+        self.file_extensions = file_extensions;
+        self
+    }
+
+    pub fn default_file_extensions() -> Vec<String> {
+        [
+            "pkg", "vw", "wo", "sl", "dd", "src", "dg", "bp", "rv", "fd", "inc", "int",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    /// Adds the `Pkg` directories of DataFlex installs found in the base directories, so that the
+    /// system packages are indexed even when the install isn't registered. A `Pkg` directory in a
+    /// base directory is used for the default version, and one in a folder like `DataFlex 24.0`
+    /// for that version.
+    pub fn with_pkg_directories(mut self, install_bases: &[PathBuf]) -> Self {
+        for (version, pkg_path) in install_bases
+            .iter()
+            .flat_map(|base| Self::pkg_directories(base))
+        {
+            let version = version.unwrap_or_else(|| self.default_version.clone());
+            if self.versioned_system_paths.is_empty() {
+                self.default_version = version.clone();
+            }
+            let paths = self.versioned_system_paths.entry(version).or_default();
+            if !paths.contains(&pkg_path) {
+                log::info!("Found DataFlex packages in {:?}", pkg_path);
+                paths.push(pkg_path);
             }
         }
+        self
+    }
+
+    /// Install locations probed for `Pkg` directories by default.
+    pub fn default_install_bases() -> Vec<PathBuf> {
+        if cfg!(target_os = "windows") {
+            vec![
+                PathBuf::from("C:\\Program Files"),
+                PathBuf::from("C:\\Program Files (x86)"),
+            ]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn pkg_directories(base: &PathBuf) -> Vec<(Option<DataFlexVersion>, PathBuf)> {
+        let mut result = Vec::new();
+        let pkg_path = base.join("Pkg");
+        if pkg_path.is_dir() {
+            result.push((None, pkg_path));
+        }
+
+        let mut installs: Vec<PathBuf> = std::fs::read_dir(base)
+            .into_iter()
+            .flatten()
+            .flat_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.to_lowercase().starts_with("dataflex"))
+            })
+            .collect();
+        installs.sort();
+        for install in installs {
+            let pkg_path = install.join("Pkg");
+            if pkg_path.is_dir() {
+                let version = install
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.split_whitespace().nth(1))
+                    .map(DataFlexVersion::from);
+                result.push((version, pkg_path));
+            }
+        }
+        result
     }
 
     pub fn system_path(&self, dataflex_version: Option<&DataFlexVersion>) -> Option<&Vec<PathBuf>> {
@@ -823,11 +1464,7 @@ impl IndexerConfig {
                 if let Some(make_path) = make_path {
                     result.insert(
                         DataFlexVersion::from(version),
-                        make_path
-                            .split(";")
-                            .map(str::trim)
-                            .map(PathBuf::from)
-                            .collect(),
+                        Self::make_path_entries(&make_path),
                     );
                 }
                 result
@@ -835,10 +1472,72 @@ impl IndexerConfig {
         ))
     }
 
-    #[cfg(not(target_os = "windows"))]
+    /// Reads the system paths from the `DATAFLEX_SYSTEM_PATHS` environment variable, e.g.
+    /// `24.0=/opt/df24/Pkg;/opt/df24/Lib,23.0=/opt/df23/Pkg`, or from `~/.dataflex/<version>`
+    /// folders, which use their `MakePath` file or else their `Pkg` folder. This is for DataFlex
+    /// installs under Wine, or copies of the packages, since there's no registry to read.
+    #[cfg(target_os = "linux")]
+    fn versioned_system_paths() -> Option<HashMap<DataFlexVersion, Vec<PathBuf>>> {
+        if let Ok(value) = std::env::var("DATAFLEX_SYSTEM_PATHS") {
+            return Some(Self::parse_versioned_system_paths(&value));
+        }
+        let dataflex_dir = PathBuf::from(std::env::var_os("HOME")?).join(".dataflex");
+        Some(Self::scan_versioned_system_paths(&dataflex_dir))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
     fn versioned_system_paths() -> Option<HashMap<DataFlexVersion, Vec<PathBuf>>> {
         None
     }
+
+    #[cfg(target_os = "linux")]
+    fn parse_versioned_system_paths(value: &str) -> HashMap<DataFlexVersion, Vec<PathBuf>> {
+        value
+            .split(',')
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(version, make_path)| {
+                (
+                    DataFlexVersion::from(version.trim()),
+                    Self::make_path_entries(make_path),
+                )
+            })
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn scan_versioned_system_paths(
+        dataflex_dir: &std::path::Path,
+    ) -> HashMap<DataFlexVersion, Vec<PathBuf>> {
+        std::fs::read_dir(dataflex_dir)
+            .into_iter()
+            .flatten()
+            .flat_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                let version = path.file_name()?.to_str()?.to_string();
+                let paths = if let Ok(make_path) = std::fs::read_to_string(path.join("MakePath")) {
+                    Self::make_path_entries(&make_path)
+                } else if path.join("Pkg").is_dir() {
+                    vec![path.join("Pkg")]
+                } else {
+                    Vec::new()
+                };
+                (!paths.is_empty()).then(|| (DataFlexVersion::from(version), paths))
+            })
+            .collect()
+    }
+
+    /// Splits a `MakePath` value, a `;` separated list of paths, into its paths.
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    fn make_path_entries(make_path: &str) -> Vec<PathBuf> {
+        make_path
+            .split(";")
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
 }
 
 #[derive(EnumString)]
@@ -849,18 +1548,23 @@ enum TagsQueryIndexElement {
     ObjectDefinition,
     MethodProcedureDefinition,
     MethodFunctionDefinition,
+    GlobalProcedureDefinition,
+    GlobalFunctionDefinition,
     PropertyDefinition,
     StructDeclaration,
     StructMember,
     GlobalVariableDeclaration,
     AliasDefinition,
     MixinClass,
+    ObjectRegistration,
+    ExternalFunctionDeclaration,
     PopStackSymbol,
 }
 
 impl Index {
     fn update_file(&mut self, index_file: IndexFile) {
         let file_ref = IndexFileRef::from(&index_file.path);
+        self.missing_files.remove(&file_ref);
         let old_index_file = self.files.insert(file_ref.clone(), index_file);
         let new_index_file = self.files.get(&file_ref);
         let symbols_diff = SymbolsDiff::diff_index_files(old_index_file.as_ref(), new_index_file);
@@ -870,21 +1574,85 @@ impl Index {
             new_index_file.and_then(|f| f.tables.as_deref()),
             &file_ref,
         );
+        self.lookup_tables.update_command_references(
+            old_index_file.as_ref().and_then(|f| f.commands.as_deref()),
+            new_index_file.and_then(|f| f.commands.as_deref()),
+            &file_ref,
+        );
+        self.lookup_tables.update_file_dependencies(
+            old_index_file.as_ref().map(|f| &f.dependencies),
+            new_index_file.map(|f| &f.dependencies),
+            &file_ref,
+        );
         self.updated_file_count += 1;
+        if let Some(progress) = self.indexing_progress.as_mut().filter(|p| !p.done) {
+            progress.indexed_file_count += 1;
+        }
     }
 
-    fn remove_file(&mut self, file_ref: IndexFileRef) {
-        if let Some(index_file) = self.files.remove(&file_ref) {
-            let symbols_diff = SymbolsDiff::diff_index_files(Some(&index_file), None);
-            self.lookup_tables.update_symbols(symbols_diff, &file_ref);
-            self.lookup_tables.update_dataflex_table_references(
-                index_file.tables.as_deref(),
-                None,
-                &file_ref,
-            );
-            self.updated_file_count += 1;
+    fn is_file_unchanged(&self, path: &PathBuf, modified: Option<std::time::SystemTime>) -> bool {
+        self.files
+            .get(&IndexFileRef::from(path))
+            .is_some_and(|index_file| index_file.path == *path && index_file.modified == modified)
+    }
+
+    /// Counts a file that didn't need indexing towards the progress of the indexing phase.
+    fn skip_unchanged_file(&mut self) {
+        if let Some(progress) = self.indexing_progress.as_mut().filter(|p| !p.done) {
+            progress.indexed_file_count += 1;
+        }
+    }
+
+    fn start_indexing_phase(
+        &mut self,
+        phase: IndexingPhase,
+        total_file_count: usize,
+        later_file_count: usize,
+    ) {
+        log::info!("Found {total_file_count} files to index for {phase:?}");
+        let earlier_file_count = self.indexing_progress.map_or(0, |progress| {
+            progress.earlier_file_count + progress.indexed_file_count.max(progress.total_file_count)
+        });
+        self.indexing_progress = Some(IndexingProgress {
+            phase,
+            indexed_file_count: 0,
+            total_file_count,
+            earlier_file_count,
+            later_file_count,
+            done: false,
+        });
+    }
+
+    fn finish_indexing_phase(&mut self) {
+        if let Some(progress) = self.indexing_progress.as_mut() {
+            progress.done = true;
         }
     }
+
+    fn remove_file(&mut self, file_ref: IndexFileRef) -> bool {
+        let Some(index_file) = self.files.remove(&file_ref) else {
+            return false;
+        };
+        let symbols_diff = SymbolsDiff::diff_index_files(Some(&index_file), None);
+        self.lookup_tables.update_symbols(symbols_diff, &file_ref);
+        self.lookup_tables.update_dataflex_table_references(
+            index_file.tables.as_deref(),
+            None,
+            &file_ref,
+        );
+        self.lookup_tables.update_command_references(
+            index_file.commands.as_deref(),
+            None,
+            &file_ref,
+        );
+        self.lookup_tables.update_file_dependencies(
+            Some(&index_file.dependencies),
+            None,
+            &file_ref,
+        );
+        self.updated_file_count += 1;
+        true
+    }
 }
 
 impl IndexFile {
@@ -910,6 +1678,54 @@ impl DataFlexDataType {
     }
 }
 
+impl MethodSymbol {
+    /// Parses `External_Function Name "entry" [Library] x.dll [Type Param ...] Returns Type`.
+    fn with_external_function_node(node: tree_sitter::Node, content: &[u8]) -> Option<Self> {
+        let name_node = node.named_child(1)?;
+        let name = name_node.utf8_text(content).ok()?;
+        let arguments = node
+            .utf8_text(content)
+            .ok()?
+            .get(name_node.end_byte() - node.start_byte()..)?;
+        let mut tokens = arguments.split_whitespace();
+
+        let entry = tokens.next()?.trim_matches('"');
+        let library = match tokens.next()? {
+            keyword if keyword.eq_ignore_ascii_case("library") => tokens.next()?,
+            library => library,
+        }
+        .trim_matches('"');
+
+        let mut parameters = Vec::new();
+        let mut return_type = None;
+        while let Some(token) = tokens.next() {
+            if token.eq_ignore_ascii_case("returns") {
+                return_type = tokens.next().map(|t| DataFlexDataType::Simple(t.into()));
+                break;
+            }
+            let parameter_name = tokens.next()?;
+            parameters.push((
+                SymbolName::from(parameter_name),
+                DataFlexDataType::Simple(token.into()),
+            ));
+        }
+
+        Some(Self {
+            location: name_node.start_position().into(),
+            range: node.range().into(),
+            symbol_path: SymbolPath::with_name(name),
+            kind: MethodKind::Get,
+            parameters,
+            return_type,
+            external: Some(ExternalBinding {
+                entry: entry.into(),
+                library: library.into(),
+            }),
+            metadata: Vec::new(),
+        })
+    }
+}
+
 impl MetadataTagSet {
     fn associated_metadata_tag_sets(
         symbol_node: &tree_sitter::Node,
@@ -943,7 +1759,20 @@ impl MetadataTagSet {
 #[cfg(test)]
 impl Indexer {
     pub fn index_test_content(content: &str, path: PathBuf, index: &IndexRef) {
-        Self::index_file_content(content.as_bytes(), path, index);
+        Self::index_file_content(content.as_bytes(), path, None, index);
+    }
+
+    /// Runs the initial indexing phases on the calling thread and returns once the indexer
+    /// is `Inactive`. Unlike `start_indexing()`, no file watching is started.
+    pub fn index_now<T: IndexerObserver>(&self, observer: T) {
+        let system_paths = self.config.system_path(self.dataflex_version.as_ref());
+        Self::initial_indexing(
+            &self.index,
+            system_paths,
+            self.config.max_directory_depth,
+            &self.config.file_extensions,
+            &observer,
+        );
     }
 }
 
@@ -951,6 +1780,458 @@ impl Indexer {
 mod tests {
     use super::*;
 
+    struct StateRecorder(std::sync::Mutex<Vec<IndexerState>>);
+
+    impl IndexerObserver for &StateRecorder {
+        fn state_transition(&self, _old_state: IndexerState, new_state: IndexerState) {
+            self.0.lock().unwrap().push(new_state);
+        }
+    }
+
+    #[test]
+    fn test_index_now_with_missing_workspace_root() {
+        struct MissingRootRecorder(std::sync::Mutex<(Vec<IndexerState>, Option<PathBuf>)>);
+
+        impl IndexerObserver for &MissingRootRecorder {
+            fn state_transition(&self, _old_state: IndexerState, new_state: IndexerState) {
+                self.0.lock().unwrap().0.push(new_state);
+            }
+
+            fn workspace_root_missing(&self, path: &PathBuf) {
+                self.0.lock().unwrap().1 = Some(path.clone());
+            }
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-missing-root-{}", std::process::id()));
+        let workspace = WorkspaceInfo::load_from_path(&dir);
+        assert_eq!(workspace.get_root_folder(), &dir);
+
+        let indexer = Indexer::new(workspace, IndexerConfig::new());
+        let recorder = MissingRootRecorder(std::sync::Mutex::new((Vec::new(), None)));
+        indexer.index_now(&recorder);
+
+        let recorded = recorder.0.lock().unwrap();
+        let (states, missing_root) = &*recorded;
+        assert_eq!(
+            *states,
+            [IndexerState::InitialIndexing, IndexerState::Inactive]
+        );
+        assert_eq!(missing_root.as_ref(), Some(&dir));
+        assert!(indexer.get_index().get().files.is_empty());
+    }
+
+    #[test]
+    fn test_index_now() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-index-now-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("AppSrc")).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        std::fs::write(
+            dir.join("AppSrc").join("classes.pkg"),
+            "Class cFoo is a cObject\nEnd_Class\nClass cBar is a cFoo\nEnd_Class\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("AppSrc").join("view.vw"),
+            "Use classes.pkg\nObject oFoo is a cFoo\nEnd_Object\n",
+        )
+        .unwrap();
+
+        let indexer = Indexer::new(WorkspaceInfo::load_from_path(&dir), IndexerConfig::new());
+        let recorder = StateRecorder(std::sync::Mutex::new(Vec::new()));
+        indexer.index_now(&recorder);
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [IndexerState::InitialIndexing, IndexerState::Inactive]
+        );
+        assert_eq!(
+            indexer.indexing_progress(),
+            Some(IndexingProgress {
+                phase: IndexingPhase::Workspace,
+                indexed_file_count: 2,
+                total_file_count: 2,
+                earlier_file_count: 0,
+                later_file_count: 0,
+                done: true,
+            })
+        );
+        assert_eq!(indexer.indexing_progress().unwrap().percentage(), 100);
+        let index = indexer.get_index().get();
+        assert_eq!(index.files.len(), 2);
+        assert_eq!(index.top_level_class_and_object_symbols().count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_index_directory_with_symlink_cycle() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-symlink-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("AppSrc")).unwrap();
+        std::fs::write(
+            dir.join("AppSrc").join("classes.pkg"),
+            "Class cFoo is a cObject\nEnd_Class\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("AppSrc").join("loop")).unwrap();
+
+        let index_ref = IndexRef::make_test_index_ref();
+        rayon::in_place_scope(|scope| {
+            Indexer::index_directory(
+                &dir,
+                IndexerConfig::default_max_directory_depth(),
+                &IndexerConfig::default_file_extensions(),
+                &index_ref,
+                scope,
+            );
+        });
+        _ = std::fs::remove_dir_all(&dir);
+
+        let index = index_ref.get();
+        assert_eq!(index.files.len(), 1);
+        assert!(index.find_class(&"cFoo".into()).is_some());
+    }
+
+    #[test]
+    fn test_index_directory_max_depth() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-max-depth-{}", std::process::id()));
+        let deep_dir = dir.join("a").join("b").join("c");
+        std::fs::create_dir_all(&deep_dir).unwrap();
+        std::fs::write(
+            dir.join("a").join("a.pkg"),
+            "Class cA is a cObject\nEnd_Class\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("a").join("b").join("b.pkg"),
+            "Class cB is a cObject\nEnd_Class\n",
+        )
+        .unwrap();
+        std::fs::write(deep_dir.join("c.pkg"), "Class cC is a cObject\nEnd_Class\n").unwrap();
+
+        let index_ref = IndexRef::make_test_index_ref();
+        rayon::in_place_scope(|scope| {
+            Indexer::index_directory(
+                &dir,
+                2,
+                &IndexerConfig::default_file_extensions(),
+                &index_ref,
+                scope,
+            );
+        });
+        let file_count = Indexer::indexable_file_count(
+            std::slice::from_ref(&dir),
+            2,
+            &IndexerConfig::default_file_extensions(),
+        );
+        _ = std::fs::remove_dir_all(&dir);
+
+        let index = index_ref.get();
+        assert_eq!(index.files.len(), 2);
+        assert_eq!(file_count, 2);
+        assert!(index.find_class(&"cA".into()).is_some());
+        assert!(index.find_class(&"cB".into()).is_some());
+        assert!(index.find_class(&"cC".into()).is_none());
+    }
+
+    #[test]
+    fn test_index_overlapping_directories() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-overlapping-directories-{}",
+            std::process::id()
+        ));
+        let first = dir.join("first");
+        let second = dir.join("second");
+        std::fs::create_dir_all(first.join("nested")).unwrap();
+        std::fs::create_dir_all(second.join("x").join("y")).unwrap();
+        std::fs::write(second.join("b.pkg"), "Class cB is a cObject\nEnd_Class\n").unwrap();
+        std::fs::write(
+            second.join("x").join("y").join("y.pkg"),
+            "Class cY is a cObject\nEnd_Class\n",
+        )
+        .unwrap();
+        // Reaches `second/x` through the first root with less remaining depth than through the
+        // second root.
+        std::os::unix::fs::symlink(second.join("x"), first.join("nested").join("link")).unwrap();
+
+        // The second root is also nested in the third.
+        let paths = vec![first, second, dir.clone()];
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_system_paths(
+            &paths,
+            2,
+            &IndexerConfig::default_file_extensions(),
+            &index_ref,
+        );
+        let file_count =
+            Indexer::indexable_file_count(&paths, 2, &IndexerConfig::default_file_extensions());
+        _ = std::fs::remove_dir_all(&dir);
+
+        let index = index_ref.get();
+        assert_eq!(index.files.len(), 2);
+        assert_eq!(file_count, 2);
+        assert!(index.find_class(&"cB".into()).is_some());
+        assert!(index.find_class(&"cY".into()).is_some());
+    }
+
+    #[test]
+    fn test_index_directory_file_extensions() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-file-extensions-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.pkg"), "Class cA is a cObject\nEnd_Class\n").unwrap();
+        std::fs::write(dir.join("b.RPT"), "Class cB is a cObject\nEnd_Class\n").unwrap();
+        std::fs::write(dir.join("c.vw"), "Class cC is a cObject\nEnd_Class\n").unwrap();
+
+        let indexer = Indexer::new(
+            WorkspaceInfo::new(),
+            IndexerConfig::new().with_file_extensions(vec!["pkg".into(), "rpt".into()]),
+        );
+        let index_ref = indexer.get_index();
+        rayon::in_place_scope(|scope| {
+            Indexer::index_directory(&dir, 0, &indexer.config.file_extensions, index_ref, scope);
+        });
+        let file_count = Indexer::indexable_file_count(
+            std::slice::from_ref(&dir),
+            0,
+            &indexer.config.file_extensions,
+        );
+        _ = std::fs::remove_dir_all(&dir);
+
+        let index = index_ref.get();
+        assert_eq!(index.files.len(), 2);
+        assert_eq!(file_count, 2);
+        assert!(index.find_class(&"cA".into()).is_some());
+        assert!(index.find_class(&"cB".into()).is_some());
+        assert!(index.find_class(&"cC".into()).is_none());
+        assert!(indexer.should_index_file(&PathBuf::from("Report.rpt")));
+        assert!(!indexer.should_index_file(&PathBuf::from("View.vw")));
+    }
+
+    #[test]
+    fn test_index_missing_dependencies() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-missing-dependencies-{}",
+            std::process::id()
+        ));
+        let pkg_dir = dir.join("Pkg");
+        std::fs::create_dir_all(&pkg_dir).unwrap();
+        std::fs::write(
+            pkg_dir.join("cView.pkg"),
+            "Use cBase.pkg\nClass cView is a cBase\nEnd_Class\n",
+        )
+        .unwrap();
+        std::fs::write(
+            pkg_dir.join("cBase.pkg"),
+            "Class cBase is a cObject\nEnd_Class\n",
+        )
+        .unwrap();
+
+        let indexer = Indexer::new(WorkspaceInfo::new(), IndexerConfig::new());
+        indexer
+            .get_index()
+            .get_mut()
+            .set_system_paths(vec![dir.clone()]);
+        let indexed = indexer.index_missing_dependencies(&["cView".into(), "Missing.pkg".into()]);
+        let indexed_again = indexer.index_missing_dependencies(&["cView".into()]);
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert!(indexed);
+        assert!(!indexed_again);
+        let index = indexer.get_index().get();
+        assert_eq!(index.files.len(), 2);
+        assert!(index.find_class(&"cView".into()).is_some());
+        assert!(index.find_class(&"cBase".into()).is_some());
+        assert!(index.missing_files.contains(&"Missing.pkg".into()));
+    }
+
+    #[test]
+    fn test_find_file_in_directories() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-find-file-{}", std::process::id()));
+        let sub_dir = dir.join("Pkg");
+        std::fs::create_dir_all(&sub_dir).unwrap();
+        std::fs::write(sub_dir.join("cMyView.pkg"), "").unwrap();
+
+        let paths = [dir.clone()];
+        let found = Indexer::find_file_in_directories(&paths, &"CMYVIEW.PKG".into(), 2);
+        let found_default = Indexer::find_file_in_directories(&paths, &"cMyView".into(), 2);
+        let too_deep = Indexer::find_file_in_directories(&paths, &"cMyView.pkg".into(), 0);
+        let missing = Indexer::find_file_in_directories(&paths, &"cOther.pkg".into(), 2);
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(found, Some(sub_dir.join("cMyView.pkg")));
+        assert_eq!(found_default, Some(sub_dir.join("cMyView.pkg")));
+        assert_eq!(too_deep, None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_unchanged_files_are_not_reindexed() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-unchanged-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.pkg");
+        std::fs::write(&path, "Class cA is a cObject\nEnd_Class\n").unwrap();
+
+        let index_ref = IndexRef::make_test_index_ref();
+        let index_dir = || {
+            rayon::in_place_scope(|scope| {
+                Indexer::index_directory(
+                    &dir,
+                    1,
+                    &IndexerConfig::default_file_extensions(),
+                    &index_ref,
+                    scope,
+                );
+            });
+            index_ref.get().updated_file_count
+        };
+        let first_count = index_dir();
+        let unchanged_count = index_dir();
+
+        std::fs::write(&path, "Class cB is a cObject\nEnd_Class\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        drop(file);
+        let changed_count = index_dir();
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(first_count, 1);
+        assert_eq!(unchanged_count, 1);
+        assert_eq!(changed_count, 2);
+        assert!(index_ref.get().find_class(&"cB".into()).is_some());
+    }
+
+    struct RemovedFilesRecorder(std::sync::Mutex<Vec<PathBuf>>);
+
+    impl IndexerObserver for RemovedFilesRecorder {
+        fn state_transition(&self, _old_state: IndexerState, _new_state: IndexerState) {}
+
+        fn file_removed(&self, path: &PathBuf) {
+            self.0.lock().unwrap().push(path.clone());
+        }
+    }
+
+    #[test]
+    fn test_file_removed() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content("Use a.pkg\n", "/src/a.pkg".into(), &index_ref);
+        Indexer::index_test_content("Use b.pkg\n", "/src/b.pkg".into(), &index_ref);
+
+        let (sender, receiver) = mpsc::channel();
+        sender
+            .send(IndexerMessage::RemoveIndexedFiles(vec![
+                "/src/a.pkg".into(),
+                "/src/missing.pkg".into(),
+            ]))
+            .unwrap();
+        sender.send(IndexerMessage::StopIndexing).unwrap();
+
+        let observer = RemovedFilesRecorder(std::sync::Mutex::new(Vec::new()));
+        Indexer::watch_and_index_changed_files(
+            &index_ref,
+            receiver,
+            IndexerConfig::default_max_directory_depth(),
+            &IndexerConfig::default_file_extensions(),
+            &observer,
+        );
+
+        assert_eq!(*observer.0.lock().unwrap(), [PathBuf::from("/src/a.pkg")]);
+        assert_eq!(
+            index_ref.get().all_known_files(),
+            [IndexFileRef::from("b.pkg")]
+        );
+    }
+
+    struct StateTransitionRecorder(std::sync::Mutex<Vec<(IndexerState, IndexerState)>>);
+
+    impl IndexerObserver for StateTransitionRecorder {
+        fn state_transition(&self, old_state: IndexerState, new_state: IndexerState) {
+            self.0.lock().unwrap().push((old_state, new_state));
+        }
+    }
+
+    #[test]
+    fn test_modified_files_are_debounced() {
+        let dir = std::env::temp_dir().join("dataflex-lsp-test-debounce");
+        std::fs::create_dir_all(&dir).unwrap();
+        let a_path = dir.join("a.pkg");
+        let b_path = dir.join("b.pkg");
+        std::fs::write(&a_path, "Class cA is a cObject\nEnd_Class\n").unwrap();
+        std::fs::write(&b_path, "Class cB is a cObject\nEnd_Class\n").unwrap();
+
+        let index_ref = IndexRef::make_test_index_ref();
+        let (sender, receiver) = mpsc::channel();
+        sender
+            .send(IndexerMessage::IndexModifiedFiles(vec![a_path.clone()]))
+            .unwrap();
+        sender
+            .send(IndexerMessage::IndexModifiedFiles(vec![
+                b_path.clone(),
+                a_path.clone(),
+            ]))
+            .unwrap();
+        sender
+            .send(IndexerMessage::RemoveIndexedFiles(vec![b_path.clone()]))
+            .unwrap();
+        sender.send(IndexerMessage::StopIndexing).unwrap();
+
+        let observer = StateTransitionRecorder(std::sync::Mutex::new(Vec::new()));
+        Indexer::watch_and_index_changed_files(
+            &index_ref,
+            receiver,
+            IndexerConfig::default_max_directory_depth(),
+            &IndexerConfig::default_file_extensions(),
+            &observer,
+        );
+
+        // Both modifications are indexed in one pass, followed by the removal.
+        assert_eq!(
+            *observer.0.lock().unwrap(),
+            [
+                (IndexerState::Inactive, IndexerState::Indexing),
+                (IndexerState::Indexing, IndexerState::Inactive),
+                (IndexerState::Inactive, IndexerState::Indexing),
+                (IndexerState::Indexing, IndexerState::Inactive),
+            ]
+        );
+        assert!(index_ref.get().is_known_class(&"cA".into()));
+        assert!(!index_ref.get().is_known_class(&"cB".into()));
+    }
+
+    #[test]
+    fn test_invalid_indexer_query() {
+        assert!(Indexer::load_query("(not_a_node_kind) @name").is_none());
+
+        // A query without the expected captures skips indexing instead of panicking.
+        let content = "Use cWebView.pkg\nClass cMyClass is a cBaseClass\nEnd_Class\n";
+        let tree = DataFlexTreeParser::new().parse(content, None).unwrap();
+        let query = Indexer::load_query("(use_statement) @element_node").unwrap();
+        let index_file = Indexer::index_parse_tree_with_query(
+            &tree,
+            content.as_bytes(),
+            "test.pkg".into(),
+            &query,
+        );
+        assert!(index_file.dependencies.is_empty());
+        assert!(index_file.symbols.is_empty());
+
+        // The default query still works.
+        let index_file = Indexer::index_parse_tree(&tree, content.as_bytes(), "test.pkg".into());
+        assert_eq!(
+            index_file.dependencies,
+            [IndexFileRef::from("cWebView.pkg")]
+        );
+        assert_eq!(index_file.symbols.len(), 1);
+    }
+
     #[test]
     fn test_index_file_dependency() {
         let index_ref = IndexRef::make_test_index_ref();
@@ -976,7 +2257,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: true, members: [], metadata: [] })]"
         );
     }
 
@@ -994,7 +2275,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 10 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 13 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 10 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 13 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: true, members: [], metadata: [] })]"
         );
     }
 
@@ -1012,7 +2293,40 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Msg, parameters: [], return_type: None, metadata: [] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })], metadata: [] })]"
+        );
+    }
+
+    #[test]
+    fn test_index_global_procedure() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Procedure foo\nEnd_Procedure\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
+            ),
+            "[Method(MethodSymbol { location: SourceLocation { line: 0, column: 10 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 13 } }, symbol_path: SymbolPath(\"foo\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })]"
+        );
+
+        Indexer::index_test_content(
+            "Class cMyClass is a cBaseClass\nEnd_Class\n\nFunction Bar Integer iValue Returns String\nEnd_Function\n",
+            "other.pkg".into(),
+            &index_ref,
+        );
+        let index = index_ref.get();
+        assert!(index.is_known_method(&"foo".into(), MethodKind::Msg));
+        assert_eq!(
+            index
+                .find_methods(&"Bar".into(), MethodKind::Get)
+                .map(|s| format!("{:?}", s.symbol_path))
+                .collect::<Vec<_>>(),
+            ["SymbolPath(\"Bar\")"]
         );
     }
 
@@ -1030,7 +2344,29 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 18 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.Server\"), kind: Set, parameters: [(SymbolName(\"sServer\"), DataFlexDataType(\"String\"))], return_type: None, metadata: [] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 18 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.Server\"), kind: Set, parameters: [(SymbolName(\"sServer\"), DataFlexDataType(\"String\"))], return_type: None, external: None, metadata: [] })], metadata: [] })]"
+        );
+
+        // The setter is only found as a Set method.
+        let index = index_ref.get();
+        assert_eq!(
+            index
+                .find_methods(&"Server".into(), MethodKind::Set)
+                .map(|s| format!("{:?}", s.symbol_path))
+                .collect::<Vec<_>>(),
+            ["SymbolPath(\"cMyClass.Server\")"]
+        );
+        assert_eq!(
+            index
+                .find_methods(&"Server".into(), MethodKind::Msg)
+                .count(),
+            0
+        );
+        assert_eq!(
+            index
+                .find_methods(&"Server".into(), MethodKind::Get)
+                .count(),
+            0
         );
     }
 
@@ -1048,7 +2384,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 13 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 16 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Get, parameters: [], return_type: Some(DataFlexDataType(\"String\")), metadata: [] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 13 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 16 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Get, parameters: [], return_type: Some(DataFlexDataType(\"String\")), external: None, metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1066,7 +2402,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Msg, parameters: [(SymbolName(\"sName\"), DataFlexDataType(\"String\"))], return_type: None, metadata: [] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.SayHello\"), kind: Msg, parameters: [(SymbolName(\"sName\"), DataFlexDataType(\"String\"))], return_type: None, external: None, metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1084,7 +2420,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 4, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.Construct_Object\"), kind: Msg, parameters: [], return_type: None, metadata: [] }), Property(VariableSymbol { location: SourceLocation { line: 2, column: 25 }, range: SourceRange { start: SourceLocation { line: 2, column: 8 }, end: SourceLocation { line: 3, column: 0 } }, symbol_path: SymbolPath(\"cMyClass.piTest\"), data_type: DataFlexDataType(\"Integer\"), metadata: [] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 0, column: 6 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 4, column: 9 } }, symbol_path: SymbolPath(\"cMyClass\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 3, column: 17 } }, symbol_path: SymbolPath(\"cMyClass.Construct_Object\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] }), Property(VariableSymbol { location: SourceLocation { line: 2, column: 25 }, range: SourceRange { start: SourceLocation { line: 2, column: 8 }, end: SourceLocation { line: 3, column: 0 } }, symbol_path: SymbolPath(\"cMyClass.piTest\"), data_type: DataFlexDataType(\"Integer\"), metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1102,7 +2438,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] })]"
+            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 1, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [], metadata: [] })]"
         );
     }
 
@@ -1120,7 +2456,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Object(ClassSymbol { location: SourceLocation { line: 1, column: 11 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 14 } }, symbol_path: SymbolPath(\"oMyObj.oMyInner\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [], metadata: [] })], metadata: [] })]"
+            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Object(ClassSymbol { location: SourceLocation { line: 1, column: 11 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 14 } }, symbol_path: SymbolPath(\"oMyObj.oMyInner\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [], metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1138,7 +2474,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"oMyObj.SayHello\"), kind: Msg, parameters: [], return_type: None, metadata: [] })], metadata: [] })]"
+            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"oMyObj.SayHello\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1156,7 +2492,7 @@ mod tests {
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"oMyObj.Private.SayHello\"), kind: Msg, parameters: [], return_type: None, metadata: [] })], metadata: [] })]"
+            "[Object(ClassSymbol { location: SourceLocation { line: 0, column: 7 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 3, column: 10 } }, symbol_path: SymbolPath(\"oMyObj\"), superclass: SymbolName(\"cBaseClass\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 1, column: 14 }, range: SourceRange { start: SourceLocation { line: 1, column: 4 }, end: SourceLocation { line: 2, column: 17 } }, symbol_path: SymbolPath(\"oMyObj.Private.SayHello\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] })], metadata: [] })]"
         );
     }
 
@@ -1243,7 +2579,7 @@ End_Class
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 4, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [SymbolName(\"cMyMixin\"), SymbolName(\"cMyOtherMixin\")], members: [], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 4, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [SymbolName(\"cMyMixin\"), SymbolName(\"cMyOtherMixin\")], is_declaration: false, members: [], metadata: [] })]"
         );
     }
 
@@ -1270,6 +2606,29 @@ End_Class
         );
     }
 
+    #[test]
+    fn test_index_int_file() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "DRIVER_NAME MSSQLDRV\nTABLE_NAME Customer\n\nFIELD_NUMBER 1\nFIELD_NAME Customer_Number\nFIELD_INDEX 1\n\nFIELD_NUMBER 2\nFIELD_NAME Name\n",
+            "Customer.int".into(),
+            &index_ref,
+        );
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                index_ref.get().files[&IndexFileRef::from("Customer.int")].tables
+            ),
+            "Some([DataFlexTable { name: SymbolName(\"Customer\"), columns: [SymbolName(\"File_Number\"), SymbolName(\"Customer_Number\"), SymbolName(\"Name\")] }])"
+        );
+        assert!(index_ref.get().is_known_dataflex_table(&"customer".into()));
+        assert!(Indexer::is_indexable_file(
+            &PathBuf::from("Customer.int"),
+            &IndexerConfig::default_file_extensions()
+        ));
+    }
+
     #[test]
     fn test_index_class_metadata() {
         let index_ref = IndexRef::make_test_index_ref();
@@ -1288,7 +2647,7 @@ End_Class
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 2, column: 6 }, range: SourceRange { start: SourceLocation { line: 2, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], members: [], metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 2, column: 6 }, range: SourceRange { start: SourceLocation { line: 2, column: 0 }, end: SourceLocation { line: 3, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], is_declaration: true, members: [], metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })]"
         );
     }
 
@@ -1312,7 +2671,7 @@ End_Class
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 5, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 3, column: 14 }, range: SourceRange { start: SourceLocation { line: 3, column: 4 }, end: SourceLocation { line: 4, column: 17 } }, symbol_path: SymbolPath(\"cFoo.MyPrivateMethod\"), kind: Msg, parameters: [], return_type: None, metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 5, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 3, column: 14 }, range: SourceRange { start: SourceLocation { line: 3, column: 4 }, end: SourceLocation { line: 4, column: 17 } }, symbol_path: SymbolPath(\"cFoo.MyPrivateMethod\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })], metadata: [] })]"
         );
     }
 
@@ -1337,7 +2696,158 @@ End_Class
                 "{:?}",
                 index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
             ),
-            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 6, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], members: [Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 5, column: 17 } }, symbol_path: SymbolPath(\"cFoo.Construct_Object\"), kind: Msg, parameters: [], return_type: None, metadata: [] }), Property(VariableSymbol { location: SourceLocation { line: 4, column: 25 }, range: SourceRange { start: SourceLocation { line: 4, column: 8 }, end: SourceLocation { line: 5, column: 0 } }, symbol_path: SymbolPath(\"cFoo.piMyProperty\"), data_type: DataFlexDataType(\"Integer\"), metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })], metadata: [] })]"
+            "[Class(ClassSymbol { location: SourceLocation { line: 1, column: 6 }, range: SourceRange { start: SourceLocation { line: 1, column: 0 }, end: SourceLocation { line: 6, column: 9 } }, symbol_path: SymbolPath(\"cFoo\"), superclass: SymbolName(\"cBar\"), mixins: [], is_declaration: false, members: [Method(MethodSymbol { location: SourceLocation { line: 2, column: 14 }, range: SourceRange { start: SourceLocation { line: 2, column: 4 }, end: SourceLocation { line: 5, column: 17 } }, symbol_path: SymbolPath(\"cFoo.Construct_Object\"), kind: Msg, parameters: [], return_type: None, external: None, metadata: [] }), Property(VariableSymbol { location: SourceLocation { line: 4, column: 25 }, range: SourceRange { start: SourceLocation { line: 4, column: 8 }, end: SourceLocation { line: 5, column: 0 } }, symbol_path: SymbolPath(\"cFoo.piMyProperty\"), data_type: DataFlexDataType(\"Integer\"), metadata: [MetadataTagSet { tags: [MetadataTag { name: SymbolName(\"Visibility\"), value: \"Private\" }] }] })], metadata: [] })]"
+        );
+    }
+
+    #[test]
+    fn test_index_external_function() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "External_Function GetTickCount \"GetTickCount\" Library \"kernel32.dll\" Returns Integer\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Function MyGlobalFunction Returns Integer\nEnd_Function\n",
+            "global.pkg".into(),
+            &index_ref,
+        );
+
+        assert_eq!(
+            format!(
+                "{:?}",
+                index_ref.get().files[&IndexFileRef::from("test.pkg")].symbols
+            ),
+            "[Method(MethodSymbol { location: SourceLocation { line: 0, column: 18 }, range: SourceRange { start: SourceLocation { line: 0, column: 0 }, end: SourceLocation { line: 0, column: 84 } }, symbol_path: SymbolPath(\"GetTickCount\"), kind: Get, parameters: [], return_type: Some(DataFlexDataType(\"Integer\")), external: Some(ExternalBinding { entry: \"GetTickCount\", library: \"kernel32.dll\" }), metadata: [] })]"
         );
+        let index = index_ref.get();
+        assert!(index.is_known_method(&"GetTickCount".into(), MethodKind::Get));
+        assert_eq!(
+            index.all_known_external_functions(),
+            [SymbolName::from("GetTickCount")]
+        );
+        assert_eq!(
+            index
+                .find_methods(&"GetTickCount".into(), MethodKind::Get)
+                .filter_map(|s| Some(index.resolve_symbol(s)?.symbol.to_string()))
+                .collect::<Vec<_>>(),
+            ["External_Function GetTickCount \"GetTickCount\" kernel32.dll Returns Integer"]
+        );
+    }
+
+    #[test]
+    fn test_pkg_directories_in_install_bases() {
+        let base =
+            std::env::temp_dir().join(format!("dataflex-lsp-install-base-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("Pkg")).unwrap();
+        std::fs::create_dir_all(base.join("DataFlex 24.0").join("Pkg")).unwrap();
+        std::fs::create_dir_all(base.join("Other").join("Pkg")).unwrap();
+        let empty_config = || IndexerConfig {
+            versioned_system_paths: HashMap::new(),
+            default_version: DataFlexVersion::default(),
+            max_directory_depth: IndexerConfig::default_max_directory_depth(),
+            index_cache: IndexerConfig::default_index_cache(),
+            file_extensions: IndexerConfig::default_file_extensions(),
+        };
+
+        let config = empty_config().with_pkg_directories(&[base.clone()]);
+        assert_eq!(config.system_path(None), Some(&vec![base.join("Pkg")]));
+        assert_eq!(
+            config.system_path(Some(&DataFlexVersion::from("24.0"))),
+            Some(&vec![base.join("DataFlex 24.0").join("Pkg")])
+        );
+
+        // Detection is disabled by not passing any install bases.
+        assert_eq!(
+            empty_config().with_pkg_directories(&[]).system_path(None),
+            None
+        );
+        _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_linux_versioned_system_paths() {
+        let paths = IndexerConfig::parse_versioned_system_paths(
+            "24.0=/opt/df24/Pkg; /opt/df24/Lib, 23.0 = /opt/df23/Pkg;,invalid",
+        );
+        assert_eq!(
+            paths.get(&DataFlexVersion::from("24.0")),
+            Some(&vec![
+                PathBuf::from("/opt/df24/Pkg"),
+                PathBuf::from("/opt/df24/Lib")
+            ])
+        );
+        assert_eq!(
+            paths.get(&DataFlexVersion::from("23.0")),
+            Some(&vec![PathBuf::from("/opt/df23/Pkg")])
+        );
+        assert_eq!(paths.len(), 2);
+
+        let dataflex_dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-dot-dataflex-{}", std::process::id()));
+        std::fs::create_dir_all(dataflex_dir.join("24.0").join("Pkg")).unwrap();
+        std::fs::create_dir_all(dataflex_dir.join("23.0")).unwrap();
+        std::fs::write(
+            dataflex_dir.join("23.0").join("MakePath"),
+            "/opt/df23/Pkg;/opt/df23/Lib\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(dataflex_dir.join("Empty")).unwrap();
+
+        let paths = IndexerConfig::scan_versioned_system_paths(&dataflex_dir);
+        assert_eq!(
+            paths.get(&DataFlexVersion::from("24.0")),
+            Some(&vec![dataflex_dir.join("24.0").join("Pkg")])
+        );
+        assert_eq!(
+            paths.get(&DataFlexVersion::from("23.0")),
+            Some(&vec![
+                PathBuf::from("/opt/df23/Pkg"),
+                PathBuf::from("/opt/df23/Lib")
+            ])
+        );
+        assert_eq!(paths.len(), 2);
+        _ = std::fs::remove_dir_all(&dataflex_dir);
+    }
+
+    #[test]
+    fn test_indexing_progress_percentage() {
+        let progress = |indexed_file_count, total_file_count, done| IndexingProgress {
+            phase: IndexingPhase::SystemPaths,
+            indexed_file_count,
+            total_file_count,
+            earlier_file_count: 0,
+            later_file_count: 0,
+            done,
+        };
+        assert_eq!(progress(0, 0, false).percentage(), 0);
+        assert_eq!(progress(1, 4, false).percentage(), 25);
+        // Files added while indexing don't push the percentage to 100 before the phase is done.
+        assert_eq!(progress(4, 4, false).percentage(), 99);
+        assert_eq!(progress(5, 4, false).percentage(), 99);
+        assert_eq!(progress(3, 4, true).percentage(), 100);
+    }
+
+    #[test]
+    fn test_indexing_progress_percentage_over_phases() {
+        let mut index = Index::new(WorkspaceInfo::new());
+        let mut percentages = Vec::new();
+        index.start_indexing_phase(IndexingPhase::SystemPaths, 6, 2);
+        percentages.push(index.indexing_progress.unwrap().percentage());
+        for _ in 0..6 {
+            index.skip_unchanged_file();
+            percentages.push(index.indexing_progress.unwrap().percentage());
+        }
+        index.finish_indexing_phase();
+        percentages.push(index.indexing_progress.unwrap().percentage());
+        index.start_indexing_phase(IndexingPhase::Workspace, 2, 0);
+        percentages.push(index.indexing_progress.unwrap().percentage());
+        index.skip_unchanged_file();
+        percentages.push(index.indexing_progress.unwrap().percentage());
+        index.finish_indexing_phase();
+        percentages.push(index.indexing_progress.unwrap().percentage());
+        assert_eq!(percentages, [0, 12, 25, 37, 50, 62, 75, 75, 75, 87, 100]);
     }
 }
@@ -62,6 +62,21 @@
       return_type: (typedecl) @return_type)
     (#set! index.element method_function_definition)) @element_node)
 
+(source_file
+  (procedure_definition
+    (procedure_header
+      name: (identifier) @name
+      (parameter)* @parameter)
+    (#set! index.element global_procedure_definition)) @element_node)
+
+(source_file
+  (function_definition
+    (function_header
+      name: (identifier) @name
+      (parameter)* @parameter
+      return_type: (typedecl) @return_type)
+    (#set! index.element global_function_definition)) @element_node)
+
 (property_definition
   type: [
     (system_typedecl
@@ -133,3 +148,17 @@
 (mixin_class
   name: (identifier) @name
   (#set! index.element mixin_class)) @element_node
+
+(other_command_statement
+  .
+  (simple_identifier) @command
+  .
+  (_) @name
+  (#match? @command "(?i)^register_object$")
+  (#set! index.element object_registration)) @element_node
+
+(other_command_statement
+  .
+  (simple_identifier) @command
+  (#match? @command "(?i)^external_function$")
+  (#set! index.element external_function_declaration)) @element_node
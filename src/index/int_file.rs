@@ -0,0 +1,38 @@
+use super::*;
+
+/// Lightweight parser for `.int` files, the intermediate files describing a table for a
+/// database driver. These are `KEYWORD value` lines rather than DataFlex source.
+pub struct IntFile;
+
+impl IntFile {
+    pub fn is_int_file(path: &PathBuf) -> bool {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("int"))
+    }
+
+    /// Records the table with the columns listed as `FIELD_NAME` entries. The table is named after
+    /// the file's root name, which is the logical name used to reference it in DataFlex source.
+    pub fn index_content(content: &[u8], path: PathBuf) -> IndexFile {
+        let mut index_file = IndexFile::new(path);
+        let Some(table_name) = index_file.path.file_stem().and_then(OsStr::to_str) else {
+            return index_file;
+        };
+
+        let content = String::from_utf8_lossy(content);
+        let columns = content.lines().filter_map(|line| {
+            let (keyword, value) = line.trim().split_once(char::is_whitespace)?;
+            keyword
+                .eq_ignore_ascii_case("field_name")
+                .then(|| SymbolName::from(value.trim()))
+        });
+        let table = DataFlexTable {
+            name: table_name.into(),
+            columns: std::iter::once("File_Number".into())
+                .chain(columns)
+                .collect(),
+        };
+        index_file.tables = Some(Box::new(vec![table]));
+        index_file
+    }
+}
@@ -3,7 +3,8 @@ use symbols_diff::SymbolsDiff;
 
 #[derive(Debug)]
 pub struct LookupTables {
-    class_lookup_table: HashMap<SymbolName, IndexSymbolRef>,
+    class_lookup_table: MultiMap<SymbolName, IndexSymbolRef>,
+    subclass_lookup_table: MultiMap<SymbolName, IndexSymbolRef>,
     object_lookup_table: MultiMap<SymbolName, IndexSymbolRef>,
     struct_lookup_table: HashMap<SymbolName, IndexSymbolRef>,
     method_lookup_tables: [MultiMap<SymbolName, IndexSymbolRef>; 3],
@@ -11,12 +12,15 @@ pub struct LookupTables {
     global_variable_lookup_table: HashMap<SymbolName, IndexSymbolRef>,
     alias_lookup_table: HashMap<SymbolName, IndexSymbolRef>,
     table_lookup_table: HashMap<SymbolName, IndexFileRef>,
+    command_lookup_table: HashMap<SymbolName, IndexFileRef>,
+    dependent_lookup_table: MultiMap<IndexFileRef, IndexFileRef>,
 }
 
 impl LookupTables {
     pub fn new() -> Self {
         Self {
-            class_lookup_table: HashMap::new(),
+            class_lookup_table: MultiMap::new(),
+            subclass_lookup_table: MultiMap::new(),
             object_lookup_table: MultiMap::new(),
             struct_lookup_table: HashMap::new(),
             method_lookup_tables: [MultiMap::new(), MultiMap::new(), MultiMap::new()],
@@ -24,17 +28,24 @@ impl LookupTables {
             global_variable_lookup_table: HashMap::new(),
             alias_lookup_table: HashMap::new(),
             table_lookup_table: HashMap::new(),
+            command_lookup_table: HashMap::new(),
+            dependent_lookup_table: MultiMap::new(),
         }
     }
 
-    pub fn class_lookup_table(&self) -> &HashMap<SymbolName, IndexSymbolRef> {
+    pub fn class_lookup_table(&self) -> &MultiMap<SymbolName, IndexSymbolRef> {
         &self.class_lookup_table
     }
 
-    pub fn class_lookup_table_mut(&mut self) -> &mut HashMap<SymbolName, IndexSymbolRef> {
+    pub fn class_lookup_table_mut(&mut self) -> &mut MultiMap<SymbolName, IndexSymbolRef> {
         &mut self.class_lookup_table
     }
 
+    /// The classes declared with a superclass, by the name of the superclass.
+    pub fn subclass_lookup_table(&self) -> &MultiMap<SymbolName, IndexSymbolRef> {
+        &self.subclass_lookup_table
+    }
+
     pub fn object_lookup_table(&self) -> &MultiMap<SymbolName, IndexSymbolRef> {
         &self.object_lookup_table
     }
@@ -126,6 +137,66 @@ impl LookupTables {
         }
     }
 
+    pub fn command_lookup_table(&self) -> &HashMap<SymbolName, IndexFileRef> {
+        &self.command_lookup_table
+    }
+
+    pub fn update_command_references(
+        &mut self,
+        old_commands: Option<&Vec<CommandDefinition>>,
+        new_commands: Option<&Vec<CommandDefinition>>,
+        file_ref: &IndexFileRef,
+    ) {
+        if let Some(commands) = old_commands {
+            commands.iter().for_each(|command| {
+                self.command_lookup_table.remove(&command.name);
+            });
+        }
+        if let Some(commands) = new_commands {
+            commands.iter().for_each(|command| {
+                self.command_lookup_table
+                    .insert(command.name.clone(), file_ref.clone());
+            });
+        }
+    }
+
+    /// The files using each file, by the file they use. References without an extension, as in
+    /// `Use cWebView`, are recorded under the conventional `.pkg` file.
+    pub fn dependent_lookup_table(&self) -> &MultiMap<IndexFileRef, IndexFileRef> {
+        &self.dependent_lookup_table
+    }
+
+    pub fn update_file_dependencies(
+        &mut self,
+        old_dependencies: Option<&Vec<IndexFileRef>>,
+        new_dependencies: Option<&Vec<IndexFileRef>>,
+        file_ref: &IndexFileRef,
+    ) {
+        if let Some(dependencies) = old_dependencies {
+            dependencies.iter().for_each(|dependency| {
+                let dependency = dependency
+                    .with_default_extension()
+                    .unwrap_or_else(|| dependency.clone());
+                if let Some(dependents) = self.dependent_lookup_table.get_vec_mut(&dependency) {
+                    dependents.retain(|dependent| dependent != file_ref);
+                    if dependents.is_empty() {
+                        self.dependent_lookup_table.remove(&dependency);
+                    }
+                }
+            });
+        }
+        if let Some(dependencies) = new_dependencies {
+            dependencies.iter().for_each(|dependency| {
+                self.dependent_lookup_table.insert(
+                    dependency
+                        .with_default_extension()
+                        .unwrap_or_else(|| dependency.clone()),
+                    file_ref.clone(),
+                );
+            });
+        }
+    }
+
     fn remove_symbols<'a>(
         &mut self,
         symbols: impl std::iter::Iterator<Item = &'a IndexSymbol>,
@@ -135,9 +206,29 @@ impl LookupTables {
             match symbol {
                 IndexSymbol::Class(class_symbol) => {
                     self.remove_symbols(class_symbol.members.iter(), file_ref);
-                    // FIXME: This needs to be updated to support multiple classes with the same name.
-                    self.class_lookup_table_mut()
-                        .remove(class_symbol.symbol_path.name());
+                    if let Some(class_symbols) = self
+                        .class_lookup_table_mut()
+                        .get_vec_mut(class_symbol.symbol_path.name())
+                    {
+                        class_symbols.retain(|s| {
+                            s.symbol_path != class_symbol.symbol_path || s.file_ref != *file_ref
+                        });
+                        if class_symbols.is_empty() {
+                            self.class_lookup_table_mut()
+                                .remove(class_symbol.symbol_path.name());
+                        }
+                    }
+                    if let Some(subclass_symbols) = self
+                        .subclass_lookup_table
+                        .get_vec_mut(&class_symbol.superclass)
+                    {
+                        subclass_symbols.retain(|s| {
+                            s.symbol_path != class_symbol.symbol_path || s.file_ref != *file_ref
+                        });
+                        if subclass_symbols.is_empty() {
+                            self.subclass_lookup_table.remove(&class_symbol.superclass);
+                        }
+                    }
                 }
                 IndexSymbol::Object(class_symbol) => {
                     self.remove_symbols(class_symbol.members.iter(), file_ref);
@@ -210,6 +301,10 @@ impl LookupTables {
                         class_symbol.symbol_path.name().clone(),
                         IndexSymbolRef::new(file_ref.clone(), class_symbol.symbol_path.clone()),
                     );
+                    self.subclass_lookup_table.insert(
+                        class_symbol.superclass.clone(),
+                        IndexSymbolRef::new(file_ref.clone(), class_symbol.symbol_path.clone()),
+                    );
                     self.add_symbols(class_symbol.members.iter(), file_ref);
                 }
                 IndexSymbol::Object(class_symbol) => {
@@ -347,6 +442,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_class_lookup_table_with_duplicate_names() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cFoo is a cObject\nEnd_Class\n",
+            "a.pkg".into(),
+            &index_ref,
+        );
+        Indexer::index_test_content(
+            "Class cFoo is a cObject\nEnd_Class\n",
+            "b.pkg".into(),
+            &index_ref,
+        );
+        let class_files = |index_ref: &IndexRef| {
+            index_ref
+                .get()
+                .lookup_tables
+                .class_lookup_table()
+                .get_vec(&"cFoo".into())
+                .map(|classes| {
+                    classes
+                        .iter()
+                        .map(|class| format!("{:?}", class.file_ref))
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert_eq!(
+            class_files(&index_ref),
+            Some(vec![
+                String::from("IndexFileRef(\"a.pkg\")"),
+                String::from("IndexFileRef(\"b.pkg\")")
+            ])
+        );
+
+        // Removing the class from one file keeps the other one.
+        Indexer::index_test_content("", "a.pkg".into(), &index_ref);
+        assert_eq!(
+            class_files(&index_ref),
+            Some(vec![String::from("IndexFileRef(\"b.pkg\")")])
+        );
+
+        Indexer::index_test_content("", "b.pkg".into(), &index_ref);
+        assert_eq!(class_files(&index_ref), None);
+    }
+
     #[test]
     fn test_method_lookup_table() {
         let index_ref = IndexRef::make_test_index_ref();
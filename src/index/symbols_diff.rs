@@ -67,6 +67,17 @@ fn diff_symbols<'a>(
                 && existing_symbol.is_matching(symbol)
             {
                 let inner_diff = match (existing_symbol, symbol) {
+                    // A class whose superclass changed is removed and added again, so that it's
+                    // listed with the subclasses of the new superclass.
+                    (
+                        IndexSymbol::Class(old_class_symbol),
+                        IndexSymbol::Class(new_class_symbol),
+                    ) if old_class_symbol.superclass != new_class_symbol.superclass => {
+                        Some(SymbolsDiff {
+                            added_symbols: vec![symbol],
+                            removed_symbols: vec![existing_symbol],
+                        })
+                    }
                     (
                         IndexSymbol::Class(old_class_symbol),
                         IndexSymbol::Class(new_class_symbol),
@@ -81,6 +92,17 @@ fn diff_symbols<'a>(
                         &old_class_symbol.members,
                         &new_class_symbol.members,
                     )),
+                    // A method whose signature changed is removed and added again, so that
+                    // anything derived from the old signature is updated.
+                    (
+                        IndexSymbol::Method(old_method_symbol),
+                        IndexSymbol::Method(new_method_symbol),
+                    ) if !old_method_symbol.has_same_signature(new_method_symbol) => {
+                        Some(SymbolsDiff {
+                            added_symbols: vec![symbol],
+                            removed_symbols: vec![existing_symbol],
+                        })
+                    }
                     (IndexSymbol::Class(_), _) => None,
                     (IndexSymbol::Object(_), _) => None,
                     (IndexSymbol::Struct(_), _) => None,
@@ -307,6 +329,74 @@ mod tests {
         assert_eq!(symbols_diff.removed_symbols.len(), 1);
     }
 
+    #[test]
+    fn test_diff_symbols_change_method_signature() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cBaseClass\n    Function Greeting String sName Returns String\n    End_Function\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+
+        let new_index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cBaseClass\n    Function Greeting String sName Integer iCount Returns String\n    End_Function\n    Procedure SayHello\n        Send Info_Box \"Hello\"\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &new_index_ref,
+        );
+
+        let orig_index = index_ref.get();
+        let new_index = new_index_ref.get();
+        let symbols_diff = orig_index
+            .files
+            .get(&IndexFileRef::from("test.pkg"))
+            .unwrap()
+            .diff_symbols(
+                new_index
+                    .files
+                    .get(&IndexFileRef::from("test.pkg"))
+                    .unwrap(),
+            );
+        assert_eq!(symbols_diff.added_symbols.len(), 1);
+        assert_eq!(symbols_diff.removed_symbols.len(), 1);
+        assert_eq!(symbols_diff.added_symbols[0].name(), &"Greeting".into());
+        assert_eq!(symbols_diff.removed_symbols[0].name(), &"Greeting".into());
+    }
+
+    #[test]
+    fn test_diff_symbols_change_superclass() {
+        let index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cBaseClass\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &index_ref,
+        );
+
+        let new_index_ref = IndexRef::make_test_index_ref();
+        Indexer::index_test_content(
+            "Class cMyClass is a cOtherBaseClass\n    Procedure SayHello\n    End_Procedure\nEnd_Class\n",
+            "test.pkg".into(),
+            &new_index_ref,
+        );
+
+        let orig_index = index_ref.get();
+        let new_index = new_index_ref.get();
+        let symbols_diff = orig_index
+            .files
+            .get(&IndexFileRef::from("test.pkg"))
+            .unwrap()
+            .diff_symbols(
+                new_index
+                    .files
+                    .get(&IndexFileRef::from("test.pkg"))
+                    .unwrap(),
+            );
+        assert_eq!(symbols_diff.added_symbols.len(), 1);
+        assert_eq!(symbols_diff.removed_symbols.len(), 1);
+        assert_eq!(symbols_diff.added_symbols[0].name(), &"cMyClass".into());
+        assert_eq!(symbols_diff.removed_symbols[0].name(), &"cMyClass".into());
+    }
+
     #[test]
     fn test_diff_symbols_add_object() {
         let index_ref = IndexRef::make_test_index_ref();
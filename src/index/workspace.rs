@@ -56,6 +56,14 @@ impl WorkspaceInfo {
     }
 
     pub fn load_from_path(path: &PathBuf) -> Self {
+        if !path.exists() {
+            log::warn!("Workspace path {:?} does not exist", path);
+            return Self {
+                root_folder: path.clone(),
+                ..Self::new()
+            };
+        }
+
         if path.is_dir()
             && let Some(file) = Self::find_first_sws(path)
         {
@@ -6,9 +6,9 @@ use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
-use crate::dataflex_document::DataFlexDocument;
+use crate::dataflex_document::{DataFlexDocument, ReferenceTarget};
 use crate::index;
-use crate::settings::Settings;
+use crate::settings::{FeatureToggles, InitializationOptions, Settings};
 
 pub struct DataFlexLanguageServer {
     inner: Arc<DataFlexLanguageServerInner>,
@@ -19,11 +19,17 @@ struct DataFlexLanguageServerInner {
     open_files: DashMap<Url, OpenFile>,
     workspace_root: OnceLock<PathBuf>,
     indexer: OnceLock<index::Indexer>,
+    initialization_options: OnceLock<InitializationOptions>,
+    versioned_document_changes: OnceLock<bool>,
+    work_done_progress: OnceLock<bool>,
     edited_files_notification: tokio::sync::Notify,
+    diagnostics_debounce: std::time::Duration,
+    pending_diagnostics: DashMap<Url, tokio::task::AbortHandle>,
 }
 
 struct OpenFile {
     doc: DataFlexDocument,
+    version: i32,
     modified: bool,
 }
 
@@ -39,15 +45,39 @@ struct IndexerProgressReporter {
     channel: tokio::sync::watch::Sender<index::IndexerState>,
 }
 
+/// How long to wait for more edits to a document before computing its diagnostics, so that a
+/// burst of keystrokes publishes the diagnostics of the final content once.
+const DIAGNOSTICS_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// The commands supported by `workspace/executeCommand`, each taking a text document position.
+const COMMANDS: [&str; 4] = [
+    "dataflex.nodeAt",
+    "dataflex.completionContext",
+    "dataflex.gotoMatchingEnd",
+    "dataflex.gotoMatchingStart",
+];
+
 impl DataFlexLanguageServer {
     pub fn new(client: Client) -> Self {
+        Self::with_diagnostics_debounce(client, DIAGNOSTICS_DEBOUNCE)
+    }
+
+    fn with_diagnostics_debounce(
+        client: Client,
+        diagnostics_debounce: std::time::Duration,
+    ) -> Self {
         Self {
             inner: Arc::new(DataFlexLanguageServerInner {
                 client,
                 open_files: DashMap::new(),
                 workspace_root: OnceLock::new(),
                 indexer: OnceLock::new(),
+                initialization_options: OnceLock::new(),
+                versioned_document_changes: OnceLock::new(),
+                work_done_progress: OnceLock::new(),
                 edited_files_notification: tokio::sync::Notify::new(),
+                diagnostics_debounce,
+                pending_diagnostics: DashMap::new(),
             }),
         }
     }
@@ -67,7 +97,10 @@ impl LanguageServer for DataFlexLanguageServer {
             .ok();
         log::info!(
             "initialize - client: {}, path: {:?}",
-            params.client_info.as_ref().unwrap().name,
+            params
+                .client_info
+                .as_ref()
+                .map_or("unknown client", |client_info| client_info.name.as_str()),
             workspace_root
         );
 
@@ -76,15 +109,43 @@ impl LanguageServer for DataFlexLanguageServer {
             .workspace_root
             .set(workspace_root.unwrap_or_default());
 
-        let semantic_tokens_options = if params
-            .capabilities
-            .text_document
-            .and_then(|t| t.semantic_tokens)
-            .is_some()
+        let options = params
+            .initialization_options
+            .and_then(|options| serde_json::from_value::<InitializationOptions>(options).ok())
+            .unwrap_or_default();
+        log::info!("initialization options = {:?}", options);
+        let features = options.features;
+        let completion_commit_characters = options.completion_commit_characters.clone();
+        _ = self.inner.initialization_options.set(options);
+        _ = self.inner.versioned_document_changes.set(
+            params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.workspace_edit.as_ref())
+                .and_then(|workspace_edit| workspace_edit.document_changes)
+                .unwrap_or(false),
+        );
+        _ = self.inner.work_done_progress.set(
+            params
+                .capabilities
+                .window
+                .as_ref()
+                .and_then(|window| window.work_done_progress)
+                .unwrap_or(false),
+        );
+
+        let semantic_tokens_options = if features.semantic_tokens
+            && params
+                .capabilities
+                .text_document
+                .and_then(|t| t.semantic_tokens)
+                .is_some()
         {
             Some(SemanticTokensServerCapabilities::from(
                 SemanticTokensOptions {
-                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                    range: Some(true),
                     legend: SemanticTokensLegend {
                         token_types: vec![
                             SemanticTokenType::KEYWORD,
@@ -96,8 +157,9 @@ impl LanguageServer for DataFlexLanguageServer {
                             SemanticTokenType::STRUCT,
                             SemanticTokenType::ENUM_MEMBER,
                             SemanticTokenType::NAMESPACE,
+                            SemanticTokenType::COMMENT,
                         ],
-                        token_modifiers: vec![],
+                        token_modifiers: vec![SemanticTokenModifier::DECLARATION],
                     },
                     ..Default::default()
                 },
@@ -116,21 +178,41 @@ impl LanguageServer for DataFlexLanguageServer {
                 )),
                 semantic_tokens_provider: semantic_tokens_options,
                 definition_provider: Some(OneOf::Left(true)),
-                completion_provider: Some(CompletionOptions {
+                completion_provider: features.completion.then(|| CompletionOptions {
                     trigger_characters: Some(vec![String::from("."), String::from(" ")]),
+                    all_commit_characters: Some(completion_commit_characters)
+                        .filter(|characters| !characters.is_empty()),
+                    resolve_provider: Some(true),
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 signature_help_provider: Some(SignatureHelpOptions {
-                    trigger_characters: Some(vec![String::from(" "), String::from("(")]),
+                    trigger_characters: Some(vec![
+                        String::from(" "),
+                        String::from("("),
+                        String::from(","),
+                    ]),
                     ..Default::default()
                 }),
                 document_highlight_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
                 document_symbol_provider: Some(OneOf::Left(true)),
                 code_lens_provider: Some(CodeLensOptions {
                     resolve_provider: Some(false),
                 }),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: COMMANDS.iter().map(|command| command.to_string()).collect(),
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             ..Default::default()
@@ -147,9 +229,19 @@ impl LanguageServer for DataFlexLanguageServer {
             .map(|path| index::WorkspaceInfo::load_from_path(path))
             .unwrap_or(index::WorkspaceInfo::new());
 
-        _ = self.inner.indexer.set(index::Indexer::new(
+        let default_options = InitializationOptions::default();
+        let options = self
+            .inner
+            .initialization_options
+            .get()
+            .unwrap_or(&default_options);
+        self.inner.set_indexer(index::Indexer::new(
             workspace_info,
-            index::IndexerConfig::new(),
+            index::IndexerConfig::new()
+                .with_pkg_directories(&options.pkg_directory_bases())
+                .with_max_directory_depth(options.max_directory_depth)
+                .with_index_cache(options.index_cache)
+                .with_file_extensions(options.file_extensions.clone()),
         ));
         if self
             .inner
@@ -193,6 +285,11 @@ impl LanguageServer for DataFlexLanguageServer {
                         .unwrap(),
                     ),
                 },
+                Registration {
+                    id: String::from("dataflex-lsp/textDocument/prepareTypeHierarchy"),
+                    method: String::from("textDocument/prepareTypeHierarchy"),
+                    register_options: Some(serde_json::json!({ "documentSelector": null })),
+                },
             ])
             .await;
 
@@ -230,19 +327,64 @@ impl LanguageServer for DataFlexLanguageServer {
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         log::trace!("Start tracking {}", params.text_document.uri);
         let file_path = params.text_document.uri.to_file_path().unwrap_or_default();
+        let indexer_index_ref = self
+            .inner
+            .indexer
+            .get()
+            .map(|indexer| indexer.get_index().clone());
+        let uses_placeholder_index = indexer_index_ref.is_none();
+        let index_ref = indexer_index_ref.unwrap_or_else(|| {
+            log::info!(
+                "Indexer not ready, opening {} with a placeholder index",
+                params.text_document.uri
+            );
+            index::IndexRef::new(index::Index::new(index::WorkspaceInfo::new()))
+        });
+        let doc = DataFlexDocument::new(file_path, &params.text_document.text, index_ref);
+        let diagnostics = self
+            .inner
+            .features()
+            .diagnostics
+            .then(|| doc.all_diagnostics());
         self.inner.open_files.insert(
-            params.text_document.uri,
-            OpenFile::new(DataFlexDocument::new(
-                file_path,
-                &params.text_document.text,
-                self.inner.indexer.get().unwrap().get_index().clone(),
-            )),
+            params.text_document.uri.clone(),
+            OpenFile::new(doc, params.text_document.version),
         );
+        // The indexer may have been set after the placeholder index was picked but before the
+        // document was inserted, so that `set_indexer()` didn't switch it to the real index.
+        if uses_placeholder_index
+            && let Some(indexer) = self.inner.indexer.get()
+            && let Some(mut open_file) = self.inner.open_files.get_mut(&params.text_document.uri)
+        {
+            open_file.doc.set_index(indexer.get_index().clone());
+        }
+
+        if let Some(diagnostics) = diagnostics {
+            self.inner
+                .client
+                .publish_diagnostics(
+                    params.text_document.uri,
+                    diagnostics,
+                    Some(params.text_document.version),
+                )
+                .await;
+        }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         self.inner.open_files.remove(&params.text_document.uri);
+        if let Some((_, pending)) = self
+            .inner
+            .pending_diagnostics
+            .remove(&params.text_document.uri)
+        {
+            pending.abort();
+        }
         log::trace!("Stop tracking {}", params.text_document.uri);
+        self.inner
+            .client
+            .publish_diagnostics(params.text_document.uri, Vec::new(), None)
+            .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -254,20 +396,26 @@ impl LanguageServer for DataFlexLanguageServer {
         let followup_edit =
             if let Some(mut open_file) = self.inner.open_files.get_mut(&params.text_document.uri) {
                 let followup_edits = open_file.doc.edit_content(&params.content_changes);
+                open_file.version = params.text_document.version;
                 open_file.modified = true;
                 self.inner.edited_files_notification.notify_one();
 
                 followup_edits.map(|edits| TextDocumentEdit {
                     text_document: OptionalVersionedTextDocumentIdentifier::new(
-                        params.text_document.uri,
+                        params.text_document.uri.clone(),
                         params.text_document.version,
                     ),
                     edits: edits.into_iter().map(OneOf::Left).collect(),
                 })
             } else {
-                None
+                return;
             };
 
+        if self.inner.features().diagnostics {
+            self.inner
+                .schedule_diagnostics(params.text_document.uri.clone());
+        }
+
         if let Some(followup_edit) = followup_edit {
             _ = self
                 .inner
@@ -289,32 +437,121 @@ impl LanguageServer for DataFlexLanguageServer {
             params.text_document.uri.as_str()
         );
 
-        let tokens = self
-            .inner
-            .open_files
-            .get(&params.text_document.uri)
-            .unwrap()
+        if !self.inner.features().semantic_tokens {
+            return Ok(None);
+        }
+        let Some(mut open_file) = self.inner.open_files.get_mut(&params.text_document.uri) else {
+            log::trace!(
+                "No open file for semantic tokens request {}",
+                params.text_document.uri.as_str()
+            );
+            return Ok(None);
+        };
+        let Some(tokens) = open_file.doc.semantic_tokens() else {
+            log::trace!(
+                "No semantic tokens available for {}",
+                params.text_document.uri.as_str()
+            );
+            return Ok(None);
+        };
+
+        Ok(Some(SemanticTokensResult::Tokens(tokens)))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        log::trace!(
+            "Got a textDocument/semanticTokensRange notification for {}",
+            params.text_document.uri.as_str()
+        );
+
+        if !self.inner.features().semantic_tokens {
+            return Ok(None);
+        }
+        let Some(open_file) = self.inner.open_files.get(&params.text_document.uri) else {
+            log::trace!(
+                "No open file for semantic tokens range request {}",
+                params.text_document.uri.as_str()
+            );
+            return Ok(None);
+        };
+        Ok(open_file
             .doc
-            .semantic_tokens_full()
-            .unwrap();
+            .semantic_tokens_range(params.range)
+            .map(|tokens| {
+                SemanticTokensRangeResult::Tokens(SemanticTokens {
+                    data: tokens,
+                    ..Default::default()
+                })
+            }))
+    }
 
-        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
-            data: tokens,
-            ..Default::default()
-        })))
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        log::trace!(
+            "Got a textDocument/semanticTokensFullDelta notification for {}",
+            params.text_document.uri.as_str()
+        );
+
+        if !self.inner.features().semantic_tokens {
+            return Ok(None);
+        }
+        let Some(mut open_file) = self.inner.open_files.get_mut(&params.text_document.uri) else {
+            log::trace!(
+                "No open file for semantic tokens delta request {}",
+                params.text_document.uri.as_str()
+            );
+            return Ok(None);
+        };
+        Ok(open_file
+            .doc
+            .semantic_tokens_delta(&params.previous_result_id))
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let locations = self
-            .inner
-            .open_files
-            .get(&params.text_document_position_params.text_document.uri)
-            .unwrap()
-            .doc
-            .find_definition(params.text_document_position_params.position);
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let find_definition = || {
+            self.inner
+                .open_files
+                .get(uri)
+                .and_then(|open_file| open_file.doc.find_definition(position))
+        };
+        let mut locations = find_definition();
+        // The definition may be in a used file that isn't indexed, like one in a directory that
+        // wasn't indexed, so index the missing dependencies and try again.
+        let dependencies = locations
+            .is_none()
+            .then(|| {
+                self.inner
+                    .open_files
+                    .get(uri)
+                    .and_then(|open_file| open_file.doc.local_index_file())
+            })
+            .flatten()
+            .map(|index_file| index_file.dependencies);
+        if let Some(dependencies) = dependencies {
+            // Searching the directories and indexing the files blocks, so keep it off the runtime.
+            let inner = self.inner.clone();
+            let indexed = tokio::task::spawn_blocking(move || {
+                inner
+                    .indexer
+                    .get()
+                    .is_some_and(|indexer| indexer.index_missing_dependencies(&dependencies))
+            })
+            .await
+            .unwrap_or(false);
+            if indexed {
+                locations = find_definition();
+            }
+        }
         if let Some(locations) = locations {
             Ok(Some(GotoDefinitionResponse::Array(locations)))
         } else {
@@ -323,26 +560,38 @@ impl LanguageServer for DataFlexLanguageServer {
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        if !self.inner.features().completion {
+            return Ok(None);
+        }
+        let (max_items, fallback) = {
+            let settings = Settings::get();
+            (settings.max_completion_items, settings.fallback_completion)
+        };
         let completions = self
             .inner
             .open_files
             .get(&params.text_document_position.text_document.uri)
-            .unwrap()
-            .doc
-            .code_completion(
-                params.text_document_position.position,
-                params
-                    .context
-                    .is_some_and(|c| c.trigger_kind == CompletionTriggerKind::TRIGGER_CHARACTER),
-            );
-        if let Some(completions) = completions {
-            Ok(Some(CompletionResponse::List(CompletionList {
-                is_incomplete: false,
-                items: completions,
-            })))
-        } else {
-            Ok(None)
+            .and_then(|open_file| {
+                open_file.doc.completion_list(
+                    params.text_document_position.position,
+                    params.context.is_some_and(|c| {
+                        c.trigger_kind == CompletionTriggerKind::TRIGGER_CHARACTER
+                    }),
+                    max_items,
+                    fallback,
+                )
+            });
+        Ok(completions.map(CompletionResponse::List))
+    }
+
+    async fn completion_resolve(&self, mut item: CompletionItem) -> Result<CompletionItem> {
+        if let Some(data) = item.data.clone()
+            && let Some(indexer) = self.inner.indexer.get()
+        {
+            item.documentation =
+                DataFlexDocument::completion_item_documentation(&indexer.get_index().get(), data);
         }
+        Ok(item)
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -350,9 +599,11 @@ impl LanguageServer for DataFlexLanguageServer {
             .inner
             .open_files
             .get(&params.text_document_position_params.text_document.uri)
-            .unwrap()
-            .doc
-            .symbol_declaration(params.text_document_position_params.position);
+            .and_then(|open_file| {
+                open_file
+                    .doc
+                    .symbol_declaration(params.text_document_position_params.position)
+            });
         if let Some(declaration) = declaration {
             Ok(Some(Hover {
                 contents: HoverContents::Scalar(declaration),
@@ -368,9 +619,11 @@ impl LanguageServer for DataFlexLanguageServer {
             .inner
             .open_files
             .get(&params.text_document_position_params.text_document.uri)
-            .unwrap()
-            .doc
-            .signature_help(params.text_document_position_params.position);
+            .and_then(|open_file| {
+                open_file
+                    .doc
+                    .signature_help(params.text_document_position_params.position)
+            });
         if let Some(signature_information) = signature_information {
             Ok(Some(SignatureHelp {
                 signatures: signature_information,
@@ -382,6 +635,128 @@ impl LanguageServer for DataFlexLanguageServer {
         }
     }
 
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = params.text_document_position;
+        let Some(target) = self
+            .inner
+            .open_files
+            .get(&position.text_document.uri)
+            .and_then(|open_file| open_file.doc.reference_target(position.position))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            self.inner
+                .reference_locations(&target, params.context.include_declaration)
+                .await,
+        ))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Some((target, range)) = self
+            .inner
+            .open_files
+            .get(&params.text_document.uri)
+            .and_then(|open_file| {
+                let target = open_file.doc.reference_target(params.position)?;
+                let range = open_file.doc.rename_range(&target, params.position)?;
+                Some((target, range))
+            })
+        else {
+            return Ok(None);
+        };
+        if let Some(message) = self.inner.rename_scope_error(&target) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(message));
+        }
+        Ok(Some(PrepareRenameResponse::Range(range)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let Some((target, error)) = self
+            .inner
+            .open_files
+            .get(&position.text_document.uri)
+            .and_then(|open_file| {
+                let target = open_file.doc.reference_target(position.position)?;
+                let error = open_file.doc.rename_error(&target, &params.new_name);
+                Some((target, error))
+            })
+        else {
+            return Ok(None);
+        };
+        if let Some(message) = error.or_else(|| self.inner.rename_scope_error(&target)) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(message));
+        }
+        Ok(Some(
+            self.inner.rename_edit(&target, &params.new_name).await,
+        ))
+    }
+
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let position = params.text_document_position_params;
+        let item = self
+            .inner
+            .open_files
+            .get(&position.text_document.uri)
+            .and_then(|open_file| open_file.doc.type_hierarchy_item(position.position));
+        Ok(item.map(|item| vec![item]))
+    }
+
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let Some(index) = self
+            .inner
+            .indexer
+            .get()
+            .map(|indexer| indexer.get_index().get())
+        else {
+            return Ok(None);
+        };
+        let Some(class) = params
+            .item
+            .data
+            .and_then(|data| serde_json::from_value::<index::IndexSymbolRef>(data).ok())
+        else {
+            return Ok(None);
+        };
+        let supertypes = index.supertypes(&class);
+        Ok(Some(
+            supertypes.iter().map(TypeHierarchyItem::from).collect(),
+        ))
+    }
+
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let Some(index) = self
+            .inner
+            .indexer
+            .get()
+            .map(|indexer| indexer.get_index().get())
+        else {
+            return Ok(None);
+        };
+        let Some(class) = params
+            .item
+            .data
+            .and_then(|data| serde_json::from_value::<index::IndexSymbolRef>(data).ok())
+        else {
+            return Ok(None);
+        };
+        let subtypes = index.subtypes(&class);
+        Ok(Some(subtypes.iter().map(TypeHierarchyItem::from).collect()))
+    }
+
     async fn document_highlight(
         &self,
         params: DocumentHighlightParams,
@@ -390,9 +765,11 @@ impl LanguageServer for DataFlexLanguageServer {
             .inner
             .open_files
             .get(&params.text_document_position_params.text_document.uri)
-            .unwrap()
-            .doc
-            .document_highlight(params.text_document_position_params.position);
+            .and_then(|open_file| {
+                open_file
+                    .doc
+                    .document_highlight(params.text_document_position_params.position)
+            });
 
         Ok(highlights)
     }
@@ -405,11 +782,9 @@ impl LanguageServer for DataFlexLanguageServer {
             .inner
             .open_files
             .get(&params.text_document.uri)
-            .unwrap()
-            .doc
-            .document_symbols();
+            .map(|open_file| open_file.doc.document_symbols());
 
-        Ok(Some(symbols.into()))
+        Ok(symbols.map(DocumentSymbolResponse::from))
     }
 
     async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
@@ -417,10 +792,56 @@ impl LanguageServer for DataFlexLanguageServer {
             .inner
             .open_files
             .get(&params.text_document.uri)
-            .unwrap()
-            .doc
-            .code_lens_items();
-        Ok(Some(code_lens_items))
+            .map(|open_file| open_file.doc.code_lens_items());
+        Ok(code_lens_items)
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let folding_ranges = self
+            .inner
+            .open_files
+            .get(&params.text_document.uri)
+            .map(|open_file| open_file.doc.folding_ranges());
+        Ok(folding_ranges)
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let selection_ranges = self
+            .inner
+            .open_files
+            .get(&params.text_document.uri)
+            .map(|open_file| open_file.doc.selection_ranges(&params.positions));
+        Ok(selection_ranges)
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let edits = self
+            .inner
+            .open_files
+            .get(&params.text_document.uri)
+            .map(|open_file| open_file.doc.formatting(&params.options));
+        Ok(edits)
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let code_actions = self
+            .inner
+            .open_files
+            .get(&params.text_document.uri)
+            .map(|open_file| {
+                open_file
+                    .doc
+                    .code_actions(&params.text_document.uri, params.range)
+            });
+        Ok(code_actions.map(|code_actions| {
+            code_actions
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction)
+                .collect()
+        }))
     }
 
     async fn symbol(
@@ -439,7 +860,8 @@ impl LanguageServer for DataFlexLanguageServer {
         let symbols = if params.query.is_empty() {
             index.top_level_class_and_object_symbols()
         } else {
-            index.matching_symbols(&params.query)
+            let mode = Settings::get().workspace_symbol_matching;
+            index.matching_symbols(&params.query, mode)
         };
 
         #[allow(deprecated)]
@@ -460,6 +882,55 @@ impl LanguageServer for DataFlexLanguageServer {
         Ok(Some(symbols))
     }
 
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        log::trace!("execute_command: {:?}", params);
+        if !COMMANDS.contains(&params.command.as_str()) {
+            return Err(tower_lsp::jsonrpc::Error::method_not_found());
+        }
+        let Some(position_params) = params
+            .arguments
+            .into_iter()
+            .next()
+            .and_then(|v| serde_json::from_value::<TextDocumentPositionParams>(v).ok())
+        else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "Expected a text document position argument",
+            ));
+        };
+        let Some(open_file) = self
+            .inner
+            .open_files
+            .get(&position_params.text_document.uri)
+        else {
+            return Ok(None);
+        };
+
+        match params.command.as_str() {
+            "dataflex.nodeAt" => {
+                let node_kind = open_file.doc.node_kind_at(position_params.position);
+                Ok(node_kind.map(|kind| serde_json::Value::String(kind.into())))
+            }
+            "dataflex.completionContext" => {
+                let context = open_file.doc.completion_context(position_params.position);
+                Ok(context.and_then(|context| serde_json::to_value(context).ok()))
+            }
+            "dataflex.gotoMatchingEnd" | "dataflex.gotoMatchingStart" => {
+                let range = open_file.doc.matching_scope_range(
+                    position_params.position,
+                    params.command == "dataflex.gotoMatchingEnd",
+                );
+                Ok(range.and_then(|range| {
+                    serde_json::to_value(Location::new(position_params.text_document.uri, range))
+                        .ok()
+                }))
+            }
+            _ => Err(tower_lsp::jsonrpc::Error::method_not_found()),
+        }
+    }
+
     async fn did_change_configuration(&self, _params: DidChangeConfigurationParams) {
         log::trace!("config changed");
         if let Ok(configs) = self
@@ -486,8 +957,18 @@ impl LanguageServer for DataFlexLanguageServer {
             .extract_if(.., |event| {
                 matches!(event.typ, FileChangeType::CHANGED | FileChangeType::CREATED)
             })
+            // Open documents are indexed from their content in the editor instead, which the
+            // client keeps up to date with changes on disk.
+            .filter(|event| !self.inner.open_files.contains_key(&event.uri))
             .filter_map(|event| event.uri.to_file_path().ok())
-            .filter(|path| path.is_dir() || index::Indexer::should_index_file(path))
+            .filter(|path| {
+                path.is_dir()
+                    || self
+                        .inner
+                        .indexer
+                        .get()
+                        .is_some_and(|indexer| indexer.should_index_file(path))
+            })
             .collect();
         let removed_files: Vec<PathBuf> = changes
             .extract_if(.., |event| matches!(event.typ, FileChangeType::DELETED))
@@ -504,48 +985,298 @@ impl LanguageServer for DataFlexLanguageServer {
     }
 }
 
-impl OpenFile {
-    fn new(doc: DataFlexDocument) -> Self {
-        Self {
-            doc,
-            modified: false,
+impl DataFlexLanguageServerInner {
+    fn set_indexer(&self, indexer: index::Indexer) {
+        let index_ref = indexer.get_index().clone();
+        if self.indexer.set(indexer).is_ok() {
+            // Documents opened before the indexer was created are using a placeholder index.
+            for mut open_file in self.open_files.iter_mut() {
+                open_file.doc.set_index(index_ref.clone());
+            }
         }
     }
-}
-
-impl IndexerCoordinator {
-    async fn watch_and_index_edited_files(inner: Arc<DataFlexLanguageServerInner>) {
-        if let Some(indexer) = inner.indexer.get() {
-            indexer.save_index()
-        }
 
-        loop {
-            inner
-                .edited_files_notification
-                .notified_debounce(std::time::Duration::from_secs(2))
-                .await;
+    /// A warning for the client when indexing didn't find any files, which usually means the
+    /// workspace root or system paths are misconfigured. Without a workspace folder only single
+    /// files are edited, so an empty index is expected.
+    fn empty_index_warning(&self) -> Option<String> {
+        let workspace_root = self
+            .workspace_root
+            .get()
+            .filter(|path| !path.as_os_str().is_empty())?;
+        let indexer = self.indexer.get()?;
+        (indexer.get_index().get().file_count() == 0).then(|| {
+            format!(
+                "No DataFlex source files were indexed in {}, check the workspace folder and the DataFlex system paths",
+                workspace_root.display()
+            )
+        })
+    }
 
-            inner
-                .open_files
-                .iter_mut()
-                .filter(|open_file| open_file.modified)
-                .for_each(|mut open_file| {
-                    if let Some(tree) = open_file.doc.tree().cloned()
-                        && let Some(file_path) = open_file.key().to_file_path().ok()
-                        && let Some(indexer) = inner.indexer.get()
-                    {
-                        let content = open_file.doc.text_content();
-                        indexer.index_modified_file_buffer(file_path, tree, content);
-                        open_file.modified = false;
-                    }
-                });
-        }
+    fn features(&self) -> FeatureToggles {
+        self.initialization_options
+            .get()
+            .map(|options| options.features)
+            .unwrap_or_default()
     }
-}
 
-impl index::IndexerObserver for IndexerCoordinator {
-    fn state_transition(&self, old_state: index::IndexerState, new_state: index::IndexerState) {
-        let Some(inner) = self.inner.upgrade() else {
+    /// The references in the open documents and in the workspace files that aren't open.
+    async fn reference_locations(
+        &self,
+        target: &ReferenceTarget,
+        include_declaration: bool,
+    ) -> Vec<Location> {
+        let mut locations: Vec<Location> = self
+            .open_files
+            .iter()
+            .flat_map(|open_file| open_file.doc.find_references(target, include_declaration))
+            .collect();
+        locations.extend(
+            self.workspace_file_references(target, include_declaration)
+                .await,
+        );
+        locations
+    }
+
+    /// Why the class or method can't be renamed, when it's declared in a file that's neither open
+    /// nor in the workspace, e.g. a system package, since the rename only edits those files.
+    fn rename_scope_error(&self, target: &ReferenceTarget) -> Option<String> {
+        let workspace_root = self
+            .workspace_root
+            .get()
+            .filter(|path| !path.as_os_str().is_empty());
+        let open_paths: Vec<PathBuf> = self
+            .open_files
+            .iter()
+            .filter_map(|open_file| open_file.key().to_file_path().ok())
+            .collect();
+        target
+            .definition_paths()
+            .find(|path| {
+                !workspace_root.is_some_and(|root| path.starts_with(root))
+                    && !open_paths.contains(path)
+            })
+            .map(|path| {
+                format!(
+                    "{} is declared in {}, which is neither open nor in the workspace",
+                    target.name,
+                    path.display()
+                )
+            })
+    }
+
+    /// The edit replacing all references to the class or method with the new name. Clients that
+    /// support document changes get edits versioned with the open documents' versions, so they
+    /// aren't applied to documents that changed in the meantime.
+    async fn rename_edit(&self, target: &ReferenceTarget, new_name: &str) -> WorkspaceEdit {
+        let mut edits: std::collections::BTreeMap<Url, Vec<TextEdit>> = Default::default();
+        for location in self.reference_locations(target, true).await {
+            edits
+                .entry(location.uri)
+                .or_default()
+                .push(TextEdit::new(location.range, new_name.into()));
+        }
+        if self
+            .versioned_document_changes
+            .get()
+            .copied()
+            .unwrap_or(false)
+        {
+            WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Edits(
+                    edits
+                        .into_iter()
+                        .map(|(uri, edits)| TextDocumentEdit {
+                            text_document: OptionalVersionedTextDocumentIdentifier {
+                                version: self.open_files.get(&uri).map(|f| f.version),
+                                uri,
+                            },
+                            edits: edits.into_iter().map(OneOf::Left).collect(),
+                        })
+                        .collect(),
+                )),
+                ..Default::default()
+            }
+        } else {
+            WorkspaceEdit::new(edits.into_iter().collect())
+        }
+    }
+
+    /// References in the workspace files that aren't open, which are read from disk and parsed on
+    /// a blocking thread. Files that don't mention the name are skipped without parsing them.
+    async fn workspace_file_references(
+        &self,
+        target: &ReferenceTarget,
+        include_declaration: bool,
+    ) -> Vec<Location> {
+        let (Some(workspace_root), Some(indexer)) = (
+            self.workspace_root
+                .get()
+                .filter(|path| !path.as_os_str().is_empty()),
+            self.indexer.get(),
+        ) else {
+            return Vec::new();
+        };
+        let index_ref = indexer.get_index().clone();
+        let open_paths: Vec<PathBuf> = self
+            .open_files
+            .iter()
+            .filter_map(|open_file| open_file.key().to_file_path().ok())
+            .collect();
+        let paths: Vec<PathBuf> = {
+            let index = index_ref.get();
+            index
+                .all_known_files()
+                .iter()
+                .filter_map(|file_ref| index.find_file_path(file_ref))
+                .filter(|path| path.starts_with(workspace_root) && !open_paths.contains(path))
+                .cloned()
+                .collect()
+        };
+        let target = target.clone();
+        tokio::task::spawn_blocking(move || {
+            let name = target.name.to_string().to_lowercase();
+            paths
+                .into_iter()
+                .filter_map(|path| {
+                    let content = std::fs::read_to_string(&path).ok()?;
+                    content
+                        .to_lowercase()
+                        .contains(&name)
+                        .then(|| DataFlexDocument::new(path, &content, index_ref.clone()))
+                })
+                .flat_map(|doc| doc.find_references(&target, include_declaration))
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Recomputes and publishes the diagnostics of the open files that use the file, since changes
+    /// to its classes and methods affect them, e.g. when a superclass is renamed.
+    async fn publish_dependent_file_diagnostics(&self, path: &PathBuf) {
+        let Some(indexer) = self.indexer.get() else {
+            return;
+        };
+        if !self.features().diagnostics {
+            return;
+        }
+        let dependents = indexer
+            .get_index()
+            .get()
+            .dependent_files(&index::IndexFileRef::from(path));
+        let diagnostics: Vec<(Url, Vec<Diagnostic>)> = self
+            .open_files
+            .iter()
+            .filter(|file| {
+                file.key().to_file_path().is_ok_and(|file_path| {
+                    dependents.contains(&index::IndexFileRef::from(&file_path))
+                })
+            })
+            .map(|file| (file.key().clone(), file.doc.all_diagnostics()))
+            .collect();
+        for (uri, diagnostics) in diagnostics {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+    }
+
+    /// Recomputes and publishes the diagnostics of all open files, e.g. when indexing has changed
+    /// which classes are known.
+    async fn publish_open_file_diagnostics(&self) {
+        if !self.features().diagnostics {
+            return;
+        }
+        let diagnostics: Vec<(Url, Vec<Diagnostic>)> = self
+            .open_files
+            .iter()
+            .map(|file| (file.key().clone(), file.doc.all_diagnostics()))
+            .collect();
+        for (uri, diagnostics) in diagnostics {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
+    }
+
+    /// Publishes the diagnostics of an open file once it hasn't been edited for the debounce
+    /// delay, replacing any diagnostics already scheduled for it. The diagnostics are stamped with
+    /// the document version they were computed from.
+    fn schedule_diagnostics(self: &Arc<Self>, uri: Url) {
+        let inner = self.clone();
+        let task_uri = uri.clone();
+        let (registered_sender, registered) = tokio::sync::oneshot::channel();
+        let task = tokio::spawn(async move {
+            // Wait for the task to be registered, so that it doesn't forget itself too early.
+            _ = registered.await;
+            tokio::time::sleep(inner.diagnostics_debounce).await;
+            let Some((diagnostics, version)) = inner
+                .open_files
+                .get(&task_uri)
+                .map(|open_file| (open_file.doc.all_diagnostics(), open_file.version))
+            else {
+                return;
+            };
+            // Forget the task unless it has already been replaced by one for a later edit.
+            inner
+                .pending_diagnostics
+                .remove_if(&task_uri, |_, pending| pending.id() == tokio::task::id());
+            inner
+                .client
+                .publish_diagnostics(task_uri, diagnostics, Some(version))
+                .await;
+        });
+        if let Some(previous) = self.pending_diagnostics.insert(uri, task.abort_handle()) {
+            previous.abort();
+        }
+        _ = registered_sender.send(());
+    }
+}
+
+impl OpenFile {
+    fn new(doc: DataFlexDocument, version: i32) -> Self {
+        Self {
+            doc,
+            version,
+            modified: false,
+        }
+    }
+}
+
+impl IndexerCoordinator {
+    async fn watch_and_index_edited_files(inner: Arc<DataFlexLanguageServerInner>) {
+        if let Some(indexer) = inner.indexer.get() {
+            indexer.save_index()
+        }
+
+        loop {
+            inner
+                .edited_files_notification
+                .notified_debounce(std::time::Duration::from_secs(2))
+                .await;
+
+            inner
+                .open_files
+                .iter_mut()
+                .filter(|open_file| open_file.modified)
+                .for_each(|mut open_file| {
+                    if let Some(tree) = open_file.doc.tree().cloned()
+                        && let Some(file_path) = open_file.key().to_file_path().ok()
+                        && let Some(indexer) = inner.indexer.get()
+                    {
+                        let content = open_file.doc.text_content();
+                        indexer.index_modified_file_buffer(file_path, tree, content);
+                        open_file.modified = false;
+                    }
+                });
+        }
+    }
+}
+
+impl index::IndexerObserver for IndexerCoordinator {
+    fn state_transition(&self, old_state: index::IndexerState, new_state: index::IndexerState) {
+        let Some(inner) = self.inner.upgrade() else {
             return;
         };
 
@@ -565,19 +1296,82 @@ impl index::IndexerObserver for IndexerCoordinator {
 
                 self.tasks.lock().unwrap().spawn_on(
                     async move {
+                        if let Some(message) = inner.empty_index_warning() {
+                            inner
+                                .client
+                                .show_message(MessageType::WARNING, message)
+                                .await;
+                        }
                         _ = inner.client.semantic_tokens_refresh().await;
                         _ = inner.client.code_lens_refresh().await;
+                        inner.publish_open_file_diagnostics().await;
                         Self::watch_and_index_edited_files(inner).await;
                     },
                     &self.runtime,
                 );
             }
+            (index::IndexerState::Indexing, index::IndexerState::Inactive) => {
+                // Files changed on disk may add or remove classes and methods used by the open
+                // documents, which affects their highlighting, code lenses and diagnostics.
+                for mut file in inner.open_files.iter_mut() {
+                    file.doc.update_syntax_map();
+                }
+
+                self.tasks.lock().unwrap().spawn_on(
+                    async move {
+                        _ = inner.client.semantic_tokens_refresh().await;
+                        _ = inner.client.code_lens_refresh().await;
+                        inner.publish_open_file_diagnostics().await;
+                    },
+                    &self.runtime,
+                );
+            }
             (_, index::IndexerState::Stopped) => {
                 self.tasks.lock().unwrap().abort_all();
             }
             _ => (),
         }
     }
+
+    fn file_buffer_indexed(&self, path: &PathBuf) {
+        let Some(inner) = self.inner.upgrade() else {
+            return;
+        };
+        let path = path.clone();
+        self.tasks.lock().unwrap().spawn_on(
+            async move {
+                // Edits only updated the tokens of the changed rows, before the classes and
+                // methods they declare were indexed, which affects the highlighting of other rows.
+                if let Ok(uri) = Url::from_file_path(&path)
+                    && let Some(mut open_file) = inner.open_files.get_mut(&uri)
+                {
+                    open_file.doc.update_syntax_map();
+                }
+                _ = inner.client.semantic_tokens_refresh().await;
+                inner.publish_dependent_file_diagnostics(&path).await;
+            },
+            &self.runtime,
+        );
+    }
+
+    fn workspace_root_missing(&self, path: &PathBuf) {
+        let Some(inner) = self.inner.upgrade() else {
+            return;
+        };
+        let message = format!(
+            "Workspace folder {} does not exist, only system packages are indexed",
+            path.display()
+        );
+        self.tasks.lock().unwrap().spawn_on(
+            async move {
+                inner
+                    .client
+                    .show_message(MessageType::WARNING, message)
+                    .await;
+            },
+            &self.runtime,
+        );
+    }
 }
 
 impl IndexerProgressReporter {
@@ -601,6 +1395,13 @@ impl IndexerProgressReporter {
         inner: Weak<DataFlexLanguageServerInner>,
         mut channel: tokio::sync::watch::Receiver<index::IndexerState>,
     ) {
+        // Progress tokens can only be created by clients that support server initiated progress.
+        if !inner
+            .upgrade()
+            .is_some_and(|inner| inner.work_done_progress.get() == Some(&true))
+        {
+            return;
+        }
         let mut reporting: Option<usize> = None;
         let timeout_duration = std::time::Duration::from_millis(250);
         loop {
@@ -629,7 +1430,7 @@ impl IndexerProgressReporter {
                                 .map(|indexer| indexer.indexed_file_count())
                                 .unwrap_or_default(),
                         );
-                        Self::start_report(&inner).await;
+                        Self::start_report(&inner, state).await;
                     } else {
                         break;
                     }
@@ -638,11 +1439,14 @@ impl IndexerProgressReporter {
                     if reporting.is_some() =>
                 {
                     if let Some(inner) = inner.upgrade()
-                        && let Some(file_count) = inner.indexer.get().map(|indexer| {
-                            indexer.indexed_file_count() - reporting.as_ref().unwrap()
-                        })
+                        && let Some(indexer) = inner.indexer.get()
                     {
-                        Self::report_progress(&inner, file_count).await;
+                        let file_count = indexer.indexed_file_count() - reporting.as_ref().unwrap();
+                        // Only the initial indexing counts the files to index up front.
+                        let progress = (state == index::IndexerState::InitialIndexing)
+                            .then(|| indexer.indexing_progress())
+                            .flatten();
+                        Self::report_progress(&inner, file_count, progress).await;
                     } else {
                         break;
                     }
@@ -671,7 +1475,7 @@ impl IndexerProgressReporter {
         }
     }
 
-    async fn start_report(inner: &DataFlexLanguageServerInner) {
+    async fn start_report(inner: &DataFlexLanguageServerInner, state: index::IndexerState) {
         _ = inner
             .client
             .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
@@ -687,7 +1491,7 @@ impl IndexerProgressReporter {
                     WorkDoneProgressBegin {
                         title: "DataFlex-LSP".into(),
                         message: Some("Indexing...".into()),
-                        percentage: None,
+                        percentage: (state == index::IndexerState::InitialIndexing).then_some(0),
                         cancellable: Some(false),
                     },
                 )),
@@ -695,21 +1499,47 @@ impl IndexerProgressReporter {
             .await;
     }
 
-    async fn report_progress(inner: &DataFlexLanguageServerInner, file_count: usize) {
+    async fn report_progress(
+        inner: &DataFlexLanguageServerInner,
+        file_count: usize,
+        progress: Option<index::IndexingProgress>,
+    ) {
         _ = inner
             .client
             .send_notification::<notification::Progress>(ProgressParams {
                 token: Self::indexing_progress_token(),
                 value: ProgressParamsValue::WorkDone(WorkDoneProgress::Report(
-                    WorkDoneProgressReport {
-                        message: Some(format!("Indexing {file_count} files...")),
-                        ..Default::default()
-                    },
+                    Self::progress_report(file_count, progress),
                 )),
             })
             .await;
     }
 
+    fn progress_report(
+        file_count: usize,
+        progress: Option<index::IndexingProgress>,
+    ) -> WorkDoneProgressReport {
+        match progress {
+            Some(progress) => WorkDoneProgressReport {
+                message: Some(format!(
+                    "Indexing {} {}/{} files...",
+                    match progress.phase {
+                        index::IndexingPhase::SystemPaths => "system packages",
+                        index::IndexingPhase::Workspace => "workspace",
+                    },
+                    progress.indexed_file_count,
+                    progress.total_file_count
+                )),
+                percentage: Some(progress.percentage()),
+                ..Default::default()
+            },
+            None => WorkDoneProgressReport {
+                message: Some(format!("Indexing {file_count} files...")),
+                ..Default::default()
+            },
+        }
+    }
+
     async fn end_report(inner: &DataFlexLanguageServerInner) {
         _ = inner
             .client
@@ -740,3 +1570,1057 @@ impl NotifyDebounce for tokio::sync::Notify {
         {}
     }
 }
+
+#[cfg(test)]
+mod test_client;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_client::TestClient;
+
+    #[tokio::test]
+    async fn test_semantic_tokens_full_for_closed_file() {
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let result = service
+            .inner()
+            .semantic_tokens_full(SemanticTokensParams {
+                text_document: TextDocumentIdentifier::new(
+                    Url::parse("file:///closed.pkg").unwrap(),
+                ),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await;
+        assert_eq!(result, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_completion_capability() {
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let initialize_params = |initialization_options| InitializeParams {
+            workspace_folders: Some(vec![WorkspaceFolder {
+                uri: Url::parse("file:///workspace").unwrap(),
+                name: String::from("workspace"),
+            }]),
+            client_info: Some(ClientInfo {
+                name: String::from("test"),
+                version: None,
+            }),
+            initialization_options,
+            ..Default::default()
+        };
+
+        let result = service
+            .inner()
+            .initialize(initialize_params(Some(
+                serde_json::json!({ "completion": false }),
+            )))
+            .await
+            .unwrap();
+        assert!(result.capabilities.completion_provider.is_none());
+        assert!(result.capabilities.hover_provider.is_some());
+
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let result = service
+            .inner()
+            .initialize(initialize_params(None))
+            .await
+            .unwrap();
+        assert!(result.capabilities.completion_provider.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_execute_unknown_command() {
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let result = service
+            .inner()
+            .execute_command(ExecuteCommandParams {
+                command: String::from("dataflex.unknown"),
+                arguments: Vec::new(),
+                work_done_progress_params: Default::default(),
+            })
+            .await;
+        assert_eq!(result, Err(tower_lsp::jsonrpc::Error::method_not_found()));
+    }
+
+    #[tokio::test]
+    async fn test_initialize_without_client_info() {
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let result = service
+            .inner()
+            .initialize(InitializeParams {
+                workspace_folders: Some(vec![WorkspaceFolder {
+                    uri: Url::parse("file:///workspace").unwrap(),
+                    name: String::from("workspace"),
+                }]),
+                client_info: None,
+                ..Default::default()
+            })
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_completion_commit_characters() {
+        let initialize = |initialization_options| async move {
+            let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+            service
+                .inner()
+                .initialize(InitializeParams {
+                    workspace_folders: Some(vec![WorkspaceFolder {
+                        uri: Url::parse("file:///workspace").unwrap(),
+                        name: String::from("workspace"),
+                    }]),
+                    client_info: Some(ClientInfo {
+                        name: String::from("test"),
+                        version: None,
+                    }),
+                    initialization_options,
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .capabilities
+                .completion_provider
+                .and_then(|completion| completion.all_commit_characters)
+        };
+
+        assert_eq!(initialize(None).await, Some(vec![String::from(" ")]));
+        assert_eq!(
+            initialize(Some(
+                serde_json::json!({ "completionCommitCharacters": [" ", "("] })
+            ))
+            .await,
+            Some(vec![String::from(" "), String::from("(")])
+        );
+        assert_eq!(
+            initialize(Some(
+                serde_json::json!({ "completionCommitCharacters": [] })
+            ))
+            .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_index_warning() {
+        struct IgnoreStateTransitions;
+
+        impl index::IndexerObserver for IgnoreStateTransitions {
+            fn state_transition(&self, _: index::IndexerState, _: index::IndexerState) {}
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-empty-workspace-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let server = service.inner();
+        _ = server.inner.workspace_root.set(dir.clone());
+        server.inner.set_indexer(index::Indexer::new(
+            index::WorkspaceInfo::load_from_path(&dir),
+            index::IndexerConfig::new(),
+        ));
+        server
+            .inner
+            .indexer
+            .get()
+            .unwrap()
+            .index_now(IgnoreStateTransitions);
+        _ = std::fs::remove_dir_all(&dir);
+        assert!(
+            server
+                .inner
+                .empty_index_warning()
+                .is_some_and(|message| message.contains(&dir.display().to_string()))
+        );
+
+        // Single-file mode, without a workspace folder.
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let server = service.inner();
+        _ = server.inner.workspace_root.set(PathBuf::new());
+        server.inner.set_indexer(index::Indexer::new(
+            index::WorkspaceInfo::new(),
+            index::IndexerConfig::new(),
+        ));
+        assert_eq!(server.inner.empty_index_warning(), None);
+    }
+
+    #[tokio::test]
+    async fn test_did_open_with_test_client() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-test-client-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::new();
+        let result = client.initialize(&dir).await.unwrap();
+        assert!(result["capabilities"]["completionProvider"].is_object());
+        assert_eq!(
+            client.wait_for("window/logMessage").await.unwrap()["message"],
+            "server initialized!"
+        );
+
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\nEnd_Class\n",
+                    }
+                }),
+            )
+            .await;
+        assert!(client.server().inner.open_files.contains_key(&uri));
+        let diagnostics = client
+            .wait_for("textDocument/publishDiagnostics")
+            .await
+            .unwrap();
+        assert_eq!(diagnostics["uri"], uri.as_str());
+        assert_eq!(
+            diagnostics["diagnostics"][0]["message"],
+            "Mismatched End_Class, expected End_Object"
+        );
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_rename_with_versioned_document_changes() {
+        let dir = std::env::temp_dir().join(format!("dataflex-lsp-rename-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        let rename_params = serde_json::json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": 8 },
+            "newName": "cBar",
+        });
+        let text_edit = serde_json::json!({
+            "range": {
+                "start": { "line": 0, "character": 6 },
+                "end": { "line": 0, "character": 10 },
+            },
+            "newText": "cBar",
+        });
+
+        for document_changes in [true, false] {
+            let mut client = TestClient::new();
+            client
+                .initialize_with_capabilities(
+                    &dir,
+                    serde_json::json!({
+                        "workspace": { "workspaceEdit": { "documentChanges": document_changes } }
+                    }),
+                )
+                .await
+                .unwrap();
+            client
+                .notify(
+                    "textDocument/didOpen",
+                    serde_json::json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "dataflex",
+                            "version": 1,
+                            "text": "",
+                        }
+                    }),
+                )
+                .await;
+            client
+                .notify(
+                    "textDocument/didChange",
+                    serde_json::json!({
+                        "textDocument": { "uri": uri, "version": 2 },
+                        "contentChanges": [{ "text": "Class cFoo is a cObject\nEnd_Class\n" }],
+                    }),
+                )
+                .await;
+
+            let edit = client
+                .request("textDocument/rename", rename_params.clone())
+                .await
+                .unwrap();
+            if document_changes {
+                assert_eq!(
+                    edit["documentChanges"],
+                    serde_json::json!([{
+                        "textDocument": { "uri": uri, "version": 2 },
+                        "edits": [text_edit],
+                    }])
+                );
+                assert!(edit.get("changes").is_none());
+            } else {
+                assert_eq!(
+                    edit["changes"][uri.as_str()],
+                    serde_json::json!([text_edit])
+                );
+                assert!(edit.get("documentChanges").is_none());
+            }
+
+            client.shutdown().await;
+        }
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_signature_help() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-signature-help-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        let mut client = TestClient::new();
+        let result = client.initialize(&dir).await.unwrap();
+        assert_eq!(
+            result["capabilities"]["signatureHelpProvider"]["triggerCharacters"],
+            serde_json::json!([" ", "(", ","])
+        );
+
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\n    Function MyMethod String sArg1 Integer iArg2 Returns Integer\n    End_Function\nEnd_Object\n\nInteger iTest\nMove (MyMethod(oTest, \"test\", 1234)) to iTest\n",
+                    }
+                }),
+            )
+            .await;
+        let signature_help = client
+            .request(
+                "textDocument/signatureHelp",
+                serde_json::json!({
+                    "textDocument": { "uri": uri },
+                    "position": { "line": 6, "character": 30 },
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            signature_help["signatures"][0]["label"],
+            "Function MyMethod String sArg1 Integer iArg2 Returns Integer"
+        );
+        assert_eq!(signature_help["signatures"][0]["activeParameter"], 1);
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_goto_definition_of_unindexed_file_dependency() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-unindexed-dependency-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+
+        // A system package directory that wasn't indexed.
+        let system_dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-unindexed-system-{}",
+            std::process::id()
+        ));
+        let path = system_dir.join("Pkg").join("cUnindexed.pkg");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "Class cUnindexed is a cObject\nEnd_Class\n").unwrap();
+        client
+            .server()
+            .inner
+            .indexer
+            .get()
+            .unwrap()
+            .get_index()
+            .get_mut()
+            .set_system_paths(vec![system_dir.clone()]);
+
+        let uri = Url::from_file_path(dir.join("test.vw")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Use cUnindexed\n",
+                    }
+                }),
+            )
+            .await;
+        let locations = client
+            .request(
+                "textDocument/definition",
+                serde_json::json!({
+                    "textDocument": { "uri": uri },
+                    "position": { "line": 0, "character": 6 },
+                }),
+            )
+            .await;
+        assert_eq!(
+            locations,
+            Some(serde_json::json!([{
+                "uri": Url::from_file_path(&path).unwrap(),
+                "range": Range::default(),
+            }]))
+        );
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+        _ = std::fs::remove_dir_all(&system_dir);
+    }
+
+    #[tokio::test]
+    async fn test_prepare_rename_and_rename_errors() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-prepare-rename-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+
+        // A class declared outside of the workspace, like a system package.
+        let system_path = std::env::temp_dir()
+            .join(format!("dataflex-lsp-system-{}", std::process::id()))
+            .join("cSystem.pkg");
+        index::Indexer::index_test_content(
+            "Class cSystem is a cObject\nEnd_Class\n",
+            system_path,
+            &client.server().inner.indexer.get().unwrap().get_index(),
+        );
+
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Use cSystem.pkg\nClass cTest is a cSystem\n    Procedure SayHello\n    End_Procedure\n\n    Procedure SayGoodbye\n    End_Procedure\nEnd_Class\n",
+                    }
+                }),
+            )
+            .await;
+        let position = |line: u32, character: u32| {
+            serde_json::json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+            })
+        };
+
+        assert_eq!(
+            client
+                .request("textDocument/prepareRename", position(2, 16))
+                .await,
+            Some(serde_json::json!({
+                "start": { "line": 2, "character": 14 },
+                "end": { "line": 2, "character": 22 },
+            }))
+        );
+        assert_eq!(
+            client
+                .request("textDocument/prepareRename", position(1, 20))
+                .await,
+            None
+        );
+
+        let mut rename_params = position(2, 16);
+        rename_params["newName"] = "SayGoodbye".into();
+        assert_eq!(
+            client.request("textDocument/rename", rename_params).await,
+            None
+        );
+        let mut rename_params = position(1, 20);
+        rename_params["newName"] = "cOther".into();
+        assert_eq!(
+            client.request("textDocument/rename", rename_params).await,
+            None
+        );
+        let mut rename_params = position(2, 16);
+        rename_params["newName"] = "SayHowdy".into();
+        assert!(
+            client
+                .request("textDocument/rename", rename_params)
+                .await
+                .is_some()
+        );
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_edits_and_semantic_tokens() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-concurrent-edits-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+        let path = dir.join("test.pkg");
+        let uri = Url::from_file_path(&path).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\nEnd_Object\n",
+                    }
+                }),
+            )
+            .await;
+
+        let server = client.server();
+        let contents: Vec<String> = (0..20)
+            .map(|i| {
+                format!(
+                    "Object oTest{i} is a cObject\n    Procedure Test{i}\n    End_Procedure\nEnd_Object\n"
+                )
+            })
+            .collect();
+        let tokens_params = || SemanticTokensParams {
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+        };
+        let edits = contents.iter().enumerate().map(|(i, content)| {
+            server.did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), i as i32 + 2),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: content.clone(),
+                }],
+            })
+        });
+        let requests = (0..20).map(|_| server.semantic_tokens_full(tokens_params()));
+        let (_, results) = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            futures::future::join(
+                futures::future::join_all(edits),
+                futures::future::join_all(requests),
+            ),
+        )
+        .await
+        .expect("interleaved edits and token requests should not deadlock");
+        assert!(results.iter().all(|result| matches!(result, Ok(Some(_)))));
+
+        let Ok(Some(SemanticTokensResult::Tokens(tokens))) =
+            server.semantic_tokens_full(tokens_params()).await
+        else {
+            panic!("expected semantic tokens");
+        };
+        let index_ref = server.inner.indexer.get().unwrap().get_index().clone();
+        let mut doc = DataFlexDocument::new(path, contents.last().unwrap(), index_ref);
+        assert_eq!(tokens.data, doc.semantic_tokens().unwrap().data);
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_publish_open_file_diagnostics() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-republish-diagnostics-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\nEnd_Class\n",
+                    }
+                }),
+            )
+            .await;
+        assert!(
+            client
+                .wait_for("textDocument/publishDiagnostics")
+                .await
+                .is_some()
+        );
+
+        // Diagnostics recomputed after indexing aren't tied to a document version.
+        client.server().inner.publish_open_file_diagnostics().await;
+        let mut republished = None;
+        for _ in 0..500 {
+            republished = client
+                .received("textDocument/publishDiagnostics")
+                .into_iter()
+                .find(|params| params["version"].is_null());
+            if republished.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let republished = republished.unwrap();
+        assert_eq!(republished["uri"], uri.as_str());
+        assert_eq!(
+            republished["diagnostics"][0]["message"],
+            "Mismatched End_Class, expected End_Object"
+        );
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_initial_indexing_progress() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-indexing-progress-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+        for file_number in 0..20 {
+            std::fs::write(
+                dir.join(format!("File{file_number}.pkg")),
+                crate::benchmarks::synthetic_package(file_number, 5, 10),
+            )
+            .unwrap();
+        }
+
+        let mut client = TestClient::new();
+        client
+            .initialize_with_capabilities(
+                &dir,
+                serde_json::json!({"window": {"workDoneProgress": true}}),
+            )
+            .await
+            .unwrap();
+        let mut progress = Vec::new();
+        for _ in 0..500 {
+            progress = client.received("$/progress");
+            if progress
+                .iter()
+                .any(|params| params["value"]["kind"] == "end")
+            {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!client.received("window/workDoneProgress/create").is_empty());
+        let kinds: Vec<&str> = progress
+            .iter()
+            .map(|params| params["value"]["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds.first(), Some(&"begin"));
+        assert_eq!(kinds.last(), Some(&"end"));
+        assert!(progress.iter().all(|params| params["token"] == "Indexing"));
+        client.shutdown().await;
+
+        // Without client support, no progress is reported.
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+        for _ in 0..500 {
+            if client.server().inner.indexer.get().is_some_and(|indexer| {
+                indexer
+                    .indexing_progress()
+                    .is_some_and(|progress| progress.done)
+            }) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(client.received("window/workDoneProgress/create").is_empty());
+        assert!(client.received("$/progress").is_empty());
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_debounced_diagnostics() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-debounced-diagnostics-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client =
+            TestClient::with_diagnostics_debounce(std::time::Duration::from_millis(100));
+        client.initialize(&dir).await.unwrap();
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\nEnd_Object\n",
+                    }
+                }),
+            )
+            .await;
+
+        let contents = [
+            "Object oTest is a cObject\nEnd_\n",
+            "Object oTest is a cObject\nEnd_Cl\n",
+            "Object oTest is a cObject\nEnd_Class\n",
+        ];
+        for (version, text) in (2..).zip(contents) {
+            client
+                .notify(
+                    "textDocument/didChange",
+                    serde_json::json!({
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": [{ "text": text }],
+                    }),
+                )
+                .await;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let published: Vec<serde_json::Value> = client
+            .received("textDocument/publishDiagnostics")
+            .into_iter()
+            .filter(|params| {
+                params["version"]
+                    .as_i64()
+                    .is_some_and(|version| version > 1)
+            })
+            .collect();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0]["uri"], uri.as_str());
+        assert_eq!(published[0]["version"], 4);
+        assert_eq!(
+            published[0]["diagnostics"][0]["message"],
+            "Mismatched End_Class, expected End_Object"
+        );
+        assert!(client.server().inner.pending_diagnostics.is_empty());
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_without_debounce() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-diagnostics-without-debounce-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::with_diagnostics_debounce(std::time::Duration::ZERO);
+        client.initialize(&dir).await.unwrap();
+        let uri = Url::from_file_path(dir.join("test.pkg")).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Object oTest is a cObject\nEnd_Object\n",
+                    }
+                }),
+            )
+            .await;
+        client
+            .notify(
+                "textDocument/didChange",
+                serde_json::json!({
+                    "textDocument": { "uri": uri, "version": 2 },
+                    "contentChanges": [{ "text": "Object oTest is a cObject\nEnd_Class\n" }],
+                }),
+            )
+            .await;
+
+        let mut published = false;
+        for _ in 0..500 {
+            published = client
+                .received("textDocument/publishDiagnostics")
+                .iter()
+                .any(|params| params["version"] == 2);
+            if published {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(published);
+        // The task forgets itself even when it runs right away.
+        assert!(client.server().inner.pending_diagnostics.is_empty());
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_watched_file_changes() {
+        let dir =
+            std::env::temp_dir().join(format!("dataflex-lsp-watched-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+        let index_ref = client
+            .server()
+            .inner
+            .indexer
+            .get()
+            .unwrap()
+            .get_index()
+            .clone();
+        // Files written during the initial indexing could be indexed by it.
+        for _ in 0..500 {
+            let indexer = client.server().inner.indexer.get().unwrap();
+            if indexer.indexing_progress().is_some_and(|progress| {
+                progress.phase == index::IndexingPhase::Workspace && progress.done
+            }) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let wait_for_class = async |name: &str, known: bool| {
+            for _ in 0..500 {
+                if index_ref.get().is_known_class(&name.into()) == known {
+                    return true;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            false
+        };
+
+        let external_path = dir.join("external.pkg");
+        let external_uri = Url::from_file_path(&external_path).unwrap();
+        std::fs::write(&external_path, "Class cExternal is a cObject\nEnd_Class\n").unwrap();
+        client
+            .notify(
+                "workspace/didChangeWatchedFiles",
+                serde_json::json!({ "changes": [{ "uri": external_uri, "type": 1 }] }),
+            )
+            .await;
+        assert!(wait_for_class("cExternal", true).await);
+
+        std::fs::remove_file(&external_path).unwrap();
+        client
+            .notify(
+                "workspace/didChangeWatchedFiles",
+                serde_json::json!({ "changes": [{ "uri": external_uri, "type": 3 }] }),
+            )
+            .await;
+        assert!(wait_for_class("cExternal", false).await);
+
+        // A change on disk to an open document doesn't replace the indexed editor content.
+        let open_path = dir.join("open.pkg");
+        let open_uri = Url::from_file_path(&open_path).unwrap();
+        std::fs::write(&open_path, "Class cOnDisk is a cObject\nEnd_Class\n").unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": open_uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": "Class cInEditor is a cObject\nEnd_Class\n",
+                    }
+                }),
+            )
+            .await;
+        let other_path = dir.join("other.pkg");
+        std::fs::write(&other_path, "Class cOther is a cObject\nEnd_Class\n").unwrap();
+        client
+            .notify(
+                "workspace/didChangeWatchedFiles",
+                serde_json::json!({ "changes": [
+                    { "uri": open_uri, "type": 2 },
+                    { "uri": Url::from_file_path(&other_path).unwrap(), "type": 1 },
+                ] }),
+            )
+            .await;
+        assert!(wait_for_class("cOther", true).await);
+        assert!(!index_ref.get().is_known_class(&"cOnDisk".into()));
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_publish_dependent_file_diagnostics() {
+        let dir = std::env::temp_dir().join(format!(
+            "dataflex-lsp-dependent-diagnostics-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("test.sws"), "{\"df\": 24.0}").unwrap();
+
+        let mut client = TestClient::new();
+        client.initialize(&dir).await.unwrap();
+        let index_ref = client
+            .server()
+            .inner
+            .indexer
+            .get()
+            .unwrap()
+            .get_index()
+            .clone();
+        let base_path = dir.join("base.pkg");
+        let derived_path = dir.join("derived.pkg");
+        let derived_content = "Use base.pkg\nClass cDerived is a cBase\nEnd_Class\n";
+        index::Indexer::index_test_content(
+            "Class cBase is a cObject\nEnd_Class\n",
+            base_path.clone(),
+            &index_ref,
+        );
+        index::Indexer::index_test_content(derived_content, derived_path.clone(), &index_ref);
+
+        let uri = Url::from_file_path(&derived_path).unwrap();
+        client
+            .notify(
+                "textDocument/didOpen",
+                serde_json::json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dataflex",
+                        "version": 1,
+                        "text": derived_content,
+                    }
+                }),
+            )
+            .await;
+        assert!(
+            client
+                .wait_for("textDocument/publishDiagnostics")
+                .await
+                .is_some()
+        );
+
+        // Renaming the class in the used file leaves the open file's superclass unknown.
+        index::Indexer::index_test_content(
+            "Class cRenamedBase is a cObject\nEnd_Class\n",
+            base_path.clone(),
+            &index_ref,
+        );
+        client
+            .server()
+            .inner
+            .publish_dependent_file_diagnostics(&base_path)
+            .await;
+        let mut republished = None;
+        for _ in 0..500 {
+            republished = client
+                .received("textDocument/publishDiagnostics")
+                .into_iter()
+                .find(|params| params["version"].is_null());
+            if republished.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let republished = republished.unwrap();
+        assert_eq!(republished["uri"], uri.as_str());
+        assert_eq!(
+            republished["diagnostics"][0]["message"],
+            "Unknown class cBase"
+        );
+
+        client.shutdown().await;
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_indexing_progress_report() {
+        let report = IndexerProgressReporter::progress_report(
+            12,
+            Some(index::IndexingProgress {
+                phase: index::IndexingPhase::SystemPaths,
+                indexed_file_count: 12,
+                total_file_count: 48,
+                earlier_file_count: 0,
+                later_file_count: 0,
+                done: false,
+            }),
+        );
+        assert_eq!(
+            report.message.as_deref(),
+            Some("Indexing system packages 12/48 files...")
+        );
+        assert_eq!(report.percentage, Some(25));
+
+        let report = IndexerProgressReporter::progress_report(3, None);
+        assert_eq!(report.message.as_deref(), Some("Indexing 3 files..."));
+        assert_eq!(report.percentage, None);
+    }
+
+    #[tokio::test]
+    async fn test_did_open_before_indexer() {
+        let (service, _socket) = tower_lsp::LspService::new(DataFlexLanguageServer::new);
+        let server = service.inner();
+        let uri = Url::parse("file:///test.pkg").unwrap();
+        server
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    String::from("dataflex"),
+                    1,
+                    String::from("Object oTest is a cMyClass\nEnd_Object\n"),
+                ),
+            })
+            .await;
+        assert!(server.inner.open_files.contains_key(&uri));
+
+        let indexer = index::Indexer::new(index::WorkspaceInfo::new(), index::IndexerConfig::new());
+        index::Indexer::index_test_content(
+            "Class cMyClass is a cObject\nEnd_Class\n",
+            "class.pkg".into(),
+            indexer.get_index(),
+        );
+        server.inner.set_indexer(indexer);
+
+        let hover = server
+            .hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri),
+                    Position::new(0, 20),
+                ),
+                work_done_progress_params: Default::default(),
+            })
+            .await;
+        assert!(hover.is_ok_and(|hover| hover.is_some()));
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use futures::{SinkExt, StreamExt};
+use tower::{Service, ServiceExt};
+use tower_lsp::LspService;
+use tower_lsp::jsonrpc::{Request, Response};
+
+use super::DataFlexLanguageServer;
+
+/// In-memory client driving the language server through JSON-RPC messages, for testing handlers
+/// end-to-end. Messages sent by the server are captured, and requests from the server are
+/// answered with a `null` result so the server doesn't wait for the client.
+pub struct TestClient {
+    service: LspService<DataFlexLanguageServer>,
+    received: Arc<Mutex<Vec<Request>>>,
+    next_id: i64,
+    _task: tokio::task::JoinHandle<()>,
+}
+
+impl TestClient {
+    pub fn new() -> Self {
+        Self::with_diagnostics_debounce(super::DIAGNOSTICS_DEBOUNCE)
+    }
+
+    /// Creates a client for a server that waits `debounce` for edits to settle before publishing
+    /// diagnostics.
+    pub fn with_diagnostics_debounce(debounce: std::time::Duration) -> Self {
+        let (service, socket) = LspService::new(move |client| {
+            DataFlexLanguageServer::with_diagnostics_debounce(client, debounce)
+        });
+        let (mut requests, mut responses) = socket.split();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let task_received = received.clone();
+        let task = tokio::spawn(async move {
+            while let Some(request) = requests.next().await {
+                if let Some(id) = request.id().cloned() {
+                    _ = responses
+                        .send(Response::from_ok(id, serde_json::Value::Null))
+                        .await;
+                }
+                task_received.lock().unwrap().push(request);
+            }
+        });
+        Self {
+            service,
+            received,
+            next_id: 0,
+            _task: task,
+        }
+    }
+
+    pub fn server(&self) -> &DataFlexLanguageServer {
+        self.service.inner()
+    }
+
+    /// Sends `initialize` and `initialized` for a workspace with the folder as its root.
+    pub async fn initialize(&mut self, workspace_root: &Path) -> Option<serde_json::Value> {
+        self.initialize_with_capabilities(workspace_root, serde_json::json!({}))
+            .await
+    }
+
+    /// Like `initialize()`, with the client capabilities.
+    pub async fn initialize_with_capabilities(
+        &mut self,
+        workspace_root: &Path,
+        capabilities: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        let uri = tower_lsp::lsp_types::Url::from_file_path(workspace_root).ok()?;
+        let result = self
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "capabilities": capabilities,
+                    "clientInfo": { "name": "test" },
+                    "workspaceFolders": [{ "uri": uri, "name": "workspace" }],
+                }),
+            )
+            .await;
+        self.notify("initialized", serde_json::json!({})).await;
+        result
+    }
+
+    /// Sends a request and returns the result, or `None` for an error response.
+    pub async fn request(
+        &mut self,
+        method: &'static str,
+        params: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        self.next_id += 1;
+        let request = Request::build(method)
+            .params(params)
+            .id(self.next_id)
+            .finish();
+        let response = self.call(request).await?;
+        response.into_parts().1.ok()
+    }
+
+    pub async fn shutdown(&mut self) {
+        self.next_id += 1;
+        _ = self
+            .call(Request::build("shutdown").id(self.next_id).finish())
+            .await;
+    }
+
+    pub async fn notify(&mut self, method: &'static str, params: serde_json::Value) {
+        let notification = Request::build(method).params(params).finish();
+        _ = self.call(notification).await;
+    }
+
+    /// The parameters of all messages with the method received from the server so far.
+    pub fn received(&self, method: &str) -> Vec<serde_json::Value> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method() == method)
+            .map(|request| request.params().cloned().unwrap_or_default())
+            .collect()
+    }
+
+    /// Waits for the server to send a message with the method, giving up after a few seconds.
+    pub async fn wait_for(&self, method: &str) -> Option<serde_json::Value> {
+        for _ in 0..500 {
+            if let Some(params) = self.received(method).pop() {
+                return Some(params);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        None
+    }
+
+    async fn call(&mut self, request: Request) -> Option<Response> {
+        self.service
+            .ready()
+            .await
+            .ok()?
+            .call(request)
+            .await
+            .ok()
+            .flatten()
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(test)]
+mod benchmarks;
 mod dataflex_document;
 mod dataflex_parser;
 mod index;
@@ -1,7 +1,9 @@
 use log::LevelFilter;
 use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::index::{IndexerConfig, SymbolMatchMode};
 use crate::logging;
 
 #[derive(Debug, Deserialize)]
@@ -9,6 +11,28 @@ use crate::logging;
 pub struct Settings {
     #[serde(deserialize_with = "Settings::deserialize_log_level")]
     pub log_level: LevelFilter,
+    #[serde(default = "Settings::default_max_semantic_token_lines")]
+    pub max_semantic_token_lines: usize,
+    #[serde(default = "Settings::default_max_completion_items")]
+    pub max_completion_items: usize,
+    #[serde(default = "Settings::default_fallback_completion")]
+    pub fallback_completion: bool,
+    #[serde(default = "Settings::default_trim_trailing_whitespace")]
+    pub trim_trailing_whitespace: bool,
+    #[serde(default = "Settings::default_insert_final_newline")]
+    pub insert_final_newline: bool,
+    #[serde(default = "Settings::default_mismatched_footer_diagnostics")]
+    pub mismatched_footer_diagnostics: bool,
+    #[serde(default = "Settings::default_unknown_class_diagnostics")]
+    pub unknown_class_diagnostics: bool,
+    #[serde(default = "Settings::default_syntax_error_diagnostics")]
+    pub syntax_error_diagnostics: bool,
+    #[serde(default = "Settings::default_inheritance_cycle_diagnostics")]
+    pub inheritance_cycle_diagnostics: bool,
+    #[serde(default = "Settings::default_use_cycle_diagnostics")]
+    pub use_cycle_diagnostics: bool,
+    #[serde(default = "Settings::default_workspace_symbol_matching")]
+    pub workspace_symbol_matching: SymbolMatchMode,
 }
 
 static SETTINGS: std::sync::RwLock<Settings> = std::sync::RwLock::new(Settings::new());
@@ -17,9 +41,65 @@ impl Settings {
     pub const fn new() -> Self {
         Self {
             log_level: LevelFilter::Info,
+            max_semantic_token_lines: Self::default_max_semantic_token_lines(),
+            max_completion_items: Self::default_max_completion_items(),
+            fallback_completion: Self::default_fallback_completion(),
+            trim_trailing_whitespace: Self::default_trim_trailing_whitespace(),
+            insert_final_newline: Self::default_insert_final_newline(),
+            mismatched_footer_diagnostics: Self::default_mismatched_footer_diagnostics(),
+            unknown_class_diagnostics: Self::default_unknown_class_diagnostics(),
+            syntax_error_diagnostics: Self::default_syntax_error_diagnostics(),
+            inheritance_cycle_diagnostics: Self::default_inheritance_cycle_diagnostics(),
+            use_cycle_diagnostics: Self::default_use_cycle_diagnostics(),
+            workspace_symbol_matching: Self::default_workspace_symbol_matching(),
         }
     }
 
+    const fn default_max_semantic_token_lines() -> usize {
+        20_000
+    }
+
+    const fn default_max_completion_items() -> usize {
+        1_000
+    }
+
+    const fn default_fallback_completion() -> bool {
+        false
+    }
+
+    const fn default_trim_trailing_whitespace() -> bool {
+        true
+    }
+
+    const fn default_insert_final_newline() -> bool {
+        true
+    }
+
+    const fn default_mismatched_footer_diagnostics() -> bool {
+        true
+    }
+
+    const fn default_unknown_class_diagnostics() -> bool {
+        true
+    }
+
+    const fn default_syntax_error_diagnostics() -> bool {
+        true
+    }
+
+    const fn default_inheritance_cycle_diagnostics() -> bool {
+        true
+    }
+
+    const fn default_use_cycle_diagnostics() -> bool {
+        // Off by default, since DataFlex allows cyclic Use statements.
+        false
+    }
+
+    const fn default_workspace_symbol_matching() -> SymbolMatchMode {
+        SymbolMatchMode::Fuzzy
+    }
+
     pub fn get() -> impl std::ops::Deref<Target = Self> {
         SETTINGS.read().unwrap()
     }
@@ -43,3 +123,89 @@ impl std::default::Default for Settings {
         Self::new()
     }
 }
+
+/// Options passed as `initializationOptions`. These are read once in `initialize`, since they
+/// change the capabilities advertised to the client.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializationOptions {
+    #[serde(flatten)]
+    pub features: FeatureToggles,
+    #[serde(default = "InitializationOptions::default_completion_commit_characters")]
+    pub completion_commit_characters: Vec<String>,
+    #[serde(default = "InitializationOptions::default_detect_pkg_directories")]
+    pub detect_pkg_directories: bool,
+    #[serde(default = "IndexerConfig::default_install_bases")]
+    pub dataflex_install_bases: Vec<PathBuf>,
+    #[serde(default = "IndexerConfig::default_max_directory_depth")]
+    pub max_directory_depth: usize,
+    #[serde(default = "IndexerConfig::default_index_cache")]
+    pub index_cache: bool,
+    #[serde(default = "IndexerConfig::default_file_extensions")]
+    pub file_extensions: Vec<String>,
+}
+
+impl InitializationOptions {
+    fn default_completion_commit_characters() -> Vec<String> {
+        vec![String::from(" ")]
+    }
+
+    const fn default_detect_pkg_directories() -> bool {
+        true
+    }
+
+    /// The install locations to probe for `Pkg` directories, or none when detection is disabled.
+    pub fn pkg_directory_bases(&self) -> Vec<PathBuf> {
+        if self.detect_pkg_directories {
+            self.dataflex_install_bases.clone()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl std::default::Default for InitializationOptions {
+    fn default() -> Self {
+        Self {
+            features: FeatureToggles::new(),
+            completion_commit_characters: Self::default_completion_commit_characters(),
+            detect_pkg_directories: Self::default_detect_pkg_directories(),
+            dataflex_install_bases: IndexerConfig::default_install_bases(),
+            max_directory_depth: IndexerConfig::default_max_directory_depth(),
+            index_cache: IndexerConfig::default_index_cache(),
+            file_extensions: IndexerConfig::default_file_extensions(),
+        }
+    }
+}
+
+/// Language features that can be turned off through the `initializationOptions`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureToggles {
+    #[serde(default = "FeatureToggles::default_enabled")]
+    pub semantic_tokens: bool,
+    #[serde(default = "FeatureToggles::default_enabled")]
+    pub completion: bool,
+    #[serde(default = "FeatureToggles::default_enabled")]
+    pub diagnostics: bool,
+}
+
+impl FeatureToggles {
+    pub const fn new() -> Self {
+        Self {
+            semantic_tokens: Self::default_enabled(),
+            completion: Self::default_enabled(),
+            diagnostics: Self::default_enabled(),
+        }
+    }
+
+    const fn default_enabled() -> bool {
+        true
+    }
+}
+
+impl std::default::Default for FeatureToggles {
+    fn default() -> Self {
+        Self::new()
+    }
+}